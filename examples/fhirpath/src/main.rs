@@ -52,21 +52,21 @@ fn main() -> Result<()> {
 }
 
 fn test_fhirpath_2(patient: &Patient) -> Result<()> {
-    let mut expr = Expr::parse("Patient.name.given.allTrue()".to_string())?;
-    let rs = patient.assert(&mut expr)?;
+    let expr = Expr::parse("Patient.name.exists(given = 'Tom')".to_string())?;
+    let rs = expr.eval(patient)?.to_boolean()?;
     
-    println!("Eval Result : {}", rs);
+    println!("Eval Result : {:?}", rs);
 
     Ok(())
 }
 
 fn test_fhirpath_3(patient: &Patient) -> Result<()> {
-    let mut expr = Expr::parse("Patient.name.given.allTrue()".to_string())?;
-    let collection = patient.path(&mut expr)?;
+    let expr = Expr::parse("Patient.name.given.distinct()".to_string())?;
+    let collection = expr.eval(patient)?;
 
     println!("Result count: {}", &collection.count());
 
-    for item in collection.0 {
+    for item in collection.iter() {
         println!("Item: {:?}", item);
     }
     
@@ -173,14 +173,24 @@ pub fn impl_fhirpath_map(struct_fields: &Vec<Field>) -> syn::Result<Vec<proc_mac
             maps.push(quote::quote!( #ident_literal => Ok(self.#ident.to_collection(index)), ));
         });
 
+    struct_fields.iter()
+        .filter_map(helper::impl_fhirpath_choice_map)
+        .for_each(|map| maps.push(map));
+
     Ok(maps)
 }
 
 pub fn impl_fhirpath(struct_name_ident: &syn::Ident, struct_fields: &Vec<Field>) -> syn::Result<proc_macro2::TokenStream> {
     let struct_name_literal = struct_name_ident.to_string();
     let maps = impl_fhirpath_map(struct_fields)?;
+    let children = helper::impl_fhirpath_children(struct_fields);
+    let reflect = helper::impl_reflect(struct_name_ident, struct_fields, "Complex");
+    let patch = helper::impl_patch(struct_name_ident, struct_fields);
 
     let ret = quote::quote!(
+        #reflect
+        #patch
+
         impl Executor for #struct_name_ident {
             // fn exec(&self, comp: &PathComponent) -> Result<PathResponse> {
             //     println!("{}: Start Exec Fhirpath...", #struct_name_literal);
@@ -199,8 +209,8 @@ pub fn impl_fhirpath(struct_name_ident: &syn::Ident, struct_fields: &Vec<Field>)
             // fn as_collection(&self) -> Collection {
             //     Collection(vec![Box::new(self.clone())])
             // }
-            fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection> {
-                 println!("{}: Element[{}]...", #struct_name_literal, &symbol);
+            fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection<'_>> {
+                 debug!("{}: Element[{}]...", #struct_name_literal, &symbol);
 
                 match symbol.as_str() {
                     #( #maps )*
@@ -208,8 +218,14 @@ pub fn impl_fhirpath(struct_name_ident: &syn::Ident, struct_fields: &Vec<Field>)
                 }
             }
 
-            fn to_collection(&self, index: &Option<usize>) -> Collection {
-                Collection::new_any(Box::new(self.clone()))
+            fn to_collection(&self, index: &Option<usize>) -> Collection<'_> {
+                Collection::new_ref(self)
+            }
+
+            fn children(&self) -> Collection<'_> {
+                let mut children = Collection::new();
+                #( #children )*
+                children
             }
         }
 
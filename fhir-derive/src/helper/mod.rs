@@ -72,6 +72,126 @@ impl From<&syn::Field> for Field {
     }
 }
 
+/// 可选类型字段带类型后缀的路径，如`valueQuantity`，只有当字段的实际类型与后缀一致时才返回该字段
+pub(crate) fn impl_fhirpath_choice_map(field: &Field) -> Option<proc_macro2::TokenStream> {
+    if field.choice.is_empty() {
+        return None
+    }
+
+    let ident = &field.name;
+    let ident_literal = &field.original;
+    let ident_len = ident_literal.len();
+    let choices: Vec<&str> = field.choice.split("|").collect();
+
+    Some(quote::quote!(
+        k_value if k_value.starts_with(#ident_literal) && [#( #choices ),*].contains(&&k_value[#ident_len..]) => {
+            match &self.#ident {
+                Some(value) if value.choice_suffix() == &k_value[#ident_len..] => Ok(value.to_collection(index)),
+                _ => Ok(Collection::new()),
+            }
+        },
+    ))
+}
+
+/// 所有字段的元素，用于FHIRPath的children()函数
+pub(crate) fn impl_fhirpath_children(struct_fields: &[Field]) -> Vec<proc_macro2::TokenStream> {
+    struct_fields.iter()
+        .map(|field| {
+            let ident = &field.name;
+            quote::quote!( children.combine(self.#ident.to_collection(&None)); )
+        })
+        .collect()
+}
+
+/// 类型的元数据，用于FHIRPath表达式的静态分析
+pub(crate) fn impl_reflect(struct_name_ident: &syn::Ident, struct_fields: &[Field], kind: &str) -> proc_macro2::TokenStream {
+    let struct_name_literal = struct_name_ident.to_string();
+    let kind = syn::Ident::new(kind, struct_name_ident.span());
+
+    let elements: Vec<proc_macro2::TokenStream> = struct_fields.iter()
+        .map(|field| {
+            let name_literal = &field.original;
+            let min: usize = field.min.parse().unwrap_or(0);
+            let max = match field.max.as_str() {
+                "*" => quote::quote!(None),
+                max => {
+                    let max: usize = max.parse().unwrap_or(1);
+                    quote::quote!(Some(#max))
+                },
+            };
+            let choices: Vec<&str> = field.choice.split('|').filter(|choice| !choice.is_empty()).collect();
+            let element_type = element_type(&field.ty);
+
+            quote::quote!(
+                ElementInfo { name: #name_literal, min: #min, max: #max, choices: &[#( #choices ),*], type_info: <#element_type as Reflect>::type_info },
+            )
+        })
+        .collect();
+
+    quote::quote!(
+        impl Reflect for #struct_name_ident {
+            fn type_info() -> &'static TypeInfo {
+                static TYPE_INFO: TypeInfo = TypeInfo {
+                    name: #struct_name_literal,
+                    kind: TypeKind::#kind,
+                    elements: &[ #( #elements )* ],
+                };
+                &TYPE_INFO
+            }
+        }
+    )
+}
+
+/// 可修改的字段，用于FHIRPath Patch
+pub(crate) fn impl_patch(struct_name_ident: &syn::Ident, struct_fields: &[Field]) -> proc_macro2::TokenStream {
+    let fields: Vec<proc_macro2::TokenStream> = struct_fields.iter()
+        .map(|field| {
+            let ident = &field.name;
+            let name_literal = &field.original;
+            let value_type = option_inner(&field.ty).unwrap_or(&field.ty);
+            if vector_inner(value_type).is_some() {
+                quote::quote!( Box::new(ListField::new(#name_literal, &mut self.#ident)), )
+            } else {
+                quote::quote!( Box::new(SingleField::new(#name_literal, &mut self.#ident)), )
+            }
+        })
+        .collect();
+
+    quote::quote!(
+        impl Patch for #struct_name_ident {
+            fn fields_mut(&mut self) -> Vec<Box<dyn PatchField<'_> + '_>> {
+                vec![ #( #fields )* ]
+            }
+        }
+
+        impl PatchItem for #struct_name_ident {
+            fn from_patch(value: &PatchValue) -> Result<Self> {
+                value.to_element(Self::default)
+            }
+
+            fn as_patch(&self) -> &dyn Patch {
+                self
+            }
+
+            fn as_patch_mut(&mut self) -> &mut dyn Patch {
+                self
+            }
+        }
+    )
+}
+
+/// 字段中单个元素的类型，去掉外层的Option、Vec和Box
+fn element_type(field_type: &syn::Type) -> &syn::Type {
+    let mut typ = option_inner(field_type).unwrap_or(field_type);
+    if let Some(inner) = vector_inner(typ) {
+        typ = inner;
+    }
+    if let Some(inner) = box_inner(typ) {
+        typ = inner;
+    }
+    typ
+}
+
 pub(crate) fn get_struct_fields(st: &syn::DeriveInput) -> syn::Result<Vec<Field>> {
     if let syn::Data::Struct(syn::DataStruct{
                                  fields: syn::Fields::Named(syn::FieldsNamed{named, ..}),
@@ -136,6 +256,24 @@ pub(crate) fn vector_inner(field_type: &syn::Type) -> Option<&syn::Type> {
     None
 }
 
+pub(crate) fn box_inner(field_type: &syn::Type) -> Option<&syn::Type> {
+    if let syn::Type::Path(syn::TypePath { path: syn::Path { ref segments, .. }, ..}) = field_type
+    {
+        if let Some(seg) = segments.last() {
+            if seg.ident == "Box" {
+                if let syn::PathArguments::AngleBracketed(
+                    syn::AngleBracketedGenericArguments{ref args, ..}
+                ) = seg.arguments {
+                    if let Some(syn::GenericArgument::Type(inner_type)) = args.first() {
+                        return Some(inner_type)
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
 pub(crate) fn is_primitive(field_type: &syn::Type) -> bool {
     if let syn::Type::Path(syn::TypePath { path: syn::Path { ref segments, .. }, ..}) = field_type
     {
@@ -0,0 +1,55 @@
+//! FHIRPath表达式在大型Bundle上的计算性能
+//!
+//! `borrowed`是当前的实现，集合中的元素直接引用资源中的节点；
+//! `cloned`模拟之前的实现，导航时把经过的每个节点都复制一份。
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use fhir_rs::prelude::*;
+
+fn bundle(size: usize) -> Result<Bundle> {
+    let mut entries = vec![];
+    for _ in 0..size {
+        let patient: Patient = from_xml(include_str!("../tests/patient-example.xml"))?;
+        entries.push(BundleEntryBackboneElement {
+            resource: Some(AnyResource::Patient(patient)),
+            ..Default::default()
+        });
+    }
+
+    Ok(Bundle { entry: Some(entries), ..Default::default() })
+}
+
+/// 每一步导航都复制经过的节点，与之前`Executor::to_collection`的行为相同
+fn eval_cloned(bundle: &Bundle, path: &[&str]) -> Result<usize> {
+    let mut collection = Collection::new_ref(bundle).into_owned();
+    for symbol in path {
+        collection = collection.element(&symbol.to_string(), &None)?.into_owned();
+    }
+
+    Ok(collection.count())
+}
+
+fn eval_borrowed(bundle: &Bundle, path: &CompiledPath) -> Result<usize> {
+    Ok(path.eval(bundle)?.count())
+}
+
+fn navigation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Bundle.entry.resource.name.given");
+    let path = CompiledPath::compile("Bundle.entry.resource.name.given").unwrap();
+
+    for size in [10, 100, 1000] {
+        let bundle = bundle(size).unwrap();
+        assert_eq!(eval_borrowed(&bundle, &path).unwrap(), eval_cloned(&bundle, &["entry", "resource", "name", "given"]).unwrap());
+
+        group.bench_with_input(BenchmarkId::new("borrowed", size), &bundle, |b, bundle| {
+            b.iter(|| eval_borrowed(black_box(bundle), &path).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("cloned", size), &bundle, |b, bundle| {
+            b.iter(|| eval_cloned(black_box(bundle), &["entry", "resource", "name", "given"]).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, navigation);
+criterion_main!(benches);
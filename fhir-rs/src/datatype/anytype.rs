@@ -9,6 +9,40 @@ macro_rules! anytype {
             $($id($ty),)+
         }
 
+        impl AnyType {
+            /// 可选类型（choice）字段名称的类型后缀，如`valueQuantity`中的`Quantity`
+            pub fn choice_suffix(&self) -> &'static str {
+                match self {
+                    $(AnyType::$id(_) => stringify!($id),)+
+                }
+            }
+
+            /// 实际取值，用于按具体类型取出值
+            pub fn as_any(&self) -> &dyn std::any::Any {
+                match self {
+                    $(AnyType::$id(value) => value,)+
+                }
+            }
+
+            /// 所有可选类型的类型后缀
+            pub const CHOICES: &'static [&'static str] = &[$(stringify!($id),)+];
+
+            /// 类型后缀对应的类型元数据，如`Quantity`对应`Quantity`，`String`对应`StringDt`
+            pub fn choice_type_info(suffix: &str) -> Option<&'static TypeInfo> {
+                match suffix {
+                    $(stringify!($id) => Some(<$ty as Reflect>::type_info()),)+
+                    _ => None,
+                }
+            }
+        }
+
+        impl Reflect for AnyType {
+            fn type_info() -> &'static TypeInfo {
+                static TYPE_INFO: TypeInfo = TypeInfo { name: "AnyType", kind: TypeKind::Choice, elements: &[] };
+                &TYPE_INFO
+            }
+        }
+
         impl Base for AnyType {
             fn type_name(&self) -> &str {
                 match self {
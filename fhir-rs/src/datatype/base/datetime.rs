@@ -3,8 +3,22 @@ use std::str::FromStr;
 use chrono::{FixedOffset, Local};
 use crate::error::FhirError;
 
+/// 日期时间的精度
+///
+/// 比较两个日期时间时，只能在双方都具有的精度范围内进行比较
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precision {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Millisecond,
+}
+
 #[derive(Clone, Debug)]
-pub struct Date(chrono::NaiveDate, usize);
+pub struct Date(pub(crate) chrono::NaiveDate, pub(crate) usize);
 
 impl From<chrono::DateTime<Local>> for Date {
     fn from(value: chrono::DateTime<Local>) -> Self {
@@ -12,6 +26,16 @@ impl From<chrono::DateTime<Local>> for Date {
     }
 }
 
+impl Date {
+    pub fn precision(&self) -> Precision {
+        match self.1 {
+            4 => Precision::Year,
+            7 => Precision::Month,
+            _ => Precision::Day,
+        }
+    }
+}
+
 impl FromStr for Date {
     type Err = FhirError;
 
@@ -40,7 +64,7 @@ impl Display for Date {
 }
 
 #[derive(Clone, Debug)]
-pub struct Time(chrono::NaiveTime, usize);
+pub struct Time(pub(crate) chrono::NaiveTime, pub(crate) usize);
 
 impl From<chrono::DateTime<Local>> for Time {
     fn from(value: chrono::DateTime<Local>) -> Self {
@@ -48,26 +72,42 @@ impl From<chrono::DateTime<Local>> for Time {
     }
 }
 
+impl Time {
+    pub fn precision(&self) -> Precision {
+        match self.1 {
+            2 => Precision::Hour,
+            5 => Precision::Minute,
+            8 => Precision::Second,
+            _ => Precision::Millisecond,
+        }
+    }
+}
+
 impl Display for Time {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self.1 {
+            2 => {write!(f, "{}", self.0.format("%H"))},
+            5 => {write!(f, "{}", self.0.format("%H:%M"))},
             8 => {write!(f, "{}", self.0.format("%H:%M:%S"))},
-            12 => {write!(f, "{}", self.0.format("%H:%M:%S.%3f"))},
+            12 => {write!(f, "{}", self.0.format("%H:%M:%S%.3f"))},
             _ => {write!(f, "Error")}
         }
     }
 }
 
+/// 接受HH、HH:MM、HH:MM:SS、HH:MM:SS.sss四种格式，秒的小数部分统一保留到毫秒
 impl FromStr for Time {
     type Err = FhirError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        let precision = s.len();
-        let val = match precision {
-            8 => {chrono::NaiveTime::parse_from_str(s, "%H:%M:%S")?},
-            12 => {chrono::NaiveTime::parse_from_str(s, "%H:%M:%S.%3f")?},
-            _ => {return Err(FhirError::error("错误的时间格式，只接受HH::MM::SS,HH:MM:SS.sss两种格式"));}
+        let (val, precision) = match s.len() {
+            2 => (format!("{}:00:00", s), 2),
+            5 => (format!("{}:00", s), 5),
+            8 => (s.to_string(), 8),
+            len if len > 9 && s.as_bytes()[8] == b'.' => (s.to_string(), 12),
+            _ => {return Err(FhirError::error("错误的时间格式，只接受HH,HH:MM,HH:MM:SS,HH:MM:SS.sss四种格式"));}
         };
+        let val = chrono::NaiveTime::parse_from_str(val.as_str(), "%H:%M:%S%.f")?;
         Ok(Time(val, precision))
     }
 }
@@ -80,52 +120,145 @@ impl FromStr for Time {
 /// * 年 - 2023
 /// * 年月 - 2023-08
 /// * 年月日 - 2023-08-17
+/// * 年月日时 - 2023-08-17T08
+/// * 年月日时分 - 2023-08-17T08:21
 /// * 年月日时分秒 - 2023-08-17T08:21:45
 /// * 年月日时分秒(毫秒) - 2023-08-17T08:21:45.234
-/// * 年月日时分秒(毫秒)时区 - 2023-08-17T08:21:45.234+08:00
+/// * 以上含有时间的格式都可以带时区 - 2023-08-17T08:21:45.234+08:00
+///
+/// 三个字段依次为：时间值、不含时区部分的长度（代表精度）、时区的写法。
+/// 没有指定时区的时间按UTC处理。
 #[derive(Clone, Debug)]
-pub struct DateTime(chrono::DateTime<FixedOffset>, usize);
+pub struct DateTime(pub(crate) chrono::DateTime<FixedOffset>, pub(crate) usize, pub(crate) Timezone);
+
+/// 日期时间中时区的写法，输出时保持与输入一致
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Timezone {
+    /// 未指定时区
+    Unspecified,
+    /// 以`Z`表示的UTC
+    Utc,
+    /// 以`+HH:MM`、`-HH:MM`表示的时区，包括`+00:00`
+    Offset,
+}
 
 impl From<chrono::DateTime<Local>> for DateTime {
     fn from(value: chrono::DateTime<Local>) -> Self {
-        Self(value.into(), 29)
+        Self(value.into(), 23, Timezone::Offset)
+    }
+}
+
+impl From<Date> for DateTime {
+    fn from(value: Date) -> Self {
+        let val = value.0.and_time(chrono::NaiveTime::MIN).and_utc().fixed_offset();
+        Self(val, value.1, Timezone::Unspecified)
+    }
+}
+
+impl From<Instant> for DateTime {
+    fn from(value: Instant) -> Self {
+        let precision = match value.1 {
+            20|25 => 19,
+            _ => 23,
+        };
+        Self(value.0, precision, Timezone::Offset)
+    }
+}
+
+impl DateTime {
+    pub fn precision(&self) -> Precision {
+        match self.1 {
+            4 => Precision::Year,
+            7 => Precision::Month,
+            10 => Precision::Day,
+            13 => Precision::Hour,
+            16 => Precision::Minute,
+            19 => Precision::Second,
+            _ => Precision::Millisecond,
+        }
+    }
+
+    /// 是否指定了时区
+    pub fn has_timezone(&self) -> bool {
+        self.2 != Timezone::Unspecified
     }
 }
 
 impl Display for DateTime {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self.1 {
-            4 => {write!(f, "{}", self.0.format("%Y"))},
-            7 => {write!(f, "{}", self.0.format("%Y-%m"))},
-            10 => {write!(f, "{}", self.0.format("%Y-%m-%d"))},
-            20|25 => {write!(f, "{}", self.0.format("%Y-%m-%dT%H:%M:%S%:z"))},
-            24|29 => {write!(f, "{}", self.0.format("%Y-%m-%dT%H:%M:%S.%3f%:z"))},
-            _ => {write!(f, "Error")}
+        let format = match self.1 {
+            4 => "%Y",
+            7 => "%Y-%m",
+            10 => "%Y-%m-%d",
+            13 => "%Y-%m-%dT%H",
+            16 => "%Y-%m-%dT%H:%M",
+            19 => "%Y-%m-%dT%H:%M:%S",
+            23 => "%Y-%m-%dT%H:%M:%S%.3f",
+            _ => return write!(f, "Error"),
+        };
+        write!(f, "{}", self.0.format(format))?;
+
+        match self.2 {
+            Timezone::Utc if self.1 > 10 => write!(f, "Z"),
+            Timezone::Offset if self.1 > 10 => write!(f, "{}", self.0.format("%:z")),
+            _ => Ok(()),
         }
     }
 }
 
+/// 除了FHIR规范约定的格式，还接受FHIRPath中只精确到时、分的格式以及以T结尾的日期，如`2015T`
 impl FromStr for DateTime {
     type Err = FhirError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        let pattern = "0000-01-01T00:00:00.000Z";
-        let precision = s.len();
-        let val = match precision {
-            4|7|10|24 => {
-                let dt = format!("{}{}", s, &pattern[precision..]);
-                chrono::DateTime::<chrono::Local>::from_str(dt.as_str())?.into()
-            },
-            20 => {
-                let dt = format!("{}{}", &s[0..precision-1], &pattern[precision-1..]);
-                chrono::DateTime::<chrono::Local>::from_str(dt.as_str())?.into()
-            },
-            25|29 => {
-                chrono::DateTime::from_str(s)?
-            },
+        let s = s.strip_suffix('T').unwrap_or(s);
+        let (local, offset) = split_timezone(s)?;
+
+        let pattern = "0000-01-01T00:00:00";
+        let (val, precision) = match local.len() {
+            len @ (4|7|10|13|16|19) => (format!("{}{}", local, &pattern[len..]), len),
+            len if len > 20 && local.as_bytes()[19] == b'.' => (local.to_string(), 23),
             _ => {return Err(FhirError::error("错误的时间格式，只接受FHIR规范约定的日期时间格式"));}
         };
-        Ok(DateTime(val, precision))
+        if offset.is_some() && precision <= 10 {
+            return Err(FhirError::error("错误的时间格式，只有含有时间的日期时间才能指定时区"));
+        }
+
+        let val = chrono::NaiveDateTime::parse_from_str(val.as_str(), "%Y-%m-%dT%H:%M:%S%.f")?;
+        let val = match offset {
+            Some(offset) => val.and_local_timezone(offset).single()
+                .ok_or_else(|| FhirError::error("错误的时间格式，无效的本地时间"))?,
+            None => val.and_utc().fixed_offset(),
+        };
+        let timezone = match offset {
+            Some(_) if s.ends_with('Z') => Timezone::Utc,
+            Some(_) => Timezone::Offset,
+            None => Timezone::Unspecified,
+        };
+        Ok(DateTime(val, precision, timezone))
+    }
+}
+
+/// 拆分日期时间中的时区，时区只能出现在时间部分之后，如`Z`、`+08:00`
+fn split_timezone(s: &str) -> std::result::Result<(&str, Option<FixedOffset>), FhirError> {
+    if let Some(local) = s.strip_suffix('Z') {
+        return Ok((local, FixedOffset::east_opt(0)))
+    }
+
+    let pos = match s.find('T').and_then(|t| s[t..].rfind(['+', '-']).map(|pos| t + pos)) {
+        Some(pos) => pos,
+        None => return Ok((s, None)),
+    };
+
+    let (local, zone) = s.split_at(pos);
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let (hours, minutes) = zone[1..].split_once(':')
+        .filter(|(hours, minutes)| hours.len() == 2 && minutes.len() == 2)
+        .ok_or_else(|| FhirError::error("错误的时区格式，只接受Z或者+HH:MM、-HH:MM"))?;
+    let seconds = hours.parse::<i32>()? * 3600 + minutes.parse::<i32>()? * 60;
+    match FixedOffset::east_opt(sign * seconds) {
+        Some(offset) => Ok((local, Some(offset))),
+        None => Err(FhirError::error("错误的时区格式，只接受Z或者+HH:MM、-HH:MM")),
     }
 }
 
@@ -144,7 +277,7 @@ impl FromStr for DateTime {
 /// * 2009-12-24T07:12:45.234+08:00
 /// 导入之后，会根据当地的时区将Z转换为对应的时区
 #[derive(Clone, Debug)]
-pub struct Instant(chrono::DateTime<FixedOffset>, usize);
+pub struct Instant(pub(crate) chrono::DateTime<FixedOffset>, pub(crate) usize);
 
 impl From<chrono::DateTime<Local>> for Instant {
     fn from(value: chrono::DateTime<Local>) -> Self {
@@ -198,3 +331,25 @@ fn test_datetime() -> crate::prelude::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_datetime_precision() -> crate::prelude::Result<()> {
+    for value in ["2015", "2015-02", "2015-02-04", "2015-02-04T14", "2015-02-04T14:34", "2015-02-04T14:34:28",
+                  "2015-02-04T14:34:28.123", "2015-02-04T14+08:00", "2015-02-04T14:34:28-05:30"] {
+        assert_eq!(value.parse::<DateTime>()?.to_string(), value);
+    }
+    assert_eq!("2015T".parse::<DateTime>()?.to_string(), "2015");
+    assert_eq!("2015-02-04T14:34:28Z".parse::<DateTime>()?.to_string(), "2015-02-04T14:34:28Z");
+    assert_eq!("2015-02-04T14:34:28.123Z".parse::<DateTime>()?.to_string(), "2015-02-04T14:34:28.123Z");
+    assert_eq!("2015-02-04T14:34:28+00:00".parse::<DateTime>()?.to_string(), "2015-02-04T14:34:28+00:00");
+    assert_eq!("2015-02-04T14:34".parse::<DateTime>()?.precision(), Precision::Minute);
+    assert!("2015-02-04+08:00".parse::<DateTime>().is_err());
+    assert!("2015-02-04T14:3".parse::<DateTime>().is_err());
+
+    for value in ["14", "14:34", "14:34:28", "14:34:28.123"] {
+        assert_eq!(value.parse::<Time>()?.to_string(), value);
+    }
+    assert_eq!("14".parse::<Time>()?.precision(), Precision::Hour);
+    assert!("25".parse::<Time>().is_err());
+    Ok(())
+}
@@ -654,10 +654,10 @@ pub struct ElementDefinition {
     #[fhir(name="orderMeaning", min="0", max="1", summary=true, modifier=false, choice="")]
     pub order_meaning: Option<StringDt>,
     /// Value must be exactly this
-    #[fhir(name="fixed", min="0", max="1", summary=true, modifier=false, choice="")]
-    pub fixed: Option<Meta>,
+    #[fhir(name="fixed", min="0", max="1", summary=true, modifier=false, choice="String|Id|Base64Binary|Markdown|Uri|Url|Oid|Uuid|Canonical|Code|Boolean|DateTime|Date|Time|Instant|UnsignedInt|PositiveInt|Integer|Integer64|Decimal|Address|Age|Annotation|Attachment|CodeableConcept|CodeableReference|Coding|ContactPoint|Count|Distance|Duration|HumanName|Identifier|Money|Period|Quantity|Range|Ratio|RatioRange|Reference|SampledData|Signature|Timing|ContactDetail|DataRequirement|Expression|ParameterDefinition|RelatedArtifact|TriggerDefinition|UsageContext|Availability|ExtendedContactDetail|Dosage|Meta")]
+    pub fixed: Option<AnyType>,
     /// Value must have at least these property values
-    #[fhir(name="pattern", min="0", max="1", summary=true, modifier=false, choice="Uri|CodeableConcept")]
+    #[fhir(name="pattern", min="0", max="1", summary=true, modifier=false, choice="String|Id|Base64Binary|Markdown|Uri|Url|Oid|Uuid|Canonical|Code|Boolean|DateTime|Date|Time|Instant|UnsignedInt|PositiveInt|Integer|Integer64|Decimal|Address|Age|Annotation|Attachment|CodeableConcept|CodeableReference|Coding|ContactPoint|Count|Distance|Duration|HumanName|Identifier|Money|Period|Quantity|Range|Ratio|RatioRange|Reference|SampledData|Signature|Timing|ContactDetail|DataRequirement|Expression|ParameterDefinition|RelatedArtifact|TriggerDefinition|UsageContext|Availability|ExtendedContactDetail|Dosage|Meta")]
     pub pattern: Option<AnyType>,
     /// Example value (as defined for type)
     #[fhir(name="example", min="0", max="*", summary=true, modifier=false, choice="")]
@@ -31,8 +31,48 @@ impl Extension {
 }
 
 impl Executor for Extension {
-    fn to_collection(&self, _index: &Option<usize>) -> Collection {
-        Collection::new_any(Box::new(self.clone()))
+    fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection<'_>> {
+        match symbol.as_str() {
+            "id" => Ok(self.id.to_collection(index)),
+            "extension" => Ok(self.extension.to_collection(index)),
+            "url" => Ok(self.url.to_collection(index)),
+            "value" => Ok(self.value.to_collection(index)),
+            other => {
+                match (other.strip_prefix("value"), &self.value) {
+                    (Some(suffix), Some(value)) if value.choice_suffix() == suffix => Ok(value.to_collection(index)),
+                    (Some(_), _) => Ok(Collection::new()),
+                    (None, _) => Err(FhirError::Message(format!("在类型Extension中发现无效的路径[{}]", other))),
+                }
+            },
+        }
+    }
+
+    fn to_collection(&self, _index: &Option<usize>) -> Collection<'_> {
+        Collection::new_ref(self)
+    }
+
+    fn children(&self) -> Collection<'_> {
+        let mut children = self.id.to_collection(&None);
+        children.combine(self.extension.to_collection(&None));
+        children.combine(self.url.to_collection(&None));
+        children.combine(self.value.to_collection(&None));
+        children
+    }
+}
+
+impl Reflect for Extension {
+    fn type_info() -> &'static TypeInfo {
+        static TYPE_INFO: TypeInfo = TypeInfo {
+            name: "Extension",
+            kind: TypeKind::Complex,
+            elements: &[
+                ElementInfo { name: "id", min: 0, max: Some(1), choices: &[], type_info: <String as Reflect>::type_info },
+                ElementInfo { name: "extension", min: 0, max: None, choices: &[], type_info: <Extension as Reflect>::type_info },
+                ElementInfo { name: "url", min: 1, max: Some(1), choices: &[], type_info: <String as Reflect>::type_info },
+                ElementInfo { name: "value", min: 0, max: Some(1), choices: AnyType::CHOICES, type_info: <AnyType as Reflect>::type_info },
+            ],
+        };
+        &TYPE_INFO
     }
 }
 
@@ -155,4 +195,28 @@ impl_deserialize_for_extension!{
     (ExtendedContactDetail, ExtendedContactDetail, "valueExtendedContactDetail"),
     (Dosage, Dosage, "valueDosage"),
     (Meta, Meta, "valueMeta"),
-}
\ No newline at end of file
+}
+impl Patch for Extension {
+    fn fields_mut(&mut self) -> Vec<Box<dyn PatchField<'_> + '_>> {
+        vec![
+            Box::new(SingleField::new("id", &mut self.id)),
+            Box::new(ListField::new("extension", &mut self.extension)),
+            Box::new(SingleField::new("url", &mut self.url)),
+            Box::new(SingleField::new("value", &mut self.value)),
+        ]
+    }
+}
+
+impl PatchItem for Extension {
+    fn from_patch(value: &PatchValue) -> Result<Self> {
+        value.to_element(Extension::default)
+    }
+
+    fn as_patch(&self) -> &dyn Patch {
+        self
+    }
+
+    fn as_patch_mut(&mut self) -> &mut dyn Patch {
+        self
+    }
+}
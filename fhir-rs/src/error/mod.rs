@@ -1,12 +1,15 @@
 use std::fmt;
 use std::fmt::{Debug, Display};
+use std::ops::Range;
 use std::string::FromUtf8Error;
+use crate::fhirpath4::PathError;
 
 pub enum FhirError {
     Message(String),
     UnImplementation(String),
     EndArrayWhileParsingList,
     FromStrError(String),
+    FhirPath(PathError),
 }
 
 impl FhirError {
@@ -21,6 +24,30 @@ impl FhirError {
     pub fn un_implementation(msg: &str) -> Self {
         FhirError::UnImplementation(String::from(msg))
     }
+
+    /// 为FHIRPath的错误记录出错位置，已经记录了位置的错误保持不变
+    pub fn at(self, span: Range<usize>) -> Self {
+        match self {
+            FhirError::Message(msg) | FhirError::FromStrError(msg) | FhirError::UnImplementation(msg) => {
+                FhirError::FhirPath(PathError::new(msg, span))
+            },
+            other => other,
+        }
+    }
+
+    /// 为FHIRPath的错误记录出错的表达式，用于输出诊断信息
+    pub fn with_expression(self, expression: &str) -> Self {
+        match self {
+            FhirError::FhirPath(err) => FhirError::FhirPath(err.with_expression(expression)),
+            other => other,
+        }
+    }
+}
+
+impl From<PathError> for FhirError {
+    fn from(value: PathError) -> Self {
+        FhirError::FhirPath(value)
+    }
 }
 
 impl From<std::io::Error> for FhirError {
@@ -77,6 +104,18 @@ impl From<FromUtf8Error> for FhirError {
     }
 }
 
+impl From<regex::Error> for FhirError {
+    fn from(value: regex::Error) -> Self {
+        FhirError::Message(value.to_string())
+    }
+}
+
+impl From<base64::DecodeError> for FhirError {
+    fn from(value: base64::DecodeError) -> Self {
+        FhirError::FromStrError(value.to_string())
+    }
+}
+
 impl Debug for FhirError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -84,6 +123,7 @@ impl Debug for FhirError {
             FhirError::UnImplementation(func) => write!(f, "函数未实现: {:?}", func),
             FhirError::EndArrayWhileParsingList => write!(f, "解析数组时遇到数组结束符号"),
             FhirError::FromStrError(msg) => write!(f, "字符串转换错误: {:?}", msg),
+            FhirError::FhirPath(err) => write!(f, "FHIRPath错误: {}", err),
         }
     }
 }
@@ -0,0 +1,494 @@
+//! FHIRPath表达式的静态分析
+//!
+//! 根据类型的元数据（[`TypeInfo`]）检查表达式中的路径是否有效、函数的参数个数是否正确，
+//! 并推断表达式结果的类型和基数，不需要实际的资源实例。
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use crate::prelude::{Result, FhirError, StructureDefinition, Quantity, Extension, Date, DateTime, Time};
+use super::*;
+
+/// 表达式结果的类型和基数
+#[derive(Debug, Clone)]
+pub struct PathType {
+    /// 结果可能的类型，为空时表示无法推断
+    pub types: Vec<&'static TypeInfo>,
+    pub min: usize,
+    /// 最大基数，`None`表示`*`
+    pub max: Option<usize>,
+    /// 结果在资源中的路径，如`Patient.contact.name`，用于匹配StructureDefinition中的元素
+    path: Option<String>,
+}
+
+impl PathType {
+    pub fn new(types: Vec<&'static TypeInfo>, min: usize, max: Option<usize>) -> Self {
+        PathType { types, min, max, path: None }
+    }
+
+    /// 只有一个元素的结果
+    pub fn single(type_info: &'static TypeInfo) -> Self {
+        PathType::new(vec![type_info], 1, Some(1))
+    }
+
+    /// 最多只有一个元素的结果
+    pub fn optional(type_info: &'static TypeInfo) -> Self {
+        PathType::new(vec![type_info], 0, Some(1))
+    }
+
+    /// 无法推断类型的结果
+    pub fn unknown() -> Self {
+        PathType::new(vec![], 0, None)
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// 结果的类型名称，如`string`、`HumanName`
+    pub fn type_names(&self) -> Vec<String> {
+        self.types.iter().map(|type_info| type_info.fhirpath_name()).collect()
+    }
+
+    /// 集合中的单个元素，用于迭代函数的参数（`$this`）
+    fn item(&self) -> PathType {
+        PathType { types: self.types.clone(), min: 1, max: Some(1), path: self.path.clone() }
+    }
+
+    /// 保持元素类型不变，只改变基数
+    fn with_cardinality(&self, min: usize, max: Option<usize>) -> PathType {
+        PathType { types: self.types.clone(), min, max, path: self.path.clone() }
+    }
+}
+
+impl Display for PathType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_unknown() {
+            write!(f, "Any")?;
+        } else {
+            write!(f, "{}", self.type_names().join(" | "))?;
+        }
+
+        match self.max {
+            Some(max) => write!(f, "[{}..{}]", self.min, max),
+            None => write!(f, "[{}..*]", self.min),
+        }
+    }
+}
+
+/// 静态分析的结果
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    /// 推断出的表达式结果
+    pub result: PathType,
+    /// 发现的错误，如无效的路径、错误的参数个数
+    pub errors: Vec<PathError>,
+}
+
+impl Analysis {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// 以某个类型为根节点，对表达式进行静态分析
+#[derive(Debug, Clone)]
+pub struct Analyzer {
+    root: &'static TypeInfo,
+    /// StructureDefinition中约束后的基数，键为元素的路径，如`Patient.name`
+    cardinalities: HashMap<String, (usize, Option<usize>)>,
+    /// 用户定义的函数，只检查参数个数，结果的类型未知
+    functions: FunctionRegistry,
+}
+
+impl Analyzer {
+    pub fn new(root: &'static TypeInfo) -> Self {
+        Analyzer { root, cardinalities: HashMap::new(), functions: FunctionRegistry::new() }
+    }
+
+    /// 设置用户定义的函数，与计算时传给[`EvalContext::with_functions`]的注册表一致
+    pub fn with_functions(mut self, functions: &FunctionRegistry) -> Self {
+        self.functions = functions.clone();
+        self
+    }
+
+    /// 根据类型名称创建分析器，如`Patient`、`HumanName`
+    pub fn for_type(name: &str) -> Result<Self> {
+        TypeInfo::lookup(name)
+            .map(Analyzer::new)
+            .ok_or_else(|| FhirError::Message(format!("无效的类型[{}]", name)))
+    }
+
+    /// 根据StructureDefinition创建分析器，元素的基数使用约束后的基数
+    pub fn for_profile(profile: &StructureDefinition) -> Result<Self> {
+        let type_name = profile.type_.as_ref()
+            .and_then(|type_| type_.value.as_ref())
+            .ok_or_else(|| FhirError::error("StructureDefinition中缺少type"))?;
+        let type_name = type_name.rsplit('/').next().unwrap_or(type_name);
+        let mut analyzer = Analyzer::for_type(type_name)?;
+
+        let elements = profile.snapshot.as_ref().and_then(|snapshot| snapshot.element.as_ref())
+            .or_else(|| profile.differential.as_ref().and_then(|differential| differential.element.as_ref()));
+
+        for element in elements.into_iter().flatten() {
+            let path = match element.path.as_ref().and_then(|path| path.value.as_ref()) {
+                Some(path) => path.clone(),
+                None => continue,
+            };
+            let (min, max) = analyzer.cardinalities.get(&path).cloned().unwrap_or((0, None));
+            let min = element.min.as_ref().and_then(|min| min.value).unwrap_or(min);
+            let max = match element.max.as_ref().and_then(|max| max.value.as_deref()) {
+                Some("*") => None,
+                Some(max) => max.parse().ok(),
+                None => max,
+            };
+            // 切片与被切片的元素具有相同的路径，只使用第一次出现的（被切片元素的）基数
+            analyzer.cardinalities.entry(path).or_insert((min, max));
+        }
+
+        Ok(analyzer)
+    }
+
+    pub fn root(&self) -> &'static TypeInfo {
+        self.root
+    }
+
+    /// 分析表达式
+    pub fn analyze(&self, expr: &Expr) -> Analysis {
+        let mut errors = Vec::new();
+        let this = PathType { path: Some(self.root.name.to_string()), ..PathType::single(self.root) };
+        let result = self.infer(expr, &this, &mut errors);
+        Analysis { result, errors }
+    }
+
+    /// 分析编译后的表达式，错误中带有表达式的原文，可以输出诊断信息
+    pub fn analyze_path(&self, path: &CompiledPath) -> Analysis {
+        let mut analysis = self.analyze(path.expr());
+        analysis.errors = analysis.errors.into_iter().map(|err| err.with_expression(path.text())).collect();
+        analysis
+    }
+
+    /// 推断表达式的结果，`this`为计算表达式时的当前元素
+    fn infer(&self, expr: &Expr, this: &PathType, errors: &mut Vec<PathError>) -> PathType {
+        match expr {
+            Expr::EmptyExpr => PathType::new(vec![], 0, Some(0)),
+            Expr::BooleanExpr(_) => PathType::single(bool::type_info()),
+            Expr::IntegerExpr(_) => PathType::single(isize::type_info()),
+            Expr::DecimalExpr(_) => PathType::single(f64::type_info()),
+            Expr::StringExpr(_) => PathType::single(String::type_info()),
+            Expr::DateTimeExpr(value) if !value.contains('T') => PathType::single(Date::type_info()),
+            Expr::DateTimeExpr(_) => PathType::single(DateTime::type_info()),
+            Expr::TimeExpr(_) => PathType::single(Time::type_info()),
+            Expr::QuantityExpr { .. } => PathType::single(Quantity::type_info()),
+            Expr::VariableExpr(name) => match name.as_str() {
+                "context" | "resource" | "rootResource" => {
+                    PathType { path: Some(self.root.name.to_string()), ..PathType::single(self.root) }
+                },
+                "ucum" | "sct" | "loinc" => PathType::single(String::type_info()),
+                name if name.starts_with("vs-") || name.starts_with("ext-") => PathType::single(String::type_info()),
+                _ => PathType::unknown(),
+            },
+            Expr::InvocationExpr(name) => match name.as_str() {
+                "this" => this.item(),
+                "index" => PathType::single(isize::type_info()),
+                _ => PathType::unknown(),
+            },
+            Expr::PathExpr { symbol, span } => {
+                // 以类型名开头的路径，如`Patient.name`中的`Patient`
+                if this.types.iter().any(|type_info| type_info.name == symbol) {
+                    this.clone()
+                } else {
+                    self.element(this, symbol, span, errors)
+                }
+            },
+            Expr::CallExpr { symbol, args, span } => {
+                self.call(this, symbol, args.as_deref().unwrap_or(&[]), this, span, errors)
+            },
+            Expr::PolarityExpr { expr, .. } => {
+                let operand = self.infer(expr, this, errors);
+                operand.with_cardinality(operand.min.min(1), Some(1))
+            },
+            Expr::BinOpExpr { lhs, rhs, op, span } => {
+                self.operation(lhs, rhs, op, span, this, errors)
+            },
+            Expr::IndexExpr { expr, index, .. } => {
+                let input = self.infer(expr, this, errors);
+                self.index(input, index, this, errors)
+            },
+        }
+    }
+
+    /// 点号[.]右侧的表达式，`input`为左侧的结果
+    fn member(&self, input: &PathType, rhs: &Expr, this: &PathType, span: &Range<usize>, errors: &mut Vec<PathError>) -> PathType {
+        match rhs {
+            Expr::PathExpr { symbol, span } => self.element(input, symbol, span, errors),
+            Expr::CallExpr { symbol, args, span } => {
+                self.call(input, symbol, args.as_deref().unwrap_or(&[]), this, span, errors)
+            },
+            Expr::IndexExpr { expr, index, .. } => {
+                let input = self.member(input, expr, this, span, errors);
+                self.index(input, index, this, errors)
+            },
+            _ => {
+                errors.push(PathError::new("点号[.]操作符不支持该右侧表达式。", span.clone()));
+                PathType::unknown()
+            },
+        }
+    }
+
+    /// 取索引后至多只有一个元素
+    fn index(&self, input: PathType, index: &Expr, this: &PathType, errors: &mut Vec<PathError>) -> PathType {
+        self.infer(index, this, errors);
+        input.with_cardinality(0, Some(input.max.map_or(1, |max| max.min(1))))
+    }
+
+    fn operation(&self, lhs: &Expr, rhs: &Expr, op: &Operator, span: &Range<usize>, this: &PathType, errors: &mut Vec<PathError>) -> PathType {
+        let left = self.infer(lhs, this, errors);
+
+        match op {
+            Operator::Dot => self.member(&left, rhs, this, span, errors),
+            Operator::Is => {
+                self.type_specifier(rhs, span, errors);
+                PathType::optional(bool::type_info())
+            },
+            Operator::As => match self.type_specifier(rhs, span, errors) {
+                Some(type_info) => PathType::new(vec![type_info], 0, left.max.map(|max| max.min(1))),
+                None => PathType::unknown(),
+            },
+            _ => {
+                let right = self.infer(rhs, this, errors);
+                match op {
+                    Operator::Union => union(&left, &right),
+                    Operator::Concat => PathType::single(String::type_info()),
+                    Operator::Div => PathType::optional(f64::type_info()),
+                    Operator::IntDiv => PathType::optional(isize::type_info()),
+                    Operator::Add | Operator::Sub | Operator::Mul | Operator::Mod => {
+                        if left.is_unknown() { right.with_cardinality(0, Some(1)) } else { left.with_cardinality(0, Some(1)) }
+                    },
+                    _ => PathType::optional(bool::type_info()),
+                }
+            },
+        }
+    }
+
+    /// 访问元素，对于有多个可能类型的输入，只要其中一个类型拥有该元素就是有效的路径
+    fn element(&self, input: &PathType, symbol: &str, span: &Range<usize>, errors: &mut Vec<PathError>) -> PathType {
+        if input.is_unknown() {
+            return PathType::unknown()
+        }
+
+        let mut types = Vec::new();
+        let mut path = None;
+        let mut matched = 0;
+        let mut min = usize::MAX;
+        let mut max = Some(0);
+        for type_info in &input.types {
+            if let Some((element, element_types)) = type_info.element(symbol) {
+                merge_types(&mut types, &element_types);
+                matched += 1;
+
+                let element_path = input.path.as_ref().map(|path| match element.choices.is_empty() {
+                    true => format!("{}.{}", path, element.name),
+                    false => format!("{}.{}[x]", path, element.name),
+                });
+                let (element_min, element_max) = element_path.as_ref()
+                    .and_then(|path| self.cardinalities.get(path))
+                    .cloned()
+                    .unwrap_or((element.min, element.max));
+
+                if element_max == Some(0) {
+                    errors.push(PathError::new(format!("元素[{}]在约束中不允许出现", symbol), span.clone()));
+                }
+
+                min = min.min(element_min);
+                max = max_of(max, element_max);
+                path = element_path;
+            }
+        }
+
+        if types.is_empty() {
+            let names = input.type_names().join("|");
+            errors.push(PathError::new(format!("在类型{}中发现无效的路径[{}]", names, symbol), span.clone()));
+            return PathType::unknown()
+        }
+
+        // 不是每个可能的类型都拥有该元素时，结果可能为空
+        if matched < input.types.len() {
+            min = 0;
+        }
+
+        let mut result = PathType::new(types, input.min * min, mul_max(input.max, max));
+        if input.types.len() == 1 {
+            result.path = path;
+        }
+        result
+    }
+
+    /// 调用函数，`this`为计算非迭代参数时的当前元素
+    fn call(&self, input: &PathType, symbol: &str, args: &[Expr], this: &PathType, span: &Range<usize>, errors: &mut Vec<PathError>) -> PathType {
+        if let Some(function) = self.functions.get(symbol) {
+            if let Err(FhirError::FhirPath(err)) = function.check_arity(args.len()).map_err(|err| err.at(span.clone())) {
+                errors.push(err);
+            }
+            args.iter().for_each(|arg| { self.infer(arg, this, errors); });
+            return PathType::unknown()
+        }
+
+        let function = match function::lookup(symbol) {
+            Some(function) => function,
+            None => {
+                errors.push(PathError::new(format!("这是无效或者未被支持的函数名[{}]", symbol), span.clone()));
+                return PathType::unknown()
+            },
+        };
+
+        if let Err(err) = function.check_arity(args.len()) {
+            if let FhirError::FhirPath(err) = err.at(span.clone()) {
+                errors.push(err);
+            }
+            return PathType::unknown()
+        }
+
+        let boolean = || PathType::single(bool::type_info());
+        let optional = |type_info: &'static TypeInfo| PathType::optional(type_info);
+
+        match symbol {
+            // 迭代函数的参数以输入中的每个元素为当前元素
+            "where" | "select" | "all" | "exists" | "repeat" | "aggregate" => {
+                let item = input.item();
+                let criteria: Vec<PathType> = args.iter().map(|arg| self.infer(arg, &item, errors)).collect();
+                match (symbol, criteria.first()) {
+                    ("where", _) => input.with_cardinality(0, input.max),
+                    ("select", Some(selected)) => {
+                        PathType::new(selected.types.clone(), 0, mul_max(input.max, selected.max))
+                    },
+                    ("repeat", Some(selected)) => PathType::new(selected.types.clone(), 0, None),
+                    ("all" | "exists", _) => boolean(),
+                    _ => PathType::unknown(),
+                }
+            },
+            "ofType" => match args.first().and_then(|arg| self.type_specifier(arg, span, errors)) {
+                Some(type_info) => PathType::new(vec![type_info], 0, input.max),
+                None => PathType::unknown(),
+            },
+            "is" | "as" => {
+                let type_info = args.first().and_then(|arg| self.type_specifier(arg, span, errors));
+                match (symbol, type_info) {
+                    ("is", _) => optional(bool::type_info()),
+                    (_, Some(type_info)) => PathType::new(vec![type_info], 0, Some(1)),
+                    _ => PathType::unknown(),
+                }
+            },
+            _ => {
+                let args: Vec<PathType> = args.iter().map(|arg| self.infer(arg, this, errors)).collect();
+                function_result(symbol, input, &args)
+            },
+        }
+    }
+
+    /// 解析类型说明符，如`Quantity`、`FHIR.string`
+    fn type_specifier(&self, expr: &Expr, span: &Range<usize>, errors: &mut Vec<PathError>) -> Option<&'static TypeInfo> {
+        let span = expr.span().unwrap_or_else(|| span.clone());
+        let specifier = match types::type_specifier(expr) {
+            Ok(specifier) => specifier,
+            Err(err) => {
+                if let FhirError::FhirPath(err) = err.at(span) {
+                    errors.push(err);
+                }
+                return None
+            },
+        };
+
+        let type_info = TypeInfo::lookup(&specifier);
+        if type_info.is_none() {
+            errors.push(PathError::new(format!("无效的类型[{}]", specifier), span));
+        }
+        type_info
+    }
+}
+
+/// 非迭代函数的结果
+fn function_result(symbol: &str, input: &PathType, args: &[PathType]) -> PathType {
+    let optional = PathType::optional;
+    match symbol {
+        "empty" | "allTrue" | "anyTrue" | "allFalse" | "anyFalse" | "subsetOf" | "supersetOf"
+            | "isDistinct" | "hasValue" => PathType::single(bool::type_info()),
+        "memberOf" | "subsumes" | "subsumedBy" | "not" => optional(bool::type_info()),
+        "count" => PathType::single(isize::type_info()),
+        "distinct" | "trace" => input.with_cardinality(input.min.min(1), input.max),
+        "extension" => PathType::new(vec![Extension::type_info()], 0, None),
+        "getValue" => {
+            let types: Vec<&'static TypeInfo> = input.types.iter()
+                .filter(|type_info| type_info.kind == TypeKind::Primitive)
+                .filter_map(|type_info| type_info.element("value"))
+                .flat_map(|(_, types)| types)
+                .collect();
+            PathType::new(types, 0, Some(1))
+        },
+        "startsWith" | "endsWith" | "contains" | "matches" | "convertsToBoolean" | "convertsToInteger"
+            | "convertsToDecimal" | "convertsToString" | "convertsToDate" | "convertsToDateTime"
+            | "convertsToTime" | "convertsToQuantity" | "toBoolean" => optional(bool::type_info()),
+        "indexOf" | "length" | "toInteger" => optional(isize::type_info()),
+        "substring" | "upper" | "lower" | "replace" | "replaceMatches" | "trim" | "encode" | "decode"
+            | "join" | "toString" => optional(String::type_info()),
+        "toChars" | "split" => PathType::new(vec![String::type_info()], 0, None),
+        "toDecimal" => optional(f64::type_info()),
+        "toDate" => optional(Date::type_info()),
+        "toDateTime" => optional(DateTime::type_info()),
+        "toTime" => optional(Time::type_info()),
+        "toQuantity" => optional(Quantity::type_info()),
+        "iif" => {
+            let mut result = args[1].clone();
+            for branch in &args[2..] {
+                merge_types(&mut result.types, &branch.types);
+                result.max = max_of(result.max, branch.max);
+            }
+            result.with_cardinality(0, result.max)
+        },
+        "first" | "last" => input.with_cardinality(0, Some(input.max.map_or(1, |max| max.min(1)))),
+        "tail" | "skip" | "take" | "intersect" | "exclude" => input.with_cardinality(0, input.max),
+        "union" | "combine" => union(input, &args[0]),
+        "ceiling" | "floor" | "truncate" | "precision" => optional(isize::type_info()),
+        "exp" | "ln" | "log" | "sqrt" | "round" => optional(f64::type_info()),
+        "abs" | "power" | "lowBoundary" | "highBoundary" => input.with_cardinality(0, Some(1)),
+        "comparable" => optional(bool::type_info()),
+        "now" => PathType::single(DateTime::type_info()),
+        "today" => PathType::single(Date::type_info()),
+        "timeOfDay" => PathType::single(Time::type_info()),
+        _ => PathType::unknown(),
+    }
+}
+
+/// 合并两个集合的结果，合并运算[|]以及`union()`、`combine()`
+fn union(left: &PathType, right: &PathType) -> PathType {
+    let mut types = left.types.clone();
+    merge_types(&mut types, &right.types);
+    if left.is_unknown() || right.is_unknown() {
+        types.clear();
+    }
+    let min = if left.min > 0 || right.min > 0 { 1 } else { 0 };
+    PathType::new(types, min, add_max(left.max, right.max))
+}
+
+fn merge_types(types: &mut Vec<&'static TypeInfo>, others: &[&'static TypeInfo]) {
+    for other in others {
+        if !types.iter().any(|type_info| std::ptr::eq(*type_info, *other)) {
+            types.push(other);
+        }
+    }
+}
+
+fn max_of(lhs: Option<usize>, rhs: Option<usize>) -> Option<usize> {
+    Some(lhs?.max(rhs?))
+}
+
+fn add_max(lhs: Option<usize>, rhs: Option<usize>) -> Option<usize> {
+    Some(lhs? + rhs?)
+}
+
+fn mul_max(lhs: Option<usize>, rhs: Option<usize>) -> Option<usize> {
+    match (lhs, rhs) {
+        (Some(0), _) | (_, Some(0)) => Some(0),
+        (Some(lhs), Some(rhs)) => Some(lhs * rhs),
+        _ => None,
+    }
+}
@@ -0,0 +1,238 @@
+//! FHIRPath的算术运算
+//!
+//! 运算数都是单个元素，按照其System类型决定运算方式：
+//! * 整数与整数运算的结果为整数，整数与小数运算时整数提升为小数
+//! * 字符串之间可以使用`+`连接
+//! * 日期、日期时间、时间可以加减日历时间单位的数量，如`@2000-01-01 + 18 years`
+//!
+//! 运算结果为`None`时表示结果为空，如除数为零、整数溢出等情况。
+use chrono::{Months, TimeDelta};
+use crate::prelude::{Result, FhirError, Integer, Decimal, Date, DateTime, Time};
+use super::*;
+use super::quantity::CalendarUnit;
+use super::types::{system_type, SystemType};
+
+pub type Value = Option<Box<dyn Executor>>;
+
+fn boxed<T: Executor + 'static>(value: T) -> Value {
+    Some(Box::new(value))
+}
+
+pub fn add(lhs: &dyn Executor, rhs: &dyn Executor) -> Result<Value> {
+    match (system_type(lhs), system_type(rhs)) {
+        (SystemType::Integer, SystemType::Integer) => {
+            Ok(lhs.to_integer()?.checked_add(rhs.to_integer()?).and_then(boxed))
+        },
+        (SystemType::Integer | SystemType::Decimal, SystemType::Integer | SystemType::Decimal) => {
+            Ok(boxed(lhs.to_decimal()? + rhs.to_decimal()?))
+        },
+        (SystemType::String, SystemType::String) => {
+            Ok(boxed(lhs.to_strings()? + &rhs.to_strings()?))
+        },
+        (SystemType::Date | SystemType::DateTime | SystemType::Time, SystemType::Quantity) => {
+            shift_temporal(lhs, rhs, 1.0)
+        },
+        (SystemType::Quantity, SystemType::Quantity) => {
+            Ok(quantity::add(lhs, rhs, 1.0)?.and_then(boxed))
+        },
+        (l_type, r_type) => Err(FhirError::Message(format!("不支持的运算[{:?} + {:?}]", l_type, r_type))),
+    }
+}
+
+pub fn sub(lhs: &dyn Executor, rhs: &dyn Executor) -> Result<Value> {
+    match (system_type(lhs), system_type(rhs)) {
+        (SystemType::Integer, SystemType::Integer) => {
+            Ok(lhs.to_integer()?.checked_sub(rhs.to_integer()?).and_then(boxed))
+        },
+        (SystemType::Integer | SystemType::Decimal, SystemType::Integer | SystemType::Decimal) => {
+            Ok(boxed(lhs.to_decimal()? - rhs.to_decimal()?))
+        },
+        (SystemType::Date | SystemType::DateTime | SystemType::Time, SystemType::Quantity) => {
+            shift_temporal(lhs, rhs, -1.0)
+        },
+        (SystemType::Quantity, SystemType::Quantity) => {
+            Ok(quantity::add(lhs, rhs, -1.0)?.and_then(boxed))
+        },
+        (l_type, r_type) => Err(FhirError::Message(format!("不支持的运算[{:?} - {:?}]", l_type, r_type))),
+    }
+}
+
+pub fn mul(lhs: &dyn Executor, rhs: &dyn Executor) -> Result<Value> {
+    match (system_type(lhs), system_type(rhs)) {
+        (SystemType::Integer, SystemType::Integer) => {
+            Ok(lhs.to_integer()?.checked_mul(rhs.to_integer()?).and_then(boxed))
+        },
+        (SystemType::Integer | SystemType::Decimal, SystemType::Integer | SystemType::Decimal) => {
+            Ok(boxed(lhs.to_decimal()? * rhs.to_decimal()?))
+        },
+        (SystemType::Quantity, SystemType::Integer | SystemType::Decimal) => {
+            Ok(quantity::scale(lhs, rhs.to_decimal()?)?.and_then(boxed))
+        },
+        (SystemType::Integer | SystemType::Decimal, SystemType::Quantity) => {
+            Ok(quantity::scale(rhs, lhs.to_decimal()?)?.and_then(boxed))
+        },
+        (l_type, r_type) => Err(FhirError::Message(format!("不支持的运算[{:?} * {:?}]", l_type, r_type))),
+    }
+}
+
+/// 取负数[-]，只支持整数、小数和数量
+pub fn negate(value: &dyn Executor) -> Result<Value> {
+    match system_type(value) {
+        SystemType::Integer => Ok(value.to_integer()?.checked_neg().and_then(boxed)),
+        SystemType::Decimal => Ok(boxed(-value.to_decimal()?)),
+        SystemType::Quantity => Ok(quantity::scale(value, -1.0)?.and_then(boxed)),
+        other => Err(FhirError::Message(format!("不支持的运算[-{:?}]", other))),
+    }
+}
+
+/// 除法的结果总是小数
+pub fn div(lhs: &dyn Executor, rhs: &dyn Executor) -> Result<Value> {
+    match (system_type(lhs), system_type(rhs)) {
+        (SystemType::Integer | SystemType::Decimal, SystemType::Integer | SystemType::Decimal) => {
+            let divisor = rhs.to_decimal()?;
+            if divisor == 0.0 { return Ok(None) }
+            Ok(boxed(lhs.to_decimal()? / divisor))
+        },
+        (SystemType::Quantity, SystemType::Integer | SystemType::Decimal) => {
+            let divisor = rhs.to_decimal()?;
+            if divisor == 0.0 { return Ok(None) }
+            Ok(quantity::scale(lhs, 1.0 / divisor)?.and_then(boxed))
+        },
+        (l_type, r_type) => Err(FhirError::Message(format!("不支持的运算[{:?} / {:?}]", l_type, r_type))),
+    }
+}
+
+/// 整除，结果为截断小数部分后的整数
+pub fn int_div(lhs: &dyn Executor, rhs: &dyn Executor) -> Result<Value> {
+    match (system_type(lhs), system_type(rhs)) {
+        (SystemType::Integer, SystemType::Integer) => {
+            Ok(lhs.to_integer()?.checked_div(rhs.to_integer()?).and_then(boxed))
+        },
+        (SystemType::Integer | SystemType::Decimal, SystemType::Integer | SystemType::Decimal) => {
+            let divisor = rhs.to_decimal()?;
+            if divisor == 0.0 { return Ok(None) }
+            Ok(boxed((lhs.to_decimal()? / divisor).trunc() as Integer))
+        },
+        (l_type, r_type) => Err(FhirError::Message(format!("不支持的运算[{:?} div {:?}]", l_type, r_type))),
+    }
+}
+
+/// 取余，整数之间的结果为整数，否则为小数
+pub fn modulo(lhs: &dyn Executor, rhs: &dyn Executor) -> Result<Value> {
+    match (system_type(lhs), system_type(rhs)) {
+        (SystemType::Integer, SystemType::Integer) => {
+            Ok(lhs.to_integer()?.checked_rem(rhs.to_integer()?).and_then(boxed))
+        },
+        (SystemType::Integer | SystemType::Decimal, SystemType::Integer | SystemType::Decimal) => {
+            let divisor = rhs.to_decimal()?;
+            if divisor == 0.0 { return Ok(None) }
+            Ok(boxed(lhs.to_decimal()? % divisor))
+        },
+        (l_type, r_type) => Err(FhirError::Message(format!("不支持的运算[{:?} mod {:?}]", l_type, r_type))),
+    }
+}
+
+/// 日期时间加减日历时间单位的数量，运算结果保持原有的精度
+fn shift_temporal(temporal: &dyn Executor, quantity: &dyn Executor, sign: Decimal) -> Result<Value> {
+    let (value, unit) = match quantity::value_and_unit(quantity)? {
+        Some(value_and_unit) => value_and_unit,
+        None => return Ok(None),
+    };
+
+    let unit = match CalendarUnit::from_unit(&unit) {
+        Some(unit) => unit,
+        None => return Err(FhirError::Message(format!("日期时间不能与单位为[{}]的数量进行运算", unit))),
+    };
+
+    let value = sign * value;
+    match system_type(temporal) {
+        SystemType::Date => {
+            let date = temporal.to_datetime()?;
+            let shifted = shift_date(date.0.date_naive(), value, unit)?;
+            Ok(shifted.and_then(|shifted| boxed(Date(shifted, date.1))))
+        },
+        SystemType::DateTime => {
+            let datetime = temporal.to_datetime()?;
+            let shifted = match unit {
+                CalendarUnit::Year | CalendarUnit::Month => shift_months(datetime.0, value, unit),
+                _ => duration(value, unit).and_then(|delta| datetime.0.checked_add_signed(delta)),
+            };
+            Ok(shifted.and_then(|shifted| boxed(DateTime(shifted, datetime.1, datetime.2))))
+        },
+        SystemType::Time => {
+            let time = temporal.to_time()?;
+            match unit {
+                CalendarUnit::Hour | CalendarUnit::Minute | CalendarUnit::Second | CalendarUnit::Millisecond => {
+                    let delta = duration(value, unit);
+                    Ok(delta.and_then(|delta| boxed(Time(time.0.overflowing_add_signed(delta).0, time.1))))
+                },
+                _ => Err(FhirError::error("时间只能与时、分、秒、毫秒单位的数量进行运算")),
+            }
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn shift_date(date: chrono::NaiveDate, value: Decimal, unit: CalendarUnit) -> Result<Option<chrono::NaiveDate>> {
+    match unit {
+        CalendarUnit::Year | CalendarUnit::Month => Ok(shift_months(date, value, unit)),
+        CalendarUnit::Week | CalendarUnit::Day => Ok(duration(value, unit).and_then(|delta| date.checked_add_signed(delta))),
+        _ => Err(FhirError::error("日期只能与年、月、周、天单位的数量进行运算")),
+    }
+}
+
+/// 按月份进行加减，年换算为12个月，数值中的小数部分被截断
+fn shift_months<T>(value: T, amount: Decimal, unit: CalendarUnit) -> Option<T>
+where T: ShiftMonths {
+    let months = match unit {
+        CalendarUnit::Year => amount.trunc() as i64 * 12,
+        _ => amount.trunc() as i64,
+    };
+
+    let shifted = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+    if months < 0 {
+        value.sub_months(shifted)
+    } else {
+        value.add_months(shifted)
+    }
+}
+
+/// 将周、天以及更小的时间单位换算为时间间隔
+fn duration(value: Decimal, unit: CalendarUnit) -> Option<TimeDelta> {
+    let milliseconds = match unit {
+        CalendarUnit::Week => value.trunc() * 7.0 * 86_400_000.0,
+        CalendarUnit::Day => value.trunc() * 86_400_000.0,
+        CalendarUnit::Hour => value.trunc() * 3_600_000.0,
+        CalendarUnit::Minute => value.trunc() * 60_000.0,
+        CalendarUnit::Second => value * 1_000.0,
+        CalendarUnit::Millisecond => value,
+        CalendarUnit::Year | CalendarUnit::Month => return None,
+    };
+
+    TimeDelta::try_milliseconds(milliseconds.round() as i64)
+}
+
+trait ShiftMonths: Sized {
+    fn add_months(self, months: Months) -> Option<Self>;
+    fn sub_months(self, months: Months) -> Option<Self>;
+}
+
+impl ShiftMonths for chrono::NaiveDate {
+    fn add_months(self, months: Months) -> Option<Self> {
+        self.checked_add_months(months)
+    }
+
+    fn sub_months(self, months: Months) -> Option<Self> {
+        self.checked_sub_months(months)
+    }
+}
+
+impl ShiftMonths for chrono::DateTime<chrono::FixedOffset> {
+    fn add_months(self, months: Months) -> Option<Self> {
+        self.checked_add_months(months)
+    }
+
+    fn sub_months(self, months: Months) -> Option<Self> {
+        self.checked_sub_months(months)
+    }
+}
@@ -0,0 +1,188 @@
+//! FHIRPath的精度与边界函数`precision()`、`lowBoundary()`、`highBoundary()`以及`comparable()`
+//!
+//! 小数的精度为小数点后的位数，日期时间的精度为数字的位数，如`@2014-01`的精度为6。
+//! 小数以浮点数保存，不保留末尾的0，因此`1.50`与`1.5`的精度相同。
+//! 边界是在当前精度范围内可能的最小值和最大值，如`1.587`的下边界为`1.5865`，`@2014`的上边界为`@2014-12-31`；
+//! 参数指定结果的精度，未指定时小数为8，日期为8，日期时间为17，时间为9。
+use chrono::{Months, NaiveDateTime, NaiveTime, TimeDelta};
+use crate::prelude::{Result, FhirError, Integer, Decimal, Date, DateTime, Time};
+use super::*;
+use super::arithmetic::Value;
+use super::conversion::single;
+use super::types::{system_type, SystemType};
+
+const DATETIME_PATTERN: &str = "0000-01-01T00:00:00.000";
+const TIME_PATTERN: &str = "00:00:00.000";
+const DATE_LENGTHS: [usize; 3] = [4, 7, 10];
+const DATETIME_LENGTHS: [usize; 7] = [4, 7, 10, 13, 16, 19, 23];
+const TIME_LENGTHS: [usize; 4] = [2, 5, 8, 12];
+/// 小数边界的最大精度
+const MAX_DECIMAL_PRECISION: Integer = 28;
+
+pub(crate) fn precision<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let value = match single(&input)? {
+        Some(value) => value,
+        None => return Ok(Collection::new()),
+    };
+
+    let digits = match system_type(value) {
+        SystemType::Integer => Some(0),
+        SystemType::Decimal => decimal_parts(value.to_decimal()?).map(|(_, scale)| scale as usize),
+        SystemType::Date | SystemType::DateTime => Some(digits(DATETIME_PATTERN, value.to_datetime()?.1)),
+        SystemType::Time => Some(digits(TIME_PATTERN, value.to_time()?.1)),
+        other => return Err(FhirError::Message(format!("precision()的输入必须是小数或者日期时间，实际为{:?}", other))),
+    };
+
+    Ok(digits.map_or_else(Collection::new, |digits| Collection::new_integer(digits as Integer)))
+}
+
+pub(crate) fn low_boundary<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    boundary(input, args, executor, context, false)
+}
+
+pub(crate) fn high_boundary<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    boundary(input, args, executor, context, true)
+}
+
+/// 两个数量的单位是否可以相互换算
+pub(crate) fn comparable<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let other = args[0].eval_with(executor, context)?;
+    let (lhs, rhs) = match (single(&input)?, single(&other)?) {
+        (Some(lhs), Some(rhs)) => (lhs, rhs),
+        _ => return Ok(Collection::new()),
+    };
+
+    if system_type(lhs) != SystemType::Quantity || system_type(rhs) != SystemType::Quantity {
+        return Err(FhirError::error("comparable()的输入和参数必须都是数量"))
+    }
+
+    match (quantity::value_and_unit(lhs)?, quantity::value_and_unit(rhs)?) {
+        (Some((_, l_unit)), Some((_, r_unit))) => Ok(Collection::new_boolean(quantity::convert(1.0, &r_unit, &l_unit).is_some())),
+        _ => Ok(Collection::new()),
+    }
+}
+
+fn boundary<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>, high: bool) -> Result<Collection<'a>> {
+    let precision = match args.first() {
+        Some(arg) => match arg.eval_with(executor, context)?.to_integer()? {
+            Some(precision) => Some(precision),
+            None => return Ok(Collection::new()),
+        },
+        None => None,
+    };
+
+    let value = match single(&input)? {
+        Some(value) => value,
+        None => return Ok(Collection::new()),
+    };
+
+    let result: Value = match system_type(value) {
+        SystemType::Integer | SystemType::Decimal => {
+            decimal_boundary(value.to_decimal()?, precision.unwrap_or(8), high).map(|value| Box::new(value) as Box<dyn Executor>)
+        },
+        SystemType::Quantity => {
+            quantity::value_and_unit(value)?
+                .and_then(|(value, unit)| Some(quantity::new_quantity(decimal_boundary(value, precision.unwrap_or(8), high)?, &unit)))
+                .map(|quantity| Box::new(quantity) as Box<dyn Executor>)
+        },
+        SystemType::Date => {
+            let datetime = value.to_datetime()?;
+            length(DATETIME_PATTERN, &DATE_LENGTHS, precision.unwrap_or(8))
+                .and_then(|len| datetime_boundary(datetime.0.naive_local(), datetime.1, high).map(|naive| (len, naive)))
+                .map(|(len, naive)| Box::new(Date(naive.date(), len)) as Box<dyn Executor>)
+        },
+        SystemType::DateTime => {
+            let datetime = value.to_datetime()?;
+            length(DATETIME_PATTERN, &DATETIME_LENGTHS, precision.unwrap_or(17))
+                .and_then(|len| datetime_boundary(datetime.0.naive_local(), datetime.1, high).map(|naive| (len, naive)))
+                .and_then(|(len, naive)| {
+                    let boundary = naive.and_local_timezone(*datetime.0.offset()).single()?;
+                    Some(Box::new(DateTime(boundary, len, datetime.2)) as Box<dyn Executor>)
+                })
+        },
+        SystemType::Time => {
+            let time = value.to_time()?;
+            length(TIME_PATTERN, &TIME_LENGTHS, precision.unwrap_or(9))
+                .map(|len| Box::new(Time(time_boundary(time.0, time.1, high), len)) as Box<dyn Executor>)
+        },
+        other => return Err(FhirError::Message(format!("边界函数的输入必须是数值、数量或者日期时间，实际为{:?}", other))),
+    };
+
+    Ok(result.map_or_else(Collection::new, Collection::new_any))
+}
+
+/// 小数的各位数字组成的整数以及小数点后的位数，如`-1.587`为`(-1587, 3)`
+fn decimal_parts(value: Decimal) -> Option<(i128, u32)> {
+    if !value.is_finite() {
+        return None
+    }
+
+    let text = value.to_string();
+    let scale = text.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+    let digits = text.replace('.', "").parse().ok()?;
+    Some((digits, u32::try_from(scale).ok()?))
+}
+
+/// 在原有精度的基础上加减最后一位的一半，再按指定的精度向外取整
+fn decimal_boundary(value: Decimal, precision: Integer, high: bool) -> Option<Decimal> {
+    if !(0..=MAX_DECIMAL_PRECISION).contains(&precision) {
+        return None
+    }
+
+    let precision = precision as u32;
+    let (digits, scale) = decimal_parts(value)?;
+    let bound = digits.checked_mul(10)? + if high { 5 } else { -5 };
+    let scale = scale + 1;
+
+    let scaled = if precision >= scale {
+        bound.checked_mul(10i128.checked_pow(precision - scale)?)?
+    } else {
+        let divisor = 10i128.checked_pow(scale - precision)?;
+        match high {
+            true => -(-bound).div_euclid(divisor),
+            false => bound.div_euclid(divisor),
+        }
+    };
+
+    Some(scaled as Decimal / 10f64.powi(precision as i32))
+}
+
+/// 日期时间格式中前`len`个字符内数字的位数
+fn digits(pattern: &str, len: usize) -> usize {
+    pattern[..len.min(pattern.len())].bytes().filter(u8::is_ascii_digit).count()
+}
+
+/// 精度对应的格式长度，不是有效的精度时返回`None`
+fn length(pattern: &str, lengths: &[usize], precision: Integer) -> Option<usize> {
+    lengths.iter().copied().find(|len| digits(pattern, *len) as Integer == precision)
+}
+
+/// 日期时间在其精度范围内的下边界或者上边界，下边界就是保存的值
+fn datetime_boundary(value: NaiveDateTime, len: usize, high: bool) -> Option<NaiveDateTime> {
+    if !high {
+        return Some(value)
+    }
+
+    let next = match len {
+        4 => value.checked_add_months(Months::new(12))?,
+        7 => value.checked_add_months(Months::new(1))?,
+        10 => value.checked_add_signed(TimeDelta::days(1))?,
+        13 => value.checked_add_signed(TimeDelta::hours(1))?,
+        16 => value.checked_add_signed(TimeDelta::minutes(1))?,
+        19 => value.checked_add_signed(TimeDelta::seconds(1))?,
+        _ => return Some(value),
+    };
+    next.checked_sub_signed(TimeDelta::milliseconds(1))
+}
+
+fn time_boundary(value: NaiveTime, len: usize, high: bool) -> NaiveTime {
+    let period = match (high, len) {
+        (false, _) => return value,
+        (true, 2) => TimeDelta::hours(1),
+        (true, 5) => TimeDelta::minutes(1),
+        (true, 8) => TimeDelta::seconds(1),
+        (true, _) => return value,
+    };
+    let (next, _) = value.overflowing_add_signed(period);
+    next.overflowing_sub_signed(TimeDelta::milliseconds(1)).0
+}
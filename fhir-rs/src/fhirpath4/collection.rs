@@ -1,55 +1,152 @@
-use std::ops::{Add, BitOr, BitXor, Div, Mul, Not, Sub};
+use std::cmp::Ordering;
+use std::fmt::{Debug, Formatter};
+use std::ops::{Add, BitOr, BitXor, Deref, Div, Mul, Not, Sub};
+use std::rc::Rc;
 use crate::datatype::Boolean;
 use crate::prelude::{Result, FhirError, Integer, Decimal, DateTime};
 use super::*;
 
-#[derive(Debug)]
-pub struct Collection(Vec<Box<dyn Executor>>);
+/// 集合中的元素
+///
+/// 从资源中导航得到的元素直接引用资源中的节点，不会复制；
+/// 计算得到的值（如字面量、运算和函数的结果）由集合持有，克隆集合时共享同一个值。
+#[derive(Clone)]
+pub enum Item<'a> {
+    Borrowed(&'a dyn Executor),
+    Owned(Rc<dyn Executor>),
+}
+
+impl<'a> Item<'a> {
+    /// 子路径对应的元素，计算得到的值的子元素会被复制出来
+    pub fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection<'a>> {
+        match self {
+            Item::Borrowed(part) => part.element(symbol, index),
+            Item::Owned(part) => Ok(part.element(symbol, index)?.into_owned()),
+        }
+    }
+
+    pub fn children(&self) -> Collection<'a> {
+        match self {
+            Item::Borrowed(part) => part.children(),
+            Item::Owned(part) => part.children().into_owned(),
+        }
+    }
+
+    /// 以该元素作为`$this`计算表达式
+    pub fn eval(&self, expr: &Expr, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        match self {
+            Item::Borrowed(part) => expr.eval_with(*part, context),
+            Item::Owned(part) => Ok(expr.eval_with(part.as_ref(), context)?.into_owned()),
+        }
+    }
 
-impl Collection {
+    /// 复制引用的元素，使其不再依赖原来的资源
+    pub fn into_owned(self) -> Item<'static> {
+        match self {
+            Item::Borrowed(part) => Item::Owned(Rc::from(part.boxed_clone())),
+            Item::Owned(part) => Item::Owned(part),
+        }
+    }
+}
+
+impl<'a> Deref for Item<'a> {
+    type Target = dyn Executor + 'a;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Item::Borrowed(part) => *part,
+            Item::Owned(part) => part.as_ref(),
+        }
+    }
+}
+
+impl<'a> AsRef<dyn Executor + 'a> for Item<'a> {
+    fn as_ref(&self) -> &(dyn Executor + 'a) {
+        self.deref()
+    }
+}
+
+impl Debug for Item<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.deref(), f)
+    }
+}
+
+impl From<Box<dyn Executor>> for Item<'_> {
+    fn from(value: Box<dyn Executor>) -> Self {
+        Item::Owned(Rc::from(value))
+    }
+}
+
+impl<T: Executor + 'static> From<Box<T>> for Item<'_> {
+    fn from(value: Box<T>) -> Self {
+        Item::Owned(Rc::from(value as Box<dyn Executor>))
+    }
+}
+
+impl<'a> From<&'a dyn Executor> for Item<'a> {
+    fn from(value: &'a dyn Executor) -> Self {
+        Item::Borrowed(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Collection<'a>(Vec<Item<'a>>);
+
+impl<'a> Collection<'a> {
     /// 创建一个空集合
     pub fn new() -> Self {
         Self(vec![])
     }
 
     pub fn new_string(value: String) -> Self {
-        Self(vec![Box::new(value)])
+        Self::new_any(Box::new(value))
     }
 
     pub fn new_integer(value: Integer) -> Self {
-        Self(vec![Box::new(value)])
+        Self::new_any(Box::new(value))
     }
 
     pub fn new_decimal(value: Decimal) -> Self {
-        Self(vec![Box::new(value)])
+        Self::new_any(Box::new(value))
     }
 
     pub fn new_datetime(value: DateTime) -> Self {
-        Self(vec![Box::new(value)])
+        Self::new_any(Box::new(value))
     }
 
     pub fn new_boolean(value: Boolean) -> Self {
-        Self(vec![Box::new(value)])
+        Self::new_any(Box::new(value))
     }
 
     pub fn new_any(value: Box<dyn Executor>) -> Self {
-        Self(vec![value])
+        Self(vec![value.into()])
+    }
+
+    /// 创建引用该元素的集合，不会复制元素
+    pub fn new_ref(value: &'a dyn Executor) -> Self {
+        Self(vec![Item::Borrowed(value)])
+    }
+
+    /// 复制所有引用的元素，使集合不再依赖原来的资源
+    pub fn into_owned(self) -> Collection<'static> {
+        Collection(self.0.into_iter().map(Item::into_owned).collect())
     }
 
-    pub fn first(&self) -> Option<&Box<dyn Executor>> {
-        self.0.get(0)
+    pub fn first(&self) -> Option<&Item<'a>> {
+        self.0.first()
     }
 
     pub fn count(&self) -> usize {
         self.0.len()
     }
 
-    pub fn combine(&mut self, other: Collection) {
+    pub fn combine(&mut self, other: Collection<'a>) {
         self.0.extend(other.0)
     }
 
-    pub fn push(&mut self, value: Box<dyn Executor>) {
-        self.0.push(value)
+    pub fn push<I: Into<Item<'a>>>(&mut self, value: I) {
+        self.0.push(value.into())
     }
 
     pub fn empty(&self) -> bool {
@@ -68,6 +165,22 @@ impl Collection {
         }
     }
 
+    pub fn to_decimal(&self) -> Result<Option<Decimal>> {
+        match self.count() {
+            0 => Ok(None),
+            1 => Ok(Some(self.0[0].to_decimal()?)),
+            _ => Err(FhirError::error("集合内元素数量大于1")),
+        }
+    }
+
+    pub fn to_strings(&self) -> Result<Option<String>> {
+        match self.count() {
+            0 => Ok(None),
+            1 => Ok(Some(self.0[0].to_strings()?)),
+            _ => Err(FhirError::error("集合内元素数量大于1")),
+        }
+    }
+
     pub fn to_boolean(&self) -> Result<Option<bool>> {
         match self.count() {
             0 => Ok(None),
@@ -76,14 +189,7 @@ impl Collection {
         }
     }
 
-    pub fn all_true(&self) -> Result<bool> {
-        // for part in &self.0 {
-        //     if !part.as_bool()? { return Ok(false)}
-        // }
-        Ok(true)
-    }
-
-    pub fn element(self, symbol: &String, index: &Option<usize>) -> Result<Collection> {
+    pub fn element(self, symbol: &String, index: &Option<usize>) -> Result<Collection<'a>> {
         let mut collection = Collection::new();
         for part in self.0 {
             let children = part.element(symbol, index)?;
@@ -93,11 +199,12 @@ impl Collection {
         Ok(collection)
     }
 
-    pub fn filter(self, criteria: &Expr) -> Result<Collection> {
+    /// 按照条件过滤集合中的元素，条件中可以使用`$this`和`$index`
+    pub fn filter(self, criteria: &Expr, context: &EvalContext<'a>) -> Result<Collection<'a>> {
         let mut collection = Collection::new();
 
-        for part in self.0 {
-            let col = criteria.eval(part.as_ref())?;
+        for (index, part) in self.0.into_iter().enumerate() {
+            let col = part.eval(criteria, &context.iteration(index))?;
             let bl = col.to_boolean()?;
             let bl = bl.unwrap_or_else(|| false);
             if bl {collection.push(part)}
@@ -106,25 +213,118 @@ impl Collection {
         Ok(collection)
     }
 
-    pub fn call(self, symbol: &String, args: &Option<Vec<Expr>>) -> Result<Collection> {
-        match symbol.as_str() {
-            "where" => {
-                match args {
-                    None => Err(FhirError::error("where()至少拥有一个参数")),
-                    Some(arg) => {
-                        if arg.len() == 1 {
-                            self.filter(&arg[0])
-                        } else {
-                            Err(FhirError::error("where()至多只能拥有一个参数"))
-                        }
-                    }
-                }
+    /// 类型判断运算[is]
+    /// 规则：
+    /// 1. 集合为空，则结果为空
+    /// 2. 集合内的元素数量大于1，则报错
+    pub fn is(self, specifier: &str) -> Result<Collection<'a>> {
+        if self.empty() {
+            return Ok(Collection::new())
+        }
+
+        if self.count() > 1 {
+            return Err(FhirError::error("集合内元素数量大于1"))
+        }
+
+        Ok(Collection::new_boolean(types::is_type(self.0[0].as_ref(), specifier)))
+    }
+
+    /// 类型转换运算[as]，元素不是指定的类型时结果为空
+    pub fn as_type(self, specifier: &str) -> Result<Collection<'a>> {
+        if self.count() > 1 {
+            return Err(FhirError::error("集合内元素数量大于1"))
+        }
+
+        match self.0.first() {
+            Some(part) if types::is_type(part.as_ref(), specifier) => Ok(self),
+            _ => Ok(Collection::new()),
+        }
+    }
+
+    /// 调用函数，先查找上下文中用户定义的函数，再查找内置函数
+    pub fn call(self, symbol: &String, args: &Option<Vec<Expr>>, executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        if let Some(function) = context.function(symbol) {
+            return function.call(self, args.as_deref().unwrap_or(&[]), executor, context)
+        }
+
+        match function::lookup(symbol) {
+            Some(function) => {
+                let args = args.as_deref().unwrap_or(&[]);
+                function.check_arity(args.len())?;
+                (function.exec)(self, args, executor, context)
             },
-            other => Err(FhirError::Message(format!("这是无效或者未被支持的函数名[{}]", other))),
+            None => Err(FhirError::Message(format!("这是无效或者未被支持的函数名[{}]", symbol))),
+        }
+    }
+
+    /// 集合中是否已经存在与该元素相等的元素
+    /// 对于不支持比较运算的复杂类型，比较两者的完整内容
+    pub fn contains_item(&self, item: &dyn Executor) -> bool {
+        self.iter().any(|part| {
+            match part.eq(item) {
+                Ok(bl) => bl,
+                Err(_) => format!("{:?}", part) == format!("{:?}", item),
+            }
+        })
+    }
+
+    /// 去除集合中重复的元素，保留元素第一次出现的顺序
+    pub fn distinct(self) -> Collection<'a> {
+        let mut collection = Collection::new();
+        for part in self {
+            if !collection.contains_item(part.as_ref()) {
+                collection.push(part)
+            }
         }
+
+        collection
     }
 
-    pub fn single(self) -> Result<Collection> {
+    /// 合并运算[|]，合并两个集合并去除重复的元素
+    pub fn union(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        let mut collection = self;
+        collection.combine(right);
+        Ok(collection.distinct())
+    }
+
+    /// 成员运算[in]，[contains]是交换了左右两侧的[in]
+    /// 规则：
+    /// 1. 左侧为空，则结果为空
+    /// 2. 左侧的元素数量大于1，则报错
+    /// 3. 右侧为空，则结果为false
+    pub fn is_in(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        if self.empty() {
+            return Ok(Collection::new())
+        }
+
+        if self.count() > 1 {
+            return Err(FhirError::error("集合内元素数量大于1"))
+        }
+
+        Ok(Collection::new_boolean(right.contains_item(self.0[0].as_ref())))
+    }
+
+    /// 蕴含运算[implies]
+    /// 左侧为false时结果为true；左侧为空时，只有右侧为true结果才为true，否则为空
+    pub fn implies(self, right: Collection<'a>) -> Result<Option<bool>> {
+        match (self.to_boolean()?, right.to_boolean()?) {
+            (Some(false), _) => Ok(Some(true)),
+            (Some(true), other) => Ok(other),
+            (None, Some(true)) => Ok(Some(true)),
+            (None, _) => Ok(None),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Executor> {
+        self.0.iter().map(|part| part.as_ref())
+    }
+
+    /// 遍历集合中的元素，与[`Collection::iter`]不同，子元素和计算结果的生命周期与集合相同
+    pub fn items(&self) -> impl Iterator<Item = &Item<'a>> {
+        self.0.iter()
+    }
+
+    pub fn single(self) -> Result<Collection<'a>> {
         if self.count() > 1 {
             return Err(FhirError::error("执行single函数时集合内超过一个元素"))
         }
@@ -136,7 +336,9 @@ impl Collection {
     /// 规则：
     /// 1. 两侧任何一侧为空，则结果为空
     /// 2. 如果两侧集合数量不相等，则结果为false
-    pub fn eq(self, right: Collection) -> Result<Collection> {
+    /// 3. 数量按单位换算后比较，单位无法换算时结果为空
+    /// 4. 日期时间在双方共有的精度范围内比较，共有的部分相同但精度不同时结果为空
+    pub fn eq(self, right: Collection<'a>) -> Result<Collection<'a>> {
         if self.empty() | right.empty() {
             return Ok(Collection::new())
         }
@@ -149,80 +351,181 @@ impl Collection {
             let lhs = part.as_ref();
             let rhs = right.0[idx].as_ref();
 
-            if !lhs.eq(rhs)? {
-                return Ok(Collection::new_boolean(false))
+            let equal = match (system_type(lhs), system_type(rhs)) {
+                (SystemType::Quantity, SystemType::Quantity) => quantity::compare(lhs, rhs)?.map(|ord| ord == Ordering::Equal),
+                (SystemType::Date | SystemType::DateTime, SystemType::Date | SystemType::DateTime)
+                    | (SystemType::Time, SystemType::Time) => lhs.compare(rhs)?.map(|ord| ord == Ordering::Equal),
+                _ => Some(lhs.eq(rhs)?),
+            };
+
+            match equal {
+                Some(true) => continue,
+                Some(false) => return Ok(Collection::new_boolean(false)),
+                None => return Ok(Collection::new()),
             }
         }
 
         Ok(Collection::new_boolean(true))
     }
 
-    pub fn not(self) -> Result<Collection> {
+    pub fn not(self) -> Result<Collection<'a>> {
         match self.to_boolean()? {
             None => Ok(Collection::new()),
             Some(bl) => Ok(Collection::new_boolean(bl.not()))
         }
     }
 
-    pub fn gt(self, _right: Collection) -> Result<Collection> {
-        Ok(Collection::new_boolean(true))
+    pub fn gt(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        self.compare(right, |ord| ord == Ordering::Greater)
     }
 
-    pub fn ge(self, _right: Collection) -> Result<Collection> {
-        Ok(Collection::new_boolean(true))
+    pub fn ge(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        self.compare(right, |ord| ord != Ordering::Less)
     }
 
-    pub fn lt(self, _right: Collection) -> Result<Collection> {
-        Ok(Collection::new_boolean(true))
+    pub fn lt(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        self.compare(right, |ord| ord == Ordering::Less)
     }
 
-    pub fn le(self, _right: Collection) -> Result<Collection> {
-        Ok(Collection::new_boolean(true))
+    pub fn le(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        self.compare(right, |ord| ord != Ordering::Greater)
     }
-}
 
-impl Add for Collection {
-    type Output = Result<Collection>;
+    /// 整除运算[div]
+    pub fn int_div(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        self.arithmetic(right, arithmetic::int_div)
+    }
 
-    fn add(self, rhs: Self) -> Self::Output {
-        if self.empty() | rhs.empty() {
+    /// 取余运算[mod]
+    pub fn modulo(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        self.arithmetic(right, arithmetic::modulo)
+    }
+
+    /// 字符串连接运算[&]，空集合被当作空字符串处理
+    pub fn concat(self, right: Collection<'a>) -> Result<Collection<'a>> {
+        let lhs = self.to_strings()?.unwrap_or_default();
+        let rhs = right.to_strings()?.unwrap_or_default();
+        Ok(Collection::new_string(lhs + &rhs))
+    }
+
+    /// 正负号运算[+ -]
+    /// 规则：
+    /// 1. 集合为空，则结果为空
+    /// 2. 元素数量大于1，则报错
+    /// 3. 只能作用于整数、小数和数量
+    pub fn polarity(self, op: Operator) -> Result<Collection<'a>> {
+        if self.count() > 1 {
+            return Err(FhirError::error("集合内元素数量大于1"))
+        }
+
+        let part = match self.0.first() {
+            Some(part) => part.as_ref(),
+            None => return Ok(Collection::new()),
+        };
+
+        match op {
+            Operator::Sub => match arithmetic::negate(part)? {
+                Some(value) => Ok(Collection::new_any(value)),
+                None => Ok(Collection::new()),
+            },
+            _ => match system_type(part) {
+                SystemType::Integer | SystemType::Decimal | SystemType::Quantity => Ok(self),
+                other => Err(FhirError::Message(format!("不支持的运算[+{:?}]", other))),
+            },
+        }
+    }
+
+    /// 算术运算
+    /// 规则：
+    /// 1. 两侧任何一侧为空，则结果为空
+    /// 2. 任何一侧的元素数量大于1，则报错
+    fn arithmetic(self, right: Collection<'a>, op: fn(&dyn Executor, &dyn Executor) -> Result<arithmetic::Value>) -> Result<Collection<'a>> {
+        if self.empty() | right.empty() {
+            return Ok(Collection::new())
+        }
+
+        if (self.count() > 1) | (right.count() > 1) {
+            return Err(FhirError::error("集合内元素数量大于1"))
+        }
+
+        match op(self.0[0].as_ref(), right.0[0].as_ref())? {
+            Some(value) => Ok(Collection::new_any(value)),
+            None => Ok(Collection::new()),
+        }
+    }
+
+    /// 左侧集合与右侧集合比较大小
+    /// 规则：
+    /// 1. 两侧任何一侧为空，则结果为空
+    /// 2. 任何一侧的元素数量大于1，则报错
+    /// 3. 无法确定大小关系时（如精度不同的日期时间、单位不同的数量），结果为空
+    fn compare(self, right: Collection<'a>, matched: fn(Ordering) -> bool) -> Result<Collection<'a>> {
+        if self.empty() | right.empty() {
             return Ok(Collection::new())
         }
 
-        if (self.count() > 1) | (rhs.count() > 1) {
+        if (self.count() > 1) | (right.count() > 1) {
             return Err(FhirError::error("集合内元素数量大于1"))
         }
-        // self.first() + rhs.first()
-        // 由第一个元素，决定掉第二个元素的to_xxxx() 来实现相加
-        Ok(Collection::new())
+
+        let lhs = self.0[0].as_ref();
+        let rhs = right.0[0].as_ref();
+
+        let ord = if quantity::is_quantity(lhs) && quantity::is_quantity(rhs) {
+            quantity::compare(lhs, rhs)?
+        } else {
+            lhs.compare(rhs)?
+        };
+
+        match ord {
+            None => Ok(Collection::new()),
+            Some(ord) => Ok(Collection::new_boolean(matched(ord))),
+        }
+    }
+}
+
+impl<'a> IntoIterator for Collection<'a> {
+    type Item = Item<'a>;
+    type IntoIter = std::vec::IntoIter<Item<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> Add for Collection<'a> {
+    type Output = Result<Collection<'a>>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.arithmetic(rhs, arithmetic::add)
     }
 }
 
-impl Sub for Collection {
-    type Output = Result<Collection>;
+impl<'a> Sub for Collection<'a> {
+    type Output = Result<Collection<'a>>;
 
-    fn sub(self, _rhs: Self) -> Self::Output {
-        todo!()
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.arithmetic(rhs, arithmetic::sub)
     }
 }
 
-impl Mul for Collection {
-    type Output = Result<Collection>;
+impl<'a> Mul for Collection<'a> {
+    type Output = Result<Collection<'a>>;
 
-    fn mul(self, _rhs: Self) -> Self::Output {
-        todo!()
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.arithmetic(rhs, arithmetic::mul)
     }
 }
 
-impl Div for Collection {
-    type Output = Result<Collection>;
+impl<'a> Div for Collection<'a> {
+    type Output = Result<Collection<'a>>;
 
-    fn div(self, _rhs: Self) -> Self::Output {
-        todo!()
+    fn div(self, rhs: Self) -> Self::Output {
+        self.arithmetic(rhs, arithmetic::div)
     }
 }
 
-impl BitAnd for Collection {
+impl BitAnd for Collection<'_> {
     type Output = Result<Option<bool>>;
 
     fn bitand(self, rhs: Self) -> Self::Output {
@@ -236,7 +539,7 @@ impl BitAnd for Collection {
     }
 }
 
-impl BitOr for Collection {
+impl BitOr for Collection<'_> {
     type Output = Result<Option<bool>>;
 
     fn bitor(self, rhs: Self) -> Self::Output {
@@ -250,7 +553,7 @@ impl BitOr for Collection {
     }
 }
 
-impl BitXor for Collection {
+impl BitXor for Collection<'_> {
     type Output = Result<Option<bool>>;
 
     fn bitxor(self, rhs: Self) -> Self::Output {
@@ -0,0 +1,137 @@
+//! 编译后的FHIRPath表达式
+//!
+//! [`CompiledPath`]只解析一次表达式，之后可以在多个线程中对任意多个资源重复计算；
+//! [`PathCache`]按表达式文本缓存编译结果，容量满时淘汰最久未使用的表达式。
+use std::fmt::{Display, Formatter};
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use lru::LruCache;
+use crate::prelude::{Result, FhirError};
+use super::*;
+
+/// 编译后的表达式，克隆时共享同一个语法树
+#[derive(Debug, Clone)]
+pub struct CompiledPath {
+    text: Arc<str>,
+    expr: Arc<Expr>,
+}
+
+impl CompiledPath {
+    pub fn compile(text: &str) -> Result<Self> {
+        let expr = Expr::parse(text.to_string())?;
+        Ok(CompiledPath { text: Arc::from(text), expr: Arc::new(expr) })
+    }
+
+    /// 表达式的原始文本
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// 计算表达式，`%context`、`%resource`、`%rootResource`都是该元素
+    pub fn eval<'a>(&self, executor: &'a dyn Executor) -> Result<Collection<'a>> {
+        self.expr.eval(executor).map_err(|err| err.with_expression(&self.text))
+    }
+
+    /// 在指定的上下文中计算表达式
+    pub fn eval_with<'a>(&self, executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        self.expr.eval_with(executor, context).map_err(|err| err.with_expression(&self.text))
+    }
+}
+
+impl FromStr for CompiledPath {
+    type Err = FhirError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        CompiledPath::compile(s)
+    }
+}
+
+impl Display for CompiledPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// 表达式的LRU缓存，可以在多个线程之间共享
+#[derive(Debug)]
+pub struct PathCache {
+    paths: Mutex<LruCache<String, CompiledPath>>,
+}
+
+impl PathCache {
+    /// 默认缓存的表达式数量
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// 创建指定容量的缓存，容量为0时按1处理
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        PathCache { paths: Mutex::new(LruCache::new(capacity)) }
+    }
+
+    /// 读取缓存的表达式，不存在时编译并放入缓存，编译失败的表达式不会被缓存
+    pub fn get_or_compile(&self, text: &str) -> Result<CompiledPath> {
+        if let Some(path) = self.lock()?.get(text) {
+            return Ok(path.clone())
+        }
+
+        let path = CompiledPath::compile(text)?;
+        self.lock()?.put(text.to_string(), path.clone());
+        Ok(path)
+    }
+
+    /// 缓存中表达式的数量
+    pub fn len(&self) -> usize {
+        self.lock().map_or(0, |paths| paths.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.lock().map_or(0, |paths| paths.cap().get())
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut paths) = self.lock() {
+            paths.clear();
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, LruCache<String, CompiledPath>>> {
+        self.paths.lock().map_err(|_| FhirError::error("表达式缓存的锁已失效"))
+    }
+}
+
+impl Default for PathCache {
+    fn default() -> Self {
+        PathCache::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[test]
+pub fn test_path_cache() -> Result<()> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<CompiledPath>();
+    assert_send_sync::<PathCache>();
+
+    let cache = PathCache::new(2);
+    let name = cache.get_or_compile("Patient.name")?;
+    assert!(Arc::ptr_eq(&name.expr, &cache.get_or_compile("Patient.name")?.expr));
+    assert_eq!(cache.len(), 1);
+
+    cache.get_or_compile("Patient.gender")?;
+    cache.get_or_compile("Patient.name")?;
+    cache.get_or_compile("Patient.active")?;
+    assert_eq!(cache.len(), 2);
+    assert!(Arc::ptr_eq(&name.expr, &cache.get_or_compile("Patient.name")?.expr));
+
+    assert!(cache.get_or_compile("Patient.name.where(").is_err());
+    assert_eq!(cache.len(), 2);
+    Ok(())
+}
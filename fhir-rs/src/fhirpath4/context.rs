@@ -0,0 +1,170 @@
+//! FHIRPath表达式的计算上下文
+//!
+//! 上下文保存了环境变量（如`%resource`、`%rootResource`、`%context`以及用户定义的变量），
+//! 以及迭代函数（如`where`、`select`、`aggregate`）绑定的`$index`和`$total`。
+//! 迭代函数为每个元素创建一个子上下文，子上下文与父上下文共享变量、引用解析器、术语服务和用户定义的函数。
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::prelude::{Result, FhirError, Integer};
+use super::*;
+
+pub struct EvalContext<'a> {
+    context: Option<&'a dyn Executor>,
+    resource: Option<&'a dyn Executor>,
+    root_resource: Option<&'a dyn Executor>,
+    variables: Rc<HashMap<String, Collection<'a>>>,
+    resolvers: Rc<Vec<&'a dyn ReferenceResolver>>,
+    terminology: Option<&'a dyn TerminologyProvider>,
+    functions: Option<&'a FunctionRegistry>,
+    index: Option<usize>,
+    total: Option<Collection<'a>>,
+}
+
+impl<'a> EvalContext<'a> {
+    /// 创建一个上下文，`%context`、`%resource`、`%rootResource`默认都是该元素
+    pub fn new(context: &'a dyn Executor) -> Self {
+        EvalContext {
+            context: Some(context),
+            resource: Some(context),
+            root_resource: Some(context),
+            variables: Rc::new(HashMap::new()),
+            resolvers: Rc::new(vec![&ContainedResolver, &BundleResolver]),
+            terminology: None,
+            functions: None,
+            index: None,
+            total: None,
+        }
+    }
+
+    /// 设置`%resource`，即包含当前元素的资源
+    pub fn with_resource(mut self, resource: &'a dyn Executor) -> Self {
+        self.resource = Some(resource);
+        self
+    }
+
+    /// 设置`%rootResource`，即包含`%resource`的容器资源，如Bundle
+    pub fn with_root_resource(mut self, root_resource: &'a dyn Executor) -> Self {
+        self.root_resource = Some(root_resource);
+        self
+    }
+
+    /// 添加一个用户定义的变量，表达式中通过`%name`引用
+    pub fn with_variable<N: Into<String>>(mut self, name: N, value: Collection<'a>) -> Self {
+        Rc::make_mut(&mut self.variables).insert(name.into(), value);
+        self
+    }
+
+    /// 添加一个引用解析器，`resolve()`在内嵌资源和Bundle中都找不到被引用的资源时依次使用
+    pub fn with_resolver(mut self, resolver: &'a dyn ReferenceResolver) -> Self {
+        Rc::make_mut(&mut self.resolvers).push(resolver);
+        self
+    }
+
+    /// 设置`memberOf()`、`subsumes()`、`subsumedBy()`使用的术语服务
+    pub fn with_terminology(mut self, terminology: &'a dyn TerminologyProvider) -> Self {
+        self.terminology = Some(terminology);
+        self
+    }
+
+    /// 术语服务
+    pub fn terminology(&self) -> Option<&'a dyn TerminologyProvider> {
+        self.terminology
+    }
+
+    /// 设置用户定义的函数，与内置函数同名时优先使用用户定义的函数
+    pub fn with_functions(mut self, functions: &'a FunctionRegistry) -> Self {
+        self.functions = Some(functions);
+        self
+    }
+
+    /// 查找用户定义的函数
+    pub fn function(&self, name: &str) -> Option<&'a UserFunction> {
+        self.functions.and_then(|functions| functions.get(name))
+    }
+
+    /// 包含当前元素的资源`%resource`
+    pub fn resource(&self) -> Option<&'a dyn Executor> {
+        self.resource
+    }
+
+    /// 包含`%resource`的容器资源`%rootResource`
+    pub fn root_resource(&self) -> Option<&'a dyn Executor> {
+        self.root_resource
+    }
+
+    /// 依次使用各个解析器查找被引用的资源
+    pub fn resolve(&self, reference: &str) -> Result<Option<Item<'a>>> {
+        for resolver in self.resolvers.iter() {
+            if let Some(resource) = resolver.resolve(reference, self)? {
+                return Ok(Some(resource))
+            }
+        }
+        Ok(None)
+    }
+
+    /// 为迭代中的元素创建子上下文，绑定`$index`
+    pub(crate) fn iteration(&self, index: usize) -> EvalContext<'a> {
+        EvalContext {
+            context: self.context,
+            resource: self.resource,
+            root_resource: self.root_resource,
+            variables: self.variables.clone(),
+            resolvers: self.resolvers.clone(),
+            terminology: self.terminology,
+            functions: self.functions,
+            index: Some(index),
+            total: None,
+        }
+    }
+
+    /// 为`aggregate`函数创建子上下文，绑定`$index`和`$total`
+    pub(crate) fn aggregation(&self, index: usize, total: Collection<'a>) -> EvalContext<'a> {
+        EvalContext {
+            total: Some(total),
+            ..self.iteration(index)
+        }
+    }
+
+    /// 读取环境变量
+    pub fn variable(&self, name: &str) -> Result<Collection<'a>> {
+        match name {
+            "context" => Ok(self.context.map_or_else(Collection::new, |context| context.to_collection(&None))),
+            "resource" => Ok(self.resource.map_or_else(Collection::new, |resource| resource.to_collection(&None))),
+            "rootResource" => Ok(self.root_resource.map_or_else(Collection::new, |root| root.to_collection(&None))),
+            "ucum" => Ok(Collection::new_string("http://unitsofmeasure.org".to_string())),
+            "sct" => Ok(Collection::new_string("http://snomed.info/sct".to_string())),
+            "loinc" => Ok(Collection::new_string("http://loinc.org".to_string())),
+            other => {
+                if let Some(value) = self.variables.get(other) {
+                    return Ok(value.clone())
+                }
+
+                if let Some(id) = other.strip_prefix("vs-") {
+                    return Ok(Collection::new_string(format!("http://hl7.org/fhir/ValueSet/{}", id)))
+                }
+
+                if let Some(id) = other.strip_prefix("ext-") {
+                    return Ok(Collection::new_string(format!("http://hl7.org/fhir/StructureDefinition/{}", id)))
+                }
+
+                Err(FhirError::Message(format!("未定义的环境变量[%{}]", other)))
+            },
+        }
+    }
+
+    /// 当前迭代元素的索引`$index`，不在迭代中时结果为空
+    pub fn index(&self) -> Collection<'a> {
+        match self.index {
+            Some(index) => Collection::new_integer(index as Integer),
+            None => Collection::new(),
+        }
+    }
+
+    /// `aggregate`函数的累计值`$total`，不在聚合中时结果为空
+    pub fn total(&self) -> Collection<'a> {
+        match &self.total {
+            Some(total) => total.clone(),
+            None => Collection::new(),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! FHIRPath的类型转换函数
+//!
+//! 每种转换都有`toX()`和`convertsToX()`两个函数：
+//! 输入集合为空时结果为空，元素数量大于1时报错；无法转换时`toX()`的结果为空，`convertsToX()`的结果为false。
+use std::str::FromStr;
+use regex::Regex;
+use crate::prelude::{Result, FhirError, Integer, Decimal, Boolean, Date, DateTime, Time, Quantity};
+use super::*;
+use super::quantity::CalendarUnit;
+
+/// 输入集合中唯一的元素
+pub(crate) fn single<'a>(input: &'a Collection) -> Result<Option<&'a dyn Executor>> {
+    match input.count() {
+        0 => Ok(None),
+        1 => Ok(input.iter().next()),
+        _ => Err(FhirError::error("集合内元素数量大于1")),
+    }
+}
+
+macro_rules! conversions {
+    (
+        $(($to: ident, $converts_to: ident, $convert: ident),)+
+    ) => {
+        $(
+            pub(crate) fn $to<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+                match single(&input)? {
+                    Some(item) => Ok($convert(item)?.map_or_else(Collection::new, |value| Collection::new_any(Box::new(value)))),
+                    None => Ok(Collection::new()),
+                }
+            }
+
+            pub(crate) fn $converts_to<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+                match single(&input)? {
+                    Some(item) => Ok(Collection::new_boolean($convert(item)?.is_some())),
+                    None => Ok(Collection::new()),
+                }
+            }
+        )+
+    };
+}
+
+conversions! {
+    (to_boolean, converts_to_boolean, convert_boolean),
+    (to_integer, converts_to_integer, convert_integer),
+    (to_decimal, converts_to_decimal, convert_decimal),
+    (to_string, converts_to_string, convert_string),
+    (to_date, converts_to_date, convert_date),
+    (to_date_time, converts_to_date_time, convert_datetime),
+    (to_time, converts_to_time, convert_time),
+}
+
+pub(crate) fn to_quantity<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let unit = quantity_unit_arg(args, executor, context)?;
+    match single(&input)? {
+        Some(item) => Ok(convert_quantity(item, unit.as_deref())?.map_or_else(Collection::new, |value| Collection::new_any(Box::new(value)))),
+        None => Ok(Collection::new()),
+    }
+}
+
+pub(crate) fn converts_to_quantity<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let unit = quantity_unit_arg(args, executor, context)?;
+    match single(&input)? {
+        Some(item) => Ok(Collection::new_boolean(convert_quantity(item, unit.as_deref())?.is_some())),
+        None => Ok(Collection::new()),
+    }
+}
+
+fn quantity_unit_arg(args: &[Expr], executor: &dyn Executor, context: &EvalContext) -> Result<Option<String>> {
+    match args.first() {
+        Some(arg) => arg.eval_with(executor, context)?.to_strings(),
+        None => Ok(None),
+    }
+}
+
+/// 字符串`true`、`t`、`yes`、`y`、`1`、`1.0`转换为true，`false`、`f`、`no`、`n`、`0`、`0.0`转换为false，不区分大小写
+fn convert_boolean(item: &dyn Executor) -> Result<Option<Boolean>> {
+    match system_type(item) {
+        SystemType::Boolean => Ok(Some(item.to_boolean()?)),
+        SystemType::Integer => {
+            match item.to_integer()? {
+                1 => Ok(Some(true)),
+                0 => Ok(Some(false)),
+                _ => Ok(None),
+            }
+        },
+        SystemType::Decimal => {
+            let value = item.to_decimal()?;
+            if value == 1.0 {
+                Ok(Some(true))
+            } else if value == 0.0 {
+                Ok(Some(false))
+            } else {
+                Ok(None)
+            }
+        },
+        SystemType::String => {
+            match item.to_strings()?.to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "1" | "1.0" => Ok(Some(true)),
+                "false" | "f" | "no" | "n" | "0" | "0.0" => Ok(Some(false)),
+                _ => Ok(None),
+            }
+        },
+        _ => Ok(None),
+    }
+}
+
+fn convert_integer(item: &dyn Executor) -> Result<Option<Integer>> {
+    match system_type(item) {
+        SystemType::Integer => Ok(Some(item.to_integer()?)),
+        SystemType::Boolean => Ok(Some(if item.to_boolean()? { 1 } else { 0 })),
+        SystemType::String => Ok(item.to_strings()?.parse().ok()),
+        _ => Ok(None),
+    }
+}
+
+fn convert_decimal(item: &dyn Executor) -> Result<Option<Decimal>> {
+    match system_type(item) {
+        SystemType::Integer | SystemType::Decimal => Ok(Some(item.to_decimal()?)),
+        SystemType::Boolean => Ok(Some(if item.to_boolean()? { 1.0 } else { 0.0 })),
+        SystemType::String => {
+            let value = item.to_strings()?;
+            if is_decimal(&value) {
+                Ok(Some(value.parse()?))
+            } else {
+                Ok(None)
+            }
+        },
+        _ => Ok(None),
+    }
+}
+
+/// 数量转换为`数值 '单位'`的形式，如`4 'mg'`
+fn convert_string(item: &dyn Executor) -> Result<Option<String>> {
+    match system_type(item) {
+        SystemType::Quantity => {
+            Ok(quantity::value_and_unit(item)?.map(|(value, unit)| format!("{} '{}'", value, unit)))
+        },
+        SystemType::Other => Ok(None),
+        _ => Ok(Some(item.to_strings()?)),
+    }
+}
+
+/// 日期时间转换为日期时截断时间部分
+fn convert_date(item: &dyn Executor) -> Result<Option<Date>> {
+    let datetime = match system_type(item) {
+        SystemType::Date | SystemType::DateTime => item.to_datetime()?,
+        SystemType::String => {
+            match DateTime::from_str(&item.to_strings()?) {
+                Ok(datetime) => datetime,
+                Err(_) => return Ok(None),
+            }
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(Date(datetime.0.date_naive(), datetime.1.min(10))))
+}
+
+fn convert_datetime(item: &dyn Executor) -> Result<Option<DateTime>> {
+    match system_type(item) {
+        SystemType::Date | SystemType::DateTime => Ok(Some(item.to_datetime()?)),
+        SystemType::String => Ok(DateTime::from_str(&item.to_strings()?).ok()),
+        _ => Ok(None),
+    }
+}
+
+fn convert_time(item: &dyn Executor) -> Result<Option<Time>> {
+    match system_type(item) {
+        SystemType::Time => Ok(Some(item.to_time()?)),
+        SystemType::String => Ok(Time::from_str(&item.to_strings()?).ok()),
+        _ => Ok(None),
+    }
+}
+
+/// 数值转换为单位为`'1'`的数量；字符串的格式为`数值 '单位'`或者`数值 日历时间单位`，如`4 'mg'`、`3 days`
+///
+/// 指定了单位时，数量的单位必须能够换算为该单位，如`1 'g'.toQuantity('mg')`
+fn convert_quantity(item: &dyn Executor, unit: Option<&str>) -> Result<Option<Quantity>> {
+    let (value, item_unit) = match system_type(item) {
+        SystemType::Integer | SystemType::Decimal => (item.to_decimal()?, "1".to_string()),
+        SystemType::Boolean => (if item.to_boolean()? { 1.0 } else { 0.0 }, "1".to_string()),
+        SystemType::Quantity => {
+            match quantity::value_and_unit(item)? {
+                Some(value_and_unit) => value_and_unit,
+                None => return Ok(None),
+            }
+        },
+        SystemType::String => {
+            match parse_quantity(&item.to_strings()?)? {
+                Some(value_and_unit) => value_and_unit,
+                None => return Ok(None),
+            }
+        },
+        _ => return Ok(None),
+    };
+
+    match unit {
+        Some(unit) => Ok(quantity::convert(value, &item_unit, unit).map(|value| quantity::new_quantity(value, unit))),
+        None => Ok(Some(quantity::new_quantity(value, &item_unit))),
+    }
+}
+
+fn parse_quantity(value: &str) -> Result<Option<(Decimal, String)>> {
+    let regex = Regex::new(r"^\s*([+-]?\d+(?:\.\d+)?)\s*(?:'([^']+)'|([a-z]+))?\s*$")?;
+    let captures = match regex.captures(value) {
+        Some(captures) => captures,
+        None => return Ok(None),
+    };
+
+    let unit = match (captures.get(2), captures.get(3)) {
+        (Some(unit), _) => unit.as_str().to_string(),
+        (None, Some(keyword)) if CalendarUnit::is_keyword(keyword.as_str()) => keyword.as_str().to_string(),
+        (None, Some(_)) => return Ok(None),
+        (None, None) => "1".to_string(),
+    };
+
+    Ok(Some((captures[1].parse()?, unit)))
+}
+
+/// 是否为FHIRPath的小数格式，如`+1.5`、`-2`，不接受科学计数法
+fn is_decimal(value: &str) -> bool {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let (integer, fraction) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits, None),
+    };
+
+    !integer.is_empty() && integer.chars().all(|ch| ch.is_ascii_digit())
+        && fraction.is_none_or(|fraction| !fraction.is_empty() && fraction.chars().all(|ch| ch.is_ascii_digit()))
+}
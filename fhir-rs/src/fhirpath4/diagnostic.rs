@@ -0,0 +1,88 @@
+//! FHIRPath表达式的错误位置
+//!
+//! 解析和计算表达式时的错误都记录了出错位置在表达式中的字节范围，
+//! 可以在表达式下方用`^`标出出错的位置。
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// FHIRPath表达式在解析或者计算时的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    pub message: String,
+    /// 出错位置在表达式中的字节范围
+    pub span: Range<usize>,
+    /// 出错的表达式，只有知道表达式时才能输出诊断信息
+    pub expression: Option<String>,
+}
+
+impl PathError {
+    pub fn new<M: Into<String>>(message: M, span: Range<usize>) -> Self {
+        PathError { message: message.into(), span, expression: None }
+    }
+
+    /// 记录出错的表达式，已经记录的表达式不会被覆盖
+    pub fn with_expression(mut self, expression: &str) -> Self {
+        if self.expression.is_none() {
+            self.expression = Some(expression.to_string());
+        }
+        self
+    }
+
+    /// 输出出错的那一行表达式，并在下方用`^`标出出错的位置，如：
+    ///
+    /// ```text
+    /// Patient.name.where(given = )
+    ///                            ^
+    /// ```
+    pub fn render(&self, expression: &str) -> String {
+        let start = floor_char_boundary(expression, self.span.start);
+        let end = floor_char_boundary(expression, self.span.end.max(start));
+
+        let line_start = expression[..start].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = expression[start..].find('\n').map_or(expression.len(), |idx| start + idx);
+        let line = &expression[line_start..line_end];
+
+        // 制表符原样保留，使标记与表达式对齐
+        let indent: String = expression[line_start..start].chars()
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = expression[start..end.min(line_end)].chars().count().max(1);
+
+        format!("{}\n{}{}", line, indent, "^".repeat(width))
+    }
+
+    /// 带有出错位置标记的诊断信息，不知道表达式时为空
+    pub fn diagnostic(&self) -> Option<String> {
+        self.expression.as_deref().map(|expression| self.render(expression))
+    }
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}，位置[{}..{}]", self.message, self.span.start, self.span.end)?;
+        if let Some(diagnostic) = self.diagnostic() {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+fn floor_char_boundary(value: &str, index: usize) -> usize {
+    let mut index = index.min(value.len());
+    while !value.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[test]
+pub fn test_render() {
+    let error = PathError::new("缺少表达式", 27..28);
+    assert_eq!(error.render("Patient.name.where(given = )"), "Patient.name.where(given = )\n                           ^");
+
+    let error = PathError::new("无效的路径名", 15..19);
+    assert_eq!(error.render("Patient.name\n\t.nmae.given"), "\t.nmae.given\n\t ^^^^");
+
+    let error = PathError::new("缺少表达式", 12..13).with_expression("Patient.name");
+    assert_eq!(error.diagnostic(), Some("Patient.name\n            ^".to_string()));
+}
@@ -1,18 +1,36 @@
+use std::cmp::Ordering;
+use chrono::{Datelike, Timelike};
 use crate::{impl_executor_for_anytype, impl_executor_for_primitive};
 use super::*;
 use crate::prelude::*;
 
-pub trait Executor: Base + Convert + Compare {
+pub trait Executor: Base + Convert + Compare + BoxedExecutor {
 
-    fn child(&self, _index: usize) -> Result<Collection> {
+    fn child(&self, _index: usize) -> Result<Collection<'_>> {
         Err(FhirError::error("该数据类型不支持获取子元素"))
     }
 
-    fn element(&self, _symbol: &String, _index: &Option<usize>) -> crate::prelude::Result<Collection> {
+    fn element(&self, _symbol: &String, _index: &Option<usize>) -> crate::prelude::Result<Collection<'_>> {
         Err(FhirError::error("该数据类型不支持获取子路径"))
     }
 
-    fn to_collection(&self, index: &Option<usize>) -> Collection;
+    fn to_collection(&self, index: &Option<usize>) -> Collection<'_>;
+
+    /// 所有的子元素，System类型没有子元素
+    fn children(&self) -> Collection<'_> {
+        Collection::new()
+    }
+}
+
+/// 将元素复制到堆上，用于从计算得到的值中取出子元素
+pub trait BoxedExecutor {
+    fn boxed_clone(&self) -> Box<dyn Executor>;
+}
+
+impl<T: Executor + Clone + 'static> BoxedExecutor for T {
+    fn boxed_clone(&self) -> Box<dyn Executor> {
+        Box::new(self.clone())
+    }
 }
 
 pub trait Convert {
@@ -32,6 +50,10 @@ pub trait Convert {
         Err(FhirError::error("该数据类型不能转换为日期和时间"))
     }
 
+    fn to_time(&self) -> Result<Time> {
+        Err(FhirError::error("该数据类型不能转换为时间"))
+    }
+
     fn to_boolean(&self) -> Result<Boolean> {
         Err(FhirError::error("该数据类型不能转换为布尔"))
     }
@@ -43,20 +65,26 @@ pub trait Compare {
         Err(FhirError::error("该数据类型不支持比较运算符[==]"))
     }
 
-    fn gt(&self, _right: &dyn Executor) -> Result<bool> {
-        Err(FhirError::error("该数据类型不支持比较运算符[>]"))
+    /// 比较两个值的大小
+    /// 无法确定大小关系时（如精度不同的日期时间）返回None
+    fn compare(&self, _right: &dyn Executor) -> Result<Option<Ordering>> {
+        Err(FhirError::error("该数据类型不支持比较运算符[>, >=, <, <=]"))
+    }
+
+    fn gt(&self, right: &dyn Executor) -> Result<Option<bool>> {
+        Ok(self.compare(right)?.map(|ord| ord == Ordering::Greater))
     }
 
-    fn ge(&self, _right: &dyn Executor) -> Result<bool> {
-        Err(FhirError::error("该数据类型不支持比较运算符[>=]"))
+    fn ge(&self, right: &dyn Executor) -> Result<Option<bool>> {
+        Ok(self.compare(right)?.map(|ord| ord != Ordering::Less))
     }
 
-    fn lt(&self, _right: &dyn Executor) -> Result<bool> {
-        Err(FhirError::error("该数据类型不支持比较运算符[<]"))
+    fn lt(&self, right: &dyn Executor) -> Result<Option<bool>> {
+        Ok(self.compare(right)?.map(|ord| ord == Ordering::Less))
     }
 
-    fn le(&self, _right: &dyn Executor) -> Result<bool> {
-        Err(FhirError::error("该数据类型不支持比较运算符[<=]"))
+    fn le(&self, right: &dyn Executor) -> Result<Option<bool>> {
+        Ok(self.compare(right)?.map(|ord| ord != Ordering::Greater))
     }
 }
 
@@ -66,43 +94,203 @@ macro_rules! impl_executor {
     ) => {
         $(
             impl Executor for $ty {
-                fn to_collection(&self, _index: &Option<usize>) -> Collection {
-                    Collection::new_any(Box::new(self.clone()))
+                fn to_collection(&self, _index: &Option<usize>) -> Collection<'_> {
+                    Collection::new_ref(self)
                 }
             }
         )+
     }
 }
 
-macro_rules! impl_convert {
+impl_executor!(usize, isize, i64, f64, bool, Instant, DateTime, Time, Date, String, Xhtml,);
+
+macro_rules! impl_convert_for_datetime {
     (
         $($ty:ident,)+
     ) => {
         $(
-            impl Convert for $ty {}
+            impl Convert for $ty {
+                fn to_strings(&self) -> Result<String> {
+                    Ok(self.to_string())
+                }
+
+                fn to_datetime(&self) -> Result<DateTime> {
+                    Ok(self.clone().into())
+                }
+            }
+
+            impl Compare for $ty {
+                fn eq(&self, right: &dyn Executor) -> Result<bool> {
+                    Ok(self.compare(right)? == Some(Ordering::Equal))
+                }
+
+                fn compare(&self, right: &dyn Executor) -> Result<Option<Ordering>> {
+                    let lhs = datetime_components(&self.to_datetime()?);
+                    let rhs = datetime_components(&right.to_datetime()?);
+                    Ok(compare_components(&lhs, &rhs))
+                }
+            }
         )+
     }
 }
 
-macro_rules! impl_compare {
+impl_convert_for_datetime!(Date, DateTime, Instant,);
+
+impl Convert for Time {
+    fn to_strings(&self) -> Result<String> {
+        Ok(self.to_string())
+    }
+
+    fn to_time(&self) -> Result<Time> {
+        Ok(self.clone())
+    }
+}
+
+impl Compare for Time {
+    fn eq(&self, right: &dyn Executor) -> Result<bool> {
+        Ok(self.compare(right)? == Some(Ordering::Equal))
+    }
+
+    fn compare(&self, right: &dyn Executor) -> Result<Option<Ordering>> {
+        let lhs = time_components(self);
+        let rhs = time_components(&right.to_time()?);
+        Ok(compare_components(&lhs, &rhs))
+    }
+}
+
+/// 将日期时间拆分为年、月、日、时、分、秒（含毫秒），只保留精度范围内的分量
+/// 含有时间的值统一转换为UTC后再拆分
+fn datetime_components(value: &DateTime) -> Vec<f64> {
+    let dt = value.0.naive_utc();
+    let components = [
+        dt.year() as f64,
+        dt.month() as f64,
+        dt.day() as f64,
+        dt.hour() as f64,
+        dt.minute() as f64,
+        dt.second() as f64 + dt.nanosecond() as f64 / 1e9,
+    ];
+
+    let len = match value.precision() {
+        Precision::Year => 1,
+        Precision::Month => 2,
+        Precision::Day => 3,
+        Precision::Hour => 4,
+        Precision::Minute => 5,
+        Precision::Second | Precision::Millisecond => 6,
+    };
+    components[..len].to_vec()
+}
+
+/// 将时间拆分为时、分、秒（含毫秒）
+fn time_components(value: &Time) -> Vec<f64> {
+    let time = value.0;
+    let components = [
+        time.hour() as f64,
+        time.minute() as f64,
+        time.second() as f64 + time.nanosecond() as f64 / 1e9,
+    ];
+
+    let len = match value.precision() {
+        Precision::Hour => 1,
+        Precision::Minute => 2,
+        _ => 3,
+    };
+    components[..len].to_vec()
+}
+
+/// 在双方共有的精度范围内逐个比较时间分量
+/// 共有的分量都相等，但双方精度不同时，无法确定大小关系
+fn compare_components(lhs: &[f64], rhs: &[f64]) -> Option<Ordering> {
+    for (l, r) in lhs.iter().zip(rhs) {
+        match l.partial_cmp(r)? {
+            Ordering::Equal => continue,
+            other => return Some(other),
+        }
+    }
+
+    if lhs.len() == rhs.len() {
+        Some(Ordering::Equal)
+    } else {
+        None
+    }
+}
+
+macro_rules! impl_convert_for_number {
     (
         $($ty:ident,)+
     ) => {
         $(
-            impl Compare for $ty {}
+            impl Convert for $ty {
+                fn to_integer(&self) -> Result<Integer> {
+                    Ok(*self as Integer)
+                }
+
+                fn to_decimal(&self) -> Result<Decimal> {
+                    Ok(*self as Decimal)
+                }
+
+                fn to_strings(&self) -> Result<String> {
+                    Ok(self.to_string())
+                }
+            }
+
+            impl Compare for $ty {
+                fn eq(&self, right: &dyn Executor) -> Result<bool> {
+                    let rhs = right.to_decimal()?;
+                    Ok(*self as Decimal == rhs)
+                }
+
+                fn compare(&self, right: &dyn Executor) -> Result<Option<Ordering>> {
+                    let rhs = right.to_decimal()?;
+                    Ok((*self as Decimal).partial_cmp(&rhs))
+                }
+            }
         )+
     }
 }
 
-impl_executor!(usize, isize, i64, f64, bool, Instant, DateTime, Time, Date, String, Xhtml,);
-impl_convert!(usize, isize, i64, f64, Instant, DateTime, Time, Date,);
-impl_compare!(usize, isize, i64, f64, bool, Instant, DateTime, Time, Date,);
+impl_convert_for_number!(usize, isize, i64,);
+
+impl Convert for f64 {
+    fn to_decimal(&self) -> Result<Decimal> {
+        Ok(*self)
+    }
+
+    fn to_strings(&self) -> Result<String> {
+        Ok(self.to_string())
+    }
+}
+
+impl Compare for f64 {
+    fn eq(&self, right: &dyn Executor) -> Result<bool> {
+        let rhs = right.to_decimal()?;
+        Ok(*self == rhs)
+    }
+
+    fn compare(&self, right: &dyn Executor) -> Result<Option<Ordering>> {
+        let rhs = right.to_decimal()?;
+        Ok(self.partial_cmp(&rhs))
+    }
+}
+
+impl Compare for bool {
+    fn eq(&self, right: &dyn Executor) -> Result<bool> {
+        let rhs = right.to_boolean()?;
+        Ok(*self == rhs)
+    }
+}
 
 impl Compare for String {
     fn eq(&self, right: &dyn Executor) -> Result<bool> {
         let rhs = right.to_strings()?;
         Ok(*self == rhs)
     }
+
+    fn compare(&self, right: &dyn Executor) -> Result<Option<Ordering>> {
+        let rhs = right.to_strings()?;
+        Ok(Some(self.as_str().cmp(rhs.as_str())))
+    }
 }
 impl Convert for String {
 
@@ -128,21 +316,25 @@ impl Convert for Boolean {
     fn to_boolean(&self) -> Result<Boolean> {
         Ok(*self)
     }
+
+    fn to_strings(&self) -> Result<String> {
+        Ok(self.to_string())
+    }
 }
 
 
-impl<T: Executor> Executor for Box<T> {
-    fn to_collection(&self, index: &Option<usize>) -> Collection {
+impl<T: Executor + Clone + 'static> Executor for Box<T> {
+    fn to_collection(&self, index: &Option<usize>) -> Collection<'_> {
         self.as_ref().to_collection(index)
     }
 }
 
-impl<T: Executor> Convert for Box<T> {}
-impl<T: Executor> Compare for Box<T> {}
+impl<T: Executor + Clone + 'static> Convert for Box<T> {}
+impl<T: Executor + Clone + 'static> Compare for Box<T> {}
 
-impl<T: Executor> Executor for Option<T> {
+impl<T: Executor + Clone + 'static> Executor for Option<T> {
 
-    fn to_collection(&self, index: &Option<usize>) -> Collection {
+    fn to_collection(&self, index: &Option<usize>) -> Collection<'_> {
         match self {
             None => Collection::new(),
             Some(value) => value.to_collection(index),
@@ -150,11 +342,11 @@ impl<T: Executor> Executor for Option<T> {
     }
 }
 
-impl<T: Executor> Convert for Option<T> {}
-impl<T: Executor> Compare for Option<T> {}
+impl<T: Executor + Clone + 'static> Convert for Option<T> {}
+impl<T: Executor + Clone + 'static> Compare for Option<T> {}
 
-impl<T: Executor> Executor for Vec<T> {
-    fn to_collection(&self, index: &Option<usize>) -> Collection {
+impl<T: Executor + Clone + 'static> Executor for Vec<T> {
+    fn to_collection(&self, index: &Option<usize>) -> Collection<'_> {
         match index {
             Some(idx) => {
                 match self.get(*idx) {
@@ -173,8 +365,8 @@ impl<T: Executor> Executor for Vec<T> {
     }
 }
 
-impl<T: Executor> Convert for Vec<T> {}
-impl<T: Executor> Compare for Vec<T> {}
+impl<T: Executor + Clone + 'static> Convert for Vec<T> {}
+impl<T: Executor + Clone + 'static> Compare for Vec<T> {}
 
 impl_executor_for_primitive!{
     StringDt,
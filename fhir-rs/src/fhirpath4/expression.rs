@@ -1,110 +1,231 @@
 use std::fmt::{Display, Formatter};
-use crate::prelude::{Integer, Result, FhirError};
+use std::ops::Range;
+use crate::prelude::{Integer, Decimal, Result, FhirError, Date, Time};
 use super::*;
 
 #[derive(Debug)]
 pub enum Expr {
+    /// 空集合字面量`{}`
+    EmptyExpr,
+    BooleanExpr(bool),
     IntegerExpr(Integer),
+    DecimalExpr(Decimal),
     StringExpr(String),
     DateTimeExpr(String),
-    PathExpr{symbol: String, index: Option<usize>},
-    CallExpr{symbol: String, args: Option<Vec<Expr>>},
-    BinOpExpr{lhs: Box<Expr>, rhs: Box<Expr>, op: Operator},
+    /// 时间字面量，如`@T14:34`，不含开头的`@T`
+    TimeExpr(String),
+    QuantityExpr{value: Decimal, unit: String},
+    VariableExpr(String),
+    InvocationExpr(String),
+    /// 路径，`span`为路径名在表达式中的位置
+    PathExpr{symbol: String, span: Range<usize>},
+    /// 函数调用，`span`为函数名在表达式中的位置
+    CallExpr{symbol: String, args: Option<Vec<Expr>>, span: Range<usize>},
+    /// 正负号，如`-5`、`-(a + b)`，`span`为符号在表达式中的位置
+    PolarityExpr{op: Operator, expr: Box<Expr>, span: Range<usize>},
+    /// 二元操作，`span`为操作符在表达式中的位置
+    BinOpExpr{lhs: Box<Expr>, rhs: Box<Expr>, op: Operator, span: Range<usize>},
+    /// 对子表达式的结果取索引，如`(a | b)[1]`，`span`为'['在表达式中的位置
+    IndexExpr{expr: Box<Expr>, index: Box<Expr>, span: Range<usize>},
 }
 
 impl Expr {
+    /// 解析表达式，出错时返回带有出错位置的[`PathError`]
     pub fn parse(input: String) -> Result<Self> {
         let mut tokenizer = Tokenizer::new(&input);
-        let tokens = tokenizer.tokenize()?;
-        Parser::new(tokens).parse_expr()
+        tokenizer.tokenize()
+            .and_then(|tokens| Parser::new(tokens).parse())
+            .map_err(|err| err.with_expression(&input))
     }
 
-    pub fn eval(&self, executor: &dyn Executor) -> Result<Collection> {
+    /// 表达式在原始文本中的位置，只有路径、函数调用、正负号、二元操作和索引记录了位置
+    pub fn span(&self) -> Option<Range<usize>> {
         match self {
+            Expr::PathExpr { span, .. } | Expr::CallExpr { span, .. } | Expr::PolarityExpr { span, .. }
+                | Expr::BinOpExpr { span, .. } | Expr::IndexExpr { span, .. } => Some(span.clone()),
+            _ => None,
+        }
+    }
+
+    /// 计算表达式，`%context`、`%resource`、`%rootResource`都是该元素
+    pub fn eval<'a>(&self, executor: &'a dyn Executor) -> Result<Collection<'a>> {
+        self.eval_with(executor, &EvalContext::new(executor))
+    }
+
+    /// 在指定的上下文中计算表达式，出错时记录出错的位置
+    pub fn eval_with<'a>(&self, executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        let result = self.eval_expr(executor, context);
+        match self.span() {
+            Some(span) => result.map_err(|err| err.at(span)),
+            None => result,
+        }
+    }
+
+    fn eval_expr<'a>(&self, executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        match self {
+            Expr::EmptyExpr => Ok(Collection::new()),
+            Expr::BooleanExpr(value) => {
+                Ok(Collection::new_boolean(*value))
+            },
             Expr::IntegerExpr(value) => {
                 Ok(Collection::new_integer(*value))
             },
+            Expr::DecimalExpr(value) => {
+                Ok(Collection::new_decimal(*value))
+            },
             Expr::StringExpr(value) => {
                 Ok(Collection::new_string(value.clone()))
             }
+            // 不含时间部分（没有`T`）的字面量是日期，如`@2015-02-04`
+            Expr::DateTimeExpr(value) if !value.contains('T') => {
+                Ok(Collection::new_any(Box::new(value.parse::<Date>()?)))
+            }
             Expr::DateTimeExpr(value) => {
                 Ok(Collection::new_datetime(value.parse()?))
             }
-            Expr::PathExpr { symbol, index } => {
+            Expr::TimeExpr(value) => {
+                Ok(Collection::new_any(Box::new(value.parse::<Time>()?)))
+            }
+            Expr::QuantityExpr { value, unit } => {
+                Ok(Collection::new_any(Box::new(quantity::new_quantity(*value, unit))))
+            }
+            Expr::VariableExpr(name) => context.variable(name),
+            Expr::InvocationExpr(name) => {
+                match name.as_str() {
+                    "this" => Ok(executor.to_collection(&None)),
+                    "index" => Ok(context.index()),
+                    "total" => Ok(context.total()),
+                    other => Err(FhirError::Message(format!("无效的调用[${}]", other))),
+                }
+            },
+            Expr::PathExpr { symbol, .. } => {
                 let l_collection = executor.to_collection(&None);
                 if executor.type_name() == symbol {
                     Ok(l_collection)
                 } else {
-                    l_collection.element(symbol, index)
+                    l_collection.element(symbol, &None)
                 }
             },
-            Expr::CallExpr { symbol, args: _ } => {
-                Err(FhirError::Message(format!("函数[{}]()为非主体表达式，只能出现在右侧。", symbol)))
+            // 没有输入的函数调用以当前元素（$this）作为输入，如`hasValue() or children().exists()`
+            Expr::CallExpr { symbol, args, .. } => {
+                executor.to_collection(&None).call(symbol, args, executor, context)
+            },
+            Expr::IndexExpr { expr, index, .. } => {
+                let collection = expr.eval_with(executor, context)?;
+                Self::index(collection, index, executor, context)
+            },
+            Expr::PolarityExpr { op, expr, .. } => {
+                expr.eval_with(executor, context)?.polarity(*op)
             },
-            Expr::BinOpExpr { lhs, rhs, op } => {
-                let l_collection = lhs.eval(executor)?;
+            Expr::BinOpExpr { lhs, rhs, op, .. } => {
+                let l_collection = lhs.eval_with(executor, context)?;
 
                 match op {
-                    Operator::Add => l_collection + rhs.eval(executor)?,
-                    Operator::Sub => l_collection - rhs.eval(executor)?,
-                    Operator::Mul => l_collection * rhs.eval(executor)?,
-                    Operator::Div => l_collection / rhs.eval(executor)?,
+                    Operator::Add => l_collection + rhs.eval_with(executor, context)?,
+                    Operator::Sub => l_collection - rhs.eval_with(executor, context)?,
+                    Operator::Mul => l_collection * rhs.eval_with(executor, context)?,
+                    Operator::Div => l_collection / rhs.eval_with(executor, context)?,
+                    Operator::IntDiv => l_collection.int_div(rhs.eval_with(executor, context)?),
+                    Operator::Mod => l_collection.modulo(rhs.eval_with(executor, context)?),
+                    Operator::Concat => l_collection.concat(rhs.eval_with(executor, context)?),
+                    Operator::Union => l_collection.union(rhs.eval_with(executor, context)?),
+                    Operator::In => l_collection.is_in(rhs.eval_with(executor, context)?),
+                    Operator::Contains => rhs.eval_with(executor, context)?.is_in(l_collection),
+                    Operator::Implies => {
+                        let r_collection = rhs.eval_with(executor, context)?;
+                        match l_collection.implies(r_collection)? {
+                            None => Ok(Collection::new()),
+                            Some(bl) => Ok(Collection::new_boolean(bl))
+                        }
+                    },
                     Operator::And => {
-                        let r_collection = rhs.eval(executor)?;
+                        let r_collection = rhs.eval_with(executor, context)?;
                         match (l_collection & r_collection)? {
                             None => Ok(Collection::new()),
                             Some(bl) => Ok(Collection::new_boolean(bl))
                         }
                     },
                     Operator::Or => {
-                        let r_collection = rhs.eval(executor)?;
+                        let r_collection = rhs.eval_with(executor, context)?;
                         match (l_collection | r_collection)? {
                             None => Ok(Collection::new()),
                             Some(bl) => Ok(Collection::new_boolean(bl))
                         }
                     },
                     Operator::Xor => {
-                        let r_collection = rhs.eval(executor)?;
+                        let r_collection = rhs.eval_with(executor, context)?;
                         match (l_collection ^ r_collection)? {
                             None => Ok(Collection::new()),
                             Some(bl) => Ok(Collection::new_boolean(bl))
                         }
                     },
-                    Operator::As => unimplemented!(),
-                    Operator::Is => unimplemented!(),
-                    Operator::Eq => l_collection.eq(rhs.eval(executor)?),
-                    Operator::Ne => l_collection.eq(rhs.eval(executor)?)?.not(),
-                    Operator::Gt => l_collection.gt(rhs.eval(executor)?),
-                    Operator::Ge => l_collection.ge(rhs.eval(executor)?),
-                    Operator::Lt => l_collection.lt(rhs.eval(executor)?),
-                    Operator::Le => l_collection.le(rhs.eval(executor)?),
-                    Operator::Dot => {
-                        match rhs.as_ref() {
-                            Expr::PathExpr {symbol, index} => {
-                                l_collection.element(symbol, index)
-                            },
-                            Expr::CallExpr {symbol, args} => {
-                                l_collection.call(symbol, args)
-                            },
-                            _ => Err(FhirError::Message(format!("点号[.]操作符不支持该右侧表达式。")))
-                        }
-                    },
+                    Operator::As => l_collection.as_type(&types::type_specifier(rhs)?),
+                    Operator::Is => l_collection.is(&types::type_specifier(rhs)?),
+                    Operator::Eq => l_collection.eq(rhs.eval_with(executor, context)?),
+                    Operator::Ne => l_collection.eq(rhs.eval_with(executor, context)?)?.not(),
+                    Operator::Gt => l_collection.gt(rhs.eval_with(executor, context)?),
+                    Operator::Ge => l_collection.ge(rhs.eval_with(executor, context)?),
+                    Operator::Lt => l_collection.lt(rhs.eval_with(executor, context)?),
+                    Operator::Le => l_collection.le(rhs.eval_with(executor, context)?),
+                    Operator::Dot => Self::member(l_collection, rhs, executor, context),
                     Operator::Unkown => unreachable!()
                 }
             }
         }
     }
+
+    /// 点号[.]右侧的表达式，以左侧的结果作为输入
+    fn member<'a>(input: Collection<'a>, rhs: &Expr, executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        match rhs {
+            Expr::PathExpr {symbol, span} => {
+                input.element(symbol, &None).map_err(|err| err.at(span.clone()))
+            },
+            Expr::CallExpr {symbol, args, span} => {
+                input.call(symbol, args, executor, context).map_err(|err| err.at(span.clone()))
+            },
+            Expr::IndexExpr {expr, index, span} => {
+                let collection = Self::member(input, expr, executor, context)?;
+                Self::index(collection, index, executor, context).map_err(|err| err.at(span.clone()))
+            },
+            _ => Err(FhirError::Message(format!("点号[.]操作符不支持该右侧表达式。")))
+        }
+    }
+
+    /// 取集合中指定位置的元素，索引为空或者超出范围时结果为空
+    fn index<'a>(collection: Collection<'a>, index: &Expr, executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        let index = match index.eval_with(executor, context)?.to_integer()? {
+            Some(index) if index >= 0 => index as usize,
+            Some(_) => return Err(FhirError::error("索引不能为负数")),
+            None => return Ok(Collection::new()),
+        };
+
+        let mut result = Collection::new();
+        if let Some(part) = collection.into_iter().nth(index) {
+            result.push(part)
+        }
+
+        Ok(result)
+    }
 }
 
 impl Display for Expr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            Expr::EmptyExpr => write!(f, "Empty"),
+            Expr::BooleanExpr(value) => write!(f, "Boolean({})", value),
             Expr::IntegerExpr(value) => write!(f, "Integer({})", value),
+            Expr::DecimalExpr(value) => write!(f, "Decimal({})", value),
             Expr::StringExpr(value) => write!(f, "Text({})", value),
             Expr::DateTimeExpr(value) => write!(f, "DateTime({})", value),
+            Expr::TimeExpr(value) => write!(f, "Time({})", value),
+            Expr::QuantityExpr { value, unit } => write!(f, "Quantity({} {})", value, unit),
+            Expr::VariableExpr(name) => write!(f, "Variable(%{})", name),
+            Expr::InvocationExpr(name) => write!(f, "Invocation(${})", name),
             Expr::PathExpr { symbol, .. } => write!(f, "Path({})", symbol),
             Expr::CallExpr { symbol, .. } => write!(f, "Function({})", symbol),
-            Expr::BinOpExpr { lhs: _, rhs: _, op } => write!(f, "Operator({})", op),
+            Expr::PolarityExpr { op, .. } => write!(f, "Polarity({})", op),
+            Expr::BinOpExpr { op, .. } => write!(f, "Operator({})", op),
+            Expr::IndexExpr { .. } => write!(f, "Index"),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,520 @@
+//! FHIRPath函数的注册表
+//!
+//! 每个函数都在[`FUNCTIONS`]中登记名称、参数个数范围以及对应的实现，
+//! `Collection::call`根据函数名查表并检查参数个数后再执行。
+use crate::prelude::{Result, FhirError, Integer, Date, DateTime, Time, info};
+use super::*;
+
+/// 函数的实现
+///
+/// 参数依次为：输入集合、函数参数表达式、用于计算非迭代参数的元素、计算上下文
+pub type FunctionImpl = for<'a> fn(Collection<'a>, &[Expr], &'a dyn Executor, &EvalContext<'a>) -> Result<Collection<'a>>;
+
+/// FHIRPath函数的定义
+pub struct Function {
+    pub name: &'static str,
+    pub min_args: usize,
+    pub max_args: usize,
+    pub exec: FunctionImpl,
+}
+
+impl Function {
+    /// 检查参数个数是否符合函数的定义
+    pub fn check_arity(&self, count: usize) -> Result<()> {
+        check_arity(self.name, self.min_args, self.max_args, count)
+    }
+}
+
+/// 检查参数个数是否在`min_args`和`max_args`之间，内置函数和用户定义的函数共用
+pub(crate) fn check_arity(name: &str, min_args: usize, max_args: usize, count: usize) -> Result<()> {
+    if count < min_args {
+        return Err(FhirError::Message(format!("{}()至少拥有{}个参数", name, min_args)))
+    }
+
+    if count > max_args {
+        return Err(FhirError::Message(format!("{}()至多只能拥有{}个参数", name, max_args)))
+    }
+
+    Ok(())
+}
+
+macro_rules! functions {
+    (
+        $(($name: literal, $min: literal, $max: literal, $exec: path),)+
+    ) => {
+        static FUNCTIONS: &[Function] = &[
+            $(Function { name: $name, min_args: $min, max_args: $max, exec: $exec },)+
+        ];
+    };
+}
+
+functions! {
+    ("empty", 0, 0, empty),
+    ("exists", 0, 1, exists),
+    ("all", 1, 1, all),
+    ("allTrue", 0, 0, all_true),
+    ("anyTrue", 0, 0, any_true),
+    ("allFalse", 0, 0, all_false),
+    ("anyFalse", 0, 0, any_false),
+    ("not", 0, 0, not),
+    ("subsetOf", 1, 1, subset_of),
+    ("supersetOf", 1, 1, superset_of),
+    ("count", 0, 0, count),
+    ("distinct", 0, 0, distinct),
+    ("isDistinct", 0, 0, is_distinct),
+    ("first", 0, 0, first),
+    ("last", 0, 0, last),
+    ("tail", 0, 0, tail),
+    ("skip", 1, 1, skip),
+    ("take", 1, 1, take),
+    ("intersect", 1, 1, intersect),
+    ("exclude", 1, 1, exclude),
+    ("union", 1, 1, union),
+    ("combine", 1, 1, combine),
+    ("where", 1, 1, where_),
+    ("select", 1, 1, select),
+    ("repeat", 1, 1, repeat),
+    ("ofType", 1, 1, of_type),
+    ("is", 1, 1, is),
+    ("as", 1, 1, as_),
+    ("aggregate", 1, 2, aggregate),
+    ("extension", 1, 1, extension),
+    ("hasValue", 0, 0, has_value),
+    ("getValue", 0, 0, get_value),
+    ("children", 0, 0, children),
+    ("descendants", 0, 0, descendants),
+    ("resolve", 0, 0, resolve),
+    ("memberOf", 1, 1, terminology::member_of),
+    ("subsumes", 1, 1, terminology::subsumes),
+    ("subsumedBy", 1, 1, terminology::subsumed_by),
+    ("startsWith", 1, 1, strings::starts_with),
+    ("endsWith", 1, 1, strings::ends_with),
+    ("contains", 1, 1, strings::contains),
+    ("indexOf", 1, 1, strings::index_of),
+    ("substring", 1, 2, strings::substring),
+    ("upper", 0, 0, strings::upper),
+    ("lower", 0, 0, strings::lower),
+    ("replace", 2, 2, strings::replace),
+    ("matches", 1, 1, strings::matches),
+    ("replaceMatches", 2, 2, strings::replace_matches),
+    ("length", 0, 0, strings::length),
+    ("toChars", 0, 0, strings::to_chars),
+    ("split", 1, 1, strings::split),
+    ("join", 0, 1, strings::join),
+    ("trim", 0, 0, strings::trim),
+    ("encode", 1, 1, strings::encode),
+    ("decode", 1, 1, strings::decode),
+    ("toBoolean", 0, 0, conversion::to_boolean),
+    ("convertsToBoolean", 0, 0, conversion::converts_to_boolean),
+    ("toInteger", 0, 0, conversion::to_integer),
+    ("convertsToInteger", 0, 0, conversion::converts_to_integer),
+    ("toDecimal", 0, 0, conversion::to_decimal),
+    ("convertsToDecimal", 0, 0, conversion::converts_to_decimal),
+    ("toString", 0, 0, conversion::to_string),
+    ("convertsToString", 0, 0, conversion::converts_to_string),
+    ("toDate", 0, 0, conversion::to_date),
+    ("convertsToDate", 0, 0, conversion::converts_to_date),
+    ("toDateTime", 0, 0, conversion::to_date_time),
+    ("convertsToDateTime", 0, 0, conversion::converts_to_date_time),
+    ("toTime", 0, 0, conversion::to_time),
+    ("convertsToTime", 0, 0, conversion::converts_to_time),
+    ("toQuantity", 0, 1, conversion::to_quantity),
+    ("convertsToQuantity", 0, 1, conversion::converts_to_quantity),
+    ("abs", 0, 0, math::abs),
+    ("ceiling", 0, 0, math::ceiling),
+    ("exp", 0, 0, math::exp),
+    ("floor", 0, 0, math::floor),
+    ("ln", 0, 0, math::ln),
+    ("log", 1, 1, math::log),
+    ("power", 1, 1, math::power),
+    ("round", 0, 1, math::round),
+    ("sqrt", 0, 0, math::sqrt),
+    ("truncate", 0, 0, math::truncate),
+    ("precision", 0, 0, boundary::precision),
+    ("lowBoundary", 0, 1, boundary::low_boundary),
+    ("highBoundary", 0, 1, boundary::high_boundary),
+    ("comparable", 1, 1, boundary::comparable),
+    ("iif", 2, 3, iif),
+    ("trace", 1, 2, trace),
+    ("now", 0, 0, now),
+    ("today", 0, 0, today),
+    ("timeOfDay", 0, 0, time_of_day),
+}
+
+/// 根据函数名查找函数定义
+pub fn lookup(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+fn empty<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(Collection::new_boolean(input.empty()))
+}
+
+fn exists<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    match args.first() {
+        None => Ok(Collection::new_boolean(input.exists())),
+        Some(criteria) => Ok(Collection::new_boolean(input.filter(criteria, context)?.exists())),
+    }
+}
+
+fn all<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    for (index, part) in input.iter().enumerate() {
+        let bl = args[0].eval_with(part, &context.iteration(index))?.to_boolean()?;
+        if bl != Some(true) {
+            return Ok(Collection::new_boolean(false))
+        }
+    }
+
+    Ok(Collection::new_boolean(true))
+}
+
+fn all_true<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    for part in input.iter() {
+        if !part.to_boolean()? {
+            return Ok(Collection::new_boolean(false))
+        }
+    }
+
+    Ok(Collection::new_boolean(true))
+}
+
+fn any_true<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    for part in input.iter() {
+        if part.to_boolean()? {
+            return Ok(Collection::new_boolean(true))
+        }
+    }
+
+    Ok(Collection::new_boolean(false))
+}
+
+fn all_false<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    for part in input.iter() {
+        if part.to_boolean()? {
+            return Ok(Collection::new_boolean(false))
+        }
+    }
+
+    Ok(Collection::new_boolean(true))
+}
+
+fn any_false<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    for part in input.iter() {
+        if !part.to_boolean()? {
+            return Ok(Collection::new_boolean(true))
+        }
+    }
+
+    Ok(Collection::new_boolean(false))
+}
+
+/// 布尔取反，输入为true时为false、为false时为true，输入为空时结果为空
+fn not<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    input.not()
+}
+
+fn subset_of<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let other = args[0].eval_with(executor, context)?;
+    let bl = input.iter().all(|part| other.contains_item(part));
+    Ok(Collection::new_boolean(bl))
+}
+
+fn superset_of<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let other = args[0].eval_with(executor, context)?;
+    let bl = other.iter().all(|part| input.contains_item(part));
+    Ok(Collection::new_boolean(bl))
+}
+
+fn count<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(Collection::new_integer(input.count() as Integer))
+}
+
+fn distinct<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(input.distinct())
+}
+
+fn is_distinct<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let count = input.count();
+    Ok(Collection::new_boolean(input.distinct().count() == count))
+}
+
+fn first<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(subset(input.into_iter().take(1)))
+}
+
+fn last<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let count = input.count();
+    Ok(subset(input.into_iter().skip(count.saturating_sub(1))))
+}
+
+fn tail<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(subset(input.into_iter().skip(1)))
+}
+
+/// 跳过前`num`个元素，`num`小于等于0时返回输入集合
+fn skip<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    match args[0].eval_with(executor, context)?.to_integer()? {
+        Some(num) if num > 0 => Ok(subset(input.into_iter().skip(num as usize))),
+        Some(_) => Ok(input),
+        None => Err(FhirError::error("skip()的参数不能为空")),
+    }
+}
+
+/// 取前`num`个元素，`num`小于等于0时结果为空
+fn take<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    match args[0].eval_with(executor, context)?.to_integer()? {
+        Some(num) if num > 0 => Ok(subset(input.into_iter().take(num as usize))),
+        Some(_) => Ok(Collection::new()),
+        None => Err(FhirError::error("take()的参数不能为空")),
+    }
+}
+
+/// 两个集合的交集，去除重复的元素并保留输入集合中的顺序
+fn intersect<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let other = args[0].eval_with(executor, context)?;
+    Ok(subset(input.distinct().into_iter().filter(|part| other.contains_item(part.as_ref()))))
+}
+
+/// 去除输入集合中出现在参数集合里的元素，不去除重复的元素
+fn exclude<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let other = args[0].eval_with(executor, context)?;
+    Ok(subset(input.into_iter().filter(|part| !other.contains_item(part.as_ref()))))
+}
+
+/// 与合并运算[|]相同，去除重复的元素
+fn union<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    input.union(args[0].eval_with(executor, context)?)
+}
+
+/// 合并两个集合，不去除重复的元素
+fn combine<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut collection = input;
+    collection.combine(args[0].eval_with(executor, context)?);
+    Ok(collection)
+}
+
+fn subset<'a>(parts: impl Iterator<Item = Item<'a>>) -> Collection<'a> {
+    let mut collection = Collection::new();
+    for part in parts {
+        collection.push(part)
+    }
+
+    collection
+}
+
+fn where_<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    input.filter(&args[0], context)
+}
+
+fn select<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut collection = Collection::new();
+    for (index, part) in input.items().enumerate() {
+        collection.combine(part.eval(&args[0], &context.iteration(index))?);
+    }
+
+    Ok(collection)
+}
+
+/// 反复对新得到的元素执行投影表达式，直到不再产生新的元素
+fn repeat<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut collection = Collection::new();
+    let mut next = repeat_step(input.items(), &args[0], &collection, context)?;
+
+    while next.exists() {
+        let start = collection.count();
+        collection.combine(next);
+        next = repeat_step(collection.items().skip(start), &args[0], &collection, context)?;
+    }
+
+    Ok(collection)
+}
+
+/// 对一组元素执行投影，只保留未出现过的元素
+fn repeat_step<'a: 'b, 'b>(parts: impl Iterator<Item = &'b Item<'a>>, projection: &Expr, seen: &Collection<'a>, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut next = Collection::new();
+    for part in parts {
+        for item in part.eval(projection, context)? {
+            if !seen.contains_item(item.as_ref()) && !next.contains_item(item.as_ref()) {
+                next.push(item);
+            }
+        }
+    }
+
+    Ok(next)
+}
+
+fn of_type<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let specifier = types::type_specifier(&args[0])?;
+    let mut collection = Collection::new();
+    for part in input {
+        if types::is_type(part.as_ref(), &specifier) {
+            collection.push(part)
+        }
+    }
+
+    Ok(collection)
+}
+
+fn is<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    input.is(&types::type_specifier(&args[0])?)
+}
+
+fn as_<'a>(input: Collection<'a>, args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    input.as_type(&types::type_specifier(&args[0])?)
+}
+
+/// 依次对每个元素执行聚合表达式，`$total`为上一次的结果，初始值为第二个参数
+fn aggregate<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut total = match args.get(1) {
+        Some(init) => init.eval_with(executor, context)?,
+        None => Collection::new(),
+    };
+
+    for (index, part) in input.items().enumerate() {
+        total = part.eval(&args[0], &context.aggregation(index, total))?;
+    }
+
+    Ok(total)
+}
+
+/// 筛选指定url的扩展，不支持扩展的元素（如System类型）被忽略
+fn extension<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let url = match args[0].eval_with(executor, context)?.to_strings()? {
+        Some(url) => url,
+        None => return Ok(Collection::new()),
+    };
+
+    let mut collection = Collection::new();
+    for part in input.items() {
+        let extensions = match part.element(&"extension".to_string(), &None) {
+            Ok(extensions) => extensions,
+            Err(_) => continue,
+        };
+
+        for extension in extensions {
+            if extension.element(&"url".to_string(), &None)?.to_strings()?.as_deref() == Some(url.as_str()) {
+                collection.push(extension);
+            }
+        }
+    }
+
+    Ok(collection)
+}
+
+/// 输入为单个拥有值的简单类型时为true，只有扩展而没有值时为false
+fn has_value<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(Collection::new_boolean(primitive_value(&input)?.exists()))
+}
+
+/// 取出简单类型的值，值为对应的System类型
+fn get_value<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    primitive_value(&input)
+}
+
+/// 输入不是单个简单类型时结果为空
+fn primitive_value<'a>(input: &Collection<'a>) -> Result<Collection<'a>> {
+    match input.first() {
+        Some(part) if input.count() == 1 && types::is_primitive(part.as_ref()) => {
+            part.element(&"value".to_string(), &None)
+        },
+        _ => Ok(Collection::new()),
+    }
+}
+
+fn children<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut collection = Collection::new();
+    for part in input.items() {
+        collection.combine(part.children());
+    }
+
+    Ok(collection)
+}
+
+/// 递归取出所有的子元素，相当于`repeat(children())`，但不去除重复的元素
+fn descendants<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut collection = Collection::new();
+    for part in input.items() {
+        collection.combine(part.children());
+    }
+
+    let mut start = 0;
+    while start < collection.count() {
+        let mut next = Collection::new();
+        for part in collection.items().skip(start) {
+            next.combine(part.children());
+        }
+
+        start = collection.count();
+        collection.combine(next);
+    }
+
+    Ok(collection)
+}
+
+/// 解析引用，输入可以是Reference或者引用地址的字符串，找不到被引用的资源时忽略该元素
+fn resolve<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut collection = Collection::new();
+    for part in input.items() {
+        let reference = match part.type_name() {
+            "Reference" => part.element(&"reference".to_string(), &None)?.to_strings()?,
+            _ if system_type(part.as_ref()) == SystemType::String => Some(part.to_strings()?),
+            _ => None,
+        };
+
+        if let Some(resource) = reference.map(|reference| context.resolve(&reference)).transpose()?.flatten() {
+            collection.push(resource);
+        }
+    }
+
+    Ok(collection)
+}
+
+/// 条件为true时计算第二个参数，否则计算第三个参数（未指定时结果为空），未选中的分支不会被计算
+fn iif<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    if input.count() > 1 {
+        return Err(FhirError::error("iif()的输入集合内元素数量大于1"))
+    }
+
+    let focus = input.first().cloned().unwrap_or(Item::Borrowed(executor));
+    match focus.eval(&args[0], context)?.to_boolean()? {
+        Some(true) => focus.eval(&args[1], context),
+        _ => match args.get(2) {
+            Some(otherwise) => focus.eval(otherwise, context),
+            None => Ok(Collection::new()),
+        },
+    }
+}
+
+/// 将输入集合（或对其执行投影的结果）输出到日志中，返回输入集合本身
+fn trace<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let name = args[0].eval_with(executor, context)?.to_strings()?.unwrap_or_default();
+    let traced = match args.get(1) {
+        Some(projection) => {
+            let mut collection = Collection::new();
+            for (index, part) in input.items().enumerate() {
+                collection.combine(part.eval(projection, &context.iteration(index))?);
+            }
+            collection
+        },
+        None => input.clone(),
+    };
+
+    let values = traced.iter()
+        .map(|part| part.to_strings().unwrap_or_else(|_| part.type_name().to_string()))
+        .collect::<Vec<String>>();
+    info!("trace[{}]: {:?}", name, values);
+
+    Ok(input)
+}
+
+fn now<'a>(_input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(Collection::new_any(Box::new(DateTime::from(chrono::Local::now()))))
+}
+
+fn today<'a>(_input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(Collection::new_any(Box::new(Date::from(chrono::Local::now()))))
+}
+
+fn time_of_day<'a>(_input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(Collection::new_any(Box::new(Time::from(chrono::Local::now()))))
+}
@@ -6,7 +6,7 @@ macro_rules! impl_executor_for_primitive {
     ) => {
         $(
             impl Executor for $ty {
-                fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection> {
+                fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection<'_>> {
                     match symbol.as_str() {
                         "id" => Ok(self.id.to_collection(index)),
                         "extension" => Ok(self.extension.to_collection(index)),
@@ -15,8 +15,30 @@ macro_rules! impl_executor_for_primitive {
                     }
                 }
 
-                fn to_collection(&self, _index: &Option<usize>) -> Collection {
-                    Collection::new_any(Box::new(self.clone()))
+                fn to_collection(&self, _index: &Option<usize>) -> Collection<'_> {
+                    Collection::new_ref(self)
+                }
+
+                /// 简单类型的值不是子元素，只有id和扩展
+                fn children(&self) -> Collection<'_> {
+                    let mut children = self.id.to_collection(&None);
+                    children.combine(self.extension.to_collection(&None));
+                    children
+                }
+            }
+
+            impl Reflect for $ty {
+                fn type_info() -> &'static TypeInfo {
+                    static TYPE_INFO: TypeInfo = TypeInfo {
+                        name: stringify!($ty),
+                        kind: TypeKind::Primitive,
+                        elements: &[
+                            ElementInfo { name: "id", min: 0, max: Some(1), choices: &[], type_info: <String as Reflect>::type_info },
+                            ElementInfo { name: "extension", min: 0, max: None, choices: &[], type_info: <Extension as Reflect>::type_info },
+                            ElementInfo { name: "value", min: 0, max: Some(1), choices: &[], type_info: <<$ty as Primitive>::T as Reflect>::type_info },
+                        ],
+                    };
+                    &TYPE_INFO
                 }
             }
 
@@ -49,6 +71,13 @@ macro_rules! impl_executor_for_primitive {
                     }
                 }
 
+                fn to_time(&self) -> Result<Time> {
+                    match &self.value {
+                        None => Err(FhirError::error("该数据类型不能转换为数值")),
+                        Some(value) => value.to_time(),
+                    }
+                }
+
                 fn to_boolean(&self) -> Result<Boolean> {
                     match &self.value {
                         None => Err(FhirError::error("该数据类型不能转换为数值")),
@@ -64,6 +93,43 @@ macro_rules! impl_executor_for_primitive {
                         None => Err(FhirError::error("该类型的value取值为空")),
                     }
                 }
+
+                fn compare(&self, right: &dyn Executor) -> Result<Option<std::cmp::Ordering>> {
+                    match &self.value {
+                        Some(value) => value.compare(right),
+                        None => Err(FhirError::error("该类型的value取值为空")),
+                    }
+                }
+            }
+
+            impl Patch for $ty {
+                fn fields_mut(&mut self) -> Vec<Box<dyn PatchField<'_> + '_>> {
+                    vec![
+                        Box::new(SingleField::new("id", &mut self.id)),
+                        Box::new(ListField::new("extension", &mut self.extension)),
+                        Box::new(SingleField::new("value", &mut self.value)),
+                    ]
+                }
+            }
+
+            impl PatchItem for $ty {
+                /// 类型不同的简单类型按取值转换，如用valueString替换code类型的元素
+                fn from_patch(value: &PatchValue) -> Result<Self> {
+                    match value {
+                        PatchValue::Value(any) if !any.as_any().is::<$ty>() => {
+                            Ok(<$ty as Primitive>::new(<<$ty as Primitive>::T as PatchItem>::from_patch(value)?))
+                        },
+                        _ => value.to_element(|| $ty { id: None, extension: None, value: None }),
+                    }
+                }
+
+                fn as_patch(&self) -> &dyn Patch {
+                    self
+                }
+
+                fn as_patch_mut(&mut self) -> &mut dyn Patch {
+                    self
+                }
             }
         )+
     };
@@ -75,21 +141,61 @@ macro_rules! impl_executor_for_anytype {
         $($id: ident,)+
     ) => {
         impl Executor for AnyType {
-            fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection> {
+            fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection<'_>> {
                 match self {
                     $(AnyType::$id(value) => value.element(symbol, index),)+
                 }
             }
 
-            fn to_collection(&self, index: &Option<usize>) -> Collection {
+            fn to_collection(&self, index: &Option<usize>) -> Collection<'_> {
                 match self {
                     $(AnyType::$id(value) => value.to_collection(index),)+
                 }
             }
+
+            fn children(&self) -> Collection<'_> {
+                match self {
+                    $(AnyType::$id(value) => value.children(),)+
+                }
+            }
         }
 
         impl Convert for AnyType {}
 
         impl Compare for AnyType {}
+
+        impl AnyType {
+            /// 实际取值的计算接口
+            pub fn as_executor(&self) -> &dyn Executor {
+                match self {
+                    $(AnyType::$id(value) => value,)+
+                }
+            }
+        }
+
+        impl PatchItem for AnyType {
+            fn from_patch(value: &PatchValue) -> Result<Self> {
+                match value {
+                    PatchValue::Value(value) => Ok(value.as_ref().clone()),
+                    _ => Err(FhirError::error("补丁中的值不是数据类型")),
+                }
+            }
+
+            fn as_patch(&self) -> &dyn Patch {
+                match self {
+                    $(AnyType::$id(value) => value,)+
+                }
+            }
+
+            fn as_patch_mut(&mut self) -> &mut dyn Patch {
+                match self {
+                    $(AnyType::$id(value) => value,)+
+                }
+            }
+
+            fn is_choice() -> bool {
+                true
+            }
+        }
     };
 }
@@ -0,0 +1,134 @@
+//! FHIRPath的数学函数
+//!
+//! 输入集合为空时结果为空，元素数量大于1时报错；运算结果无法表示时（如负数的平方根、整数溢出）结果为空。
+//! `abs()`对数量按数值运算并保留单位，其余函数只接受整数和小数。
+use crate::prelude::{Result, FhirError, Integer, Decimal};
+use super::*;
+use super::arithmetic::Value;
+use super::conversion::single;
+use super::types::{system_type, SystemType};
+
+/// 对输入集合中唯一的数值执行运算
+fn math<'a, F>(input: Collection<'a>, name: &str, operation: F) -> Result<Collection<'a>>
+where
+    F: FnOnce(&dyn Executor, SystemType) -> Result<Value>,
+{
+    let value = match single(&input)? {
+        Some(value) => value,
+        None => return Ok(Collection::new()),
+    };
+
+    match system_type(value) {
+        kind @ (SystemType::Integer | SystemType::Decimal) => Ok(operation(value, kind)?.map_or_else(Collection::new, Collection::new_any)),
+        SystemType::Quantity if name == "abs" => Ok(operation(value, SystemType::Quantity)?.map_or_else(Collection::new, Collection::new_any)),
+        other => Err(FhirError::Message(format!("{}()的输入必须是数值，实际为{:?}", name, other))),
+    }
+}
+
+fn integer(value: Integer) -> Value {
+    Some(Box::new(value))
+}
+
+/// 非有限的小数（NaN、无穷大）表示结果无法表示
+fn decimal(value: Decimal) -> Value {
+    match value.is_finite() {
+        true => Some(Box::new(value)),
+        false => None,
+    }
+}
+
+/// 小数取整后转换为整数，超出整数范围时结果为空
+fn to_integer(value: Decimal) -> Value {
+    match value.is_finite() && value >= Integer::MIN as Decimal && value <= Integer::MAX as Decimal {
+        true => integer(value as Integer),
+        false => None,
+    }
+}
+
+/// 计算非迭代的数值参数，参数为空时返回`None`
+fn number_arg(args: &[Expr], index: usize, executor: &dyn Executor, context: &EvalContext) -> Result<Option<Decimal>> {
+    match args.get(index) {
+        Some(arg) => arg.eval_with(executor, context)?.to_decimal(),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn abs<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    math(input, "abs", |value, kind| match kind {
+        SystemType::Integer => Ok(value.to_integer()?.checked_abs().and_then(integer)),
+        SystemType::Quantity => Ok(quantity::value_and_unit(value)?
+            .map(|(value, unit)| Box::new(quantity::new_quantity(value.abs(), &unit)) as Box<dyn Executor>)),
+        _ => Ok(decimal(value.to_decimal()?.abs())),
+    })
+}
+
+pub(crate) fn ceiling<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    math(input, "ceiling", |value, _| Ok(to_integer(value.to_decimal()?.ceil())))
+}
+
+pub(crate) fn floor<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    math(input, "floor", |value, _| Ok(to_integer(value.to_decimal()?.floor())))
+}
+
+pub(crate) fn truncate<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    math(input, "truncate", |value, _| Ok(to_integer(value.to_decimal()?.trunc())))
+}
+
+pub(crate) fn exp<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    math(input, "exp", |value, _| Ok(decimal(value.to_decimal()?.exp())))
+}
+
+pub(crate) fn ln<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    math(input, "ln", |value, _| Ok(decimal(value.to_decimal()?.ln())))
+}
+
+pub(crate) fn sqrt<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    math(input, "sqrt", |value, _| Ok(decimal(value.to_decimal()?.sqrt())))
+}
+
+/// 以参数为底的对数
+pub(crate) fn log<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let base = match number_arg(args, 0, executor, context)? {
+        Some(base) => base,
+        None => return Ok(Collection::new()),
+    };
+    math(input, "log", |value, _| Ok(decimal(value.to_decimal()?.log(base))))
+}
+
+/// 整数的非负整数次幂仍为整数，其余情况为小数
+pub(crate) fn power<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let exponent = match args[0].eval_with(executor, context)?.into_iter().next() {
+        Some(exponent) => exponent,
+        None => return Ok(Collection::new()),
+    };
+
+    math(input, "power", |value, kind| match (kind, system_type(exponent.as_ref())) {
+        (SystemType::Integer, SystemType::Integer) => {
+            match u32::try_from(exponent.to_integer()?) {
+                Ok(exponent) => Ok(value.to_integer()?.checked_pow(exponent).and_then(integer)),
+                Err(_) => Ok(decimal((value.to_integer()? as Decimal).powf(exponent.to_decimal()?))),
+            }
+        },
+        (_, SystemType::Integer | SystemType::Decimal) => Ok(decimal(value.to_decimal()?.powf(exponent.to_decimal()?))),
+        (_, other) => Err(FhirError::Message(format!("power()的参数必须是数值，实际为{:?}", other))),
+    })
+}
+
+/// 四舍五入到指定的小数位数，未指定时取整，结果为小数
+pub(crate) fn round<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let precision = match args.first() {
+        Some(arg) => match arg.eval_with(executor, context)?.to_integer()? {
+            Some(precision) if precision >= 0 => precision,
+            Some(_) => return Err(FhirError::error("round()的精度不能为负数")),
+            None => return Ok(Collection::new()),
+        },
+        None => 0,
+    };
+
+    let factor = 10f64.powi(precision.min(i32::MAX as Integer) as i32);
+    math(input, "round", |value, _| {
+        let value = value.to_decimal()?;
+        let rounded = (value * factor).round() / factor;
+        Ok(decimal(if rounded.is_finite() { rounded } else { value }))
+    })
+}
@@ -7,13 +7,42 @@ mod parser;
 mod expression;
 mod collection;
 mod macros;
+mod function;
+mod quantity;
+mod arithmetic;
+mod types;
+mod strings;
+mod context;
+mod conversion;
+mod compiled;
+mod diagnostic;
+mod reflect;
+mod analyzer;
+mod resolver;
+mod registry;
+mod terminology;
+mod patch;
+mod math;
+mod boundary;
 
 use std::ops::BitAnd;
 pub use tokenizer::{Token, TokenType, Tokenizer, Operator};
 pub use parser::Parser;
-pub use collection::Collection;
+pub use collection::{Collection, Item};
 pub use expression::{Expr};
-pub use executor::{Executor, Convert, Compare};
+pub use compiled::{CompiledPath, PathCache};
+pub use diagnostic::PathError;
+pub use reflect::{Reflect, TypeInfo, TypeKind, ElementInfo};
+pub use analyzer::{Analyzer, Analysis, PathType};
+pub use resolver::{ReferenceResolver, ContainedResolver, BundleResolver, MapResolver};
+pub use registry::{FunctionRegistry, UserFunction, UserFunctionImpl};
+pub use terminology::{TerminologyProvider, LocalTerminology};
+pub(crate) use terminology::item_member_of;
+pub(crate) use resolver::relative_reference;
+pub use patch::{Patch, PatchItem, PatchField, PatchValue, PatchOperation, PatchError, SingleField, ListField, apply_patch};
+pub use context::EvalContext;
+pub use executor::{Executor, BoxedExecutor, Convert, Compare};
+pub use types::{SystemType, system_type};
 
 #[test]
 pub fn test_parser() -> crate::prelude::Result<()> {
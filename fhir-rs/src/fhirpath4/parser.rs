@@ -1,7 +1,10 @@
 use std::cell::Cell;
+use std::ops::Range;
+use std::str::FromStr;
 
-use crate::prelude::{Result, FhirError};
+use crate::prelude::{Result, FhirError, Date, DateTime, Time};
 use super::*;
+use super::quantity::CalendarUnit;
 
 pub struct Parser {
     tokens: Vec<Token>,
@@ -22,45 +25,123 @@ impl Parser {
         self.parse_binary_expr(0, expr)
     }
 
+    /// 解析完整的表达式，表达式之后不能有多余的内容
+    pub fn parse(&self) -> Result<Expr> {
+        let expr = self.parse_expr()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(token) => Err(self.error_at(format!("表达式中有多余的内容[{:?}]", token.token_type))),
+        }
+    }
+
     fn parse_primary(&self) -> Result<Expr> {
         match self.peek() {
-            None => Err(FhirError::error("expect expr")),
+            None => Err(self.error_at("缺少表达式")),
             Some(token) => {
                 self.eat();
-                match &token.token_type {
-                    TokenType::Symbol(symbol) => self.parse_symbol_expr(symbol),
+                let span = token.span();
+                let expr = match &token.token_type {
                     TokenType::Text(value) => Ok(Expr::StringExpr(value.clone())),
-                    TokenType::DateTime(value) => Ok(Expr::DateTimeExpr(value.clone())),
-                    TokenType::Number(value) => Ok(Expr::IntegerExpr(value.parse()?)),
+                    TokenType::DateTime(value) => self.parse_datetime_expr(value),
+                    TokenType::Variable(name) => Ok(Expr::VariableExpr(name.clone())),
+                    TokenType::Invocation(name) => Ok(Expr::InvocationExpr(name.clone())),
+                    TokenType::Number(value) => self.parse_number_expr(value),
+                    TokenType::Symbol(symbol) if symbol == "true" => Ok(Expr::BooleanExpr(true)),
+                    TokenType::Symbol(symbol) if symbol == "false" => Ok(Expr::BooleanExpr(false)),
+                    TokenType::Symbol(symbol) => self.parse_symbol_expr(symbol, span.clone()),
                     TokenType::OpenParen => self.parse_paren_expr(),
-                    other => Err(FhirError::Message(format!("unknown token: {:#?}", other)))
-                }
+                    TokenType::OpenBrace => self.parse_empty_expr(),
+                    TokenType::Operator(op) if op.is_keyword() => self.parse_symbol_expr(&op.to_string(), span.clone()),
+                    TokenType::Operator(op @ (Operator::Add | Operator::Sub)) => return self.parse_polarity_expr(*op, span),
+                    _ => return Err(PathError::new("缺少表达式", span).into()),
+                };
+                self.parse_index_expr(expr.map_err(|err| err.at(span))?)
             }
         }
     }
 
+    /// 处理正负号，正负号只作用于紧随其后的项（包括其后的路径、函数调用和索引），
+    /// 优先级高于乘除，如`-5.abs()`相当于`-(5.abs())`，`-1 + 3`相当于`(-1) + 3`
+    fn parse_polarity_expr(&self, op: Operator, span: Range<usize>) -> Result<Expr> {
+        // 只继续处理优先级高于乘除（10）的操作符，即点号
+        let operand = self.parse_primary()?;
+        let operand = self.parse_binary_expr(10, operand)?;
+        Ok(Expr::PolarityExpr { op, expr: Box::new(operand), span })
+    }
+
+    /// 处理任意子表达式之后的索引，如`(a | b)[1]`、`name.where(use = 'official')[%i]`
+    fn parse_index_expr(&self, expr: Expr) -> Result<Expr> {
+        let mut expr = expr;
+        while let Some(token) = self.peek().filter(|token| token.token_type == TokenType::OpenBracket) {
+            let span = token.span();
+            self.eat();  // eat '['
+            let index = self.parse_expr()?;
+            match self.peek() {
+                Some(Token { token_type: TokenType::CloseBracket, .. }) => self.eat(),
+                _ => return Err(self.error_at("索引缺少']'")),
+            }
+            expr = Expr::IndexExpr { expr: Box::new(expr), index: Box::new(index), span };
+        }
+
+        Ok(expr)
+    }
+
+    /// 处理日期时间和时间字面量，如`@2015-02`、`@T14:34`，解析时即检查格式是否有效
+    fn parse_datetime_expr(&self, value: &str) -> Result<Expr> {
+        match value.strip_prefix('T') {
+            Some(time) => {
+                Time::from_str(time)?;
+                Ok(Expr::TimeExpr(time.to_string()))
+            },
+            None if !value.contains('T') => {
+                Date::from_str(value)?;
+                Ok(Expr::DateTimeExpr(value.to_string()))
+            },
+            None => {
+                DateTime::from_str(value)?;
+                Ok(Expr::DateTimeExpr(value.to_string()))
+            },
+        }
+    }
+
+    /// 处理数值，数值后面紧跟日历时间单位或者字符串形式的UCUM单位时为数量，如`18 years`、`5 'mg'`
+    fn parse_number_expr(&self, value: &str) -> Result<Expr> {
+        match self.peek() {
+            Some(Token { token_type: TokenType::Symbol(unit), .. }) if CalendarUnit::is_keyword(unit) => {
+                self.eat();  // eat unit
+                return Ok(Expr::QuantityExpr { value: value.parse()?, unit: unit.clone() })
+            },
+            Some(Token { token_type: TokenType::Text(unit), .. }) => {
+                self.eat();  // eat unit
+                return Ok(Expr::QuantityExpr { value: value.parse()?, unit: unit.clone() })
+            },
+            _ => {},
+        }
+
+        if value.contains('.') {
+            Ok(Expr::DecimalExpr(value.parse()?))
+        } else {
+            Ok(Expr::IntegerExpr(value.parse()?))
+        }
+    }
+
     /// 处理标识符
     /// 标识符可能有两种：
-    /// 1. 路径（路径可能含有索引）
+    /// 1. 路径（路径之后的索引由`parse_index_expr`处理）
     /// 2. 函数
-    fn parse_symbol_expr(&self, symbol: &String) -> Result<Expr> {
+    fn parse_symbol_expr(&self, symbol: &String, span: Range<usize>) -> Result<Expr> {
         match self.peek() {
             Some(token) => {
                 match &token.token_type {
                     TokenType::OpenParen => {
                         self.eat();
                         let args = self.parse_function_args()?;
-                        Ok(Expr::CallExpr {symbol: symbol.clone(), args})
+                        Ok(Expr::CallExpr {symbol: symbol.clone(), args, span})
                     },
-                    TokenType::OpenBracket => {
-                        self.eat();  // eat '['
-                        let index = self.parse_path_index()?;
-                        Ok(Expr::PathExpr { symbol: symbol.clone(), index: Some(index) })
-                    }
-                    _ => Ok(Expr::PathExpr {symbol: symbol.clone(), index: None}),
+                    _ => Ok(Expr::PathExpr {symbol: symbol.clone(), span: span.clone()}),
                 }
             }
-            None => Ok(Expr::PathExpr {symbol: symbol.clone(), index: None}),
+            None => Ok(Expr::PathExpr {symbol: symbol.clone(), span: span.clone()}),
         }
     }
 
@@ -73,6 +154,7 @@ impl Parser {
             match token {
                 Some(op_token) => {
                     let (curr_prec, op) = self.token_precedence(op_token);
+                    let span = op_token.span();
 
                     if curr_prec <= precedence {
                         return Ok(lhs)
@@ -90,7 +172,8 @@ impl Parser {
                     lhs = Expr::BinOpExpr {
                         lhs: Box::new(lhs),
                         rhs: Box::new(rhs),
-                        op: op,
+                        op,
+                        span,
                     }
                 },
                 None => return Ok(lhs)
@@ -101,7 +184,6 @@ impl Parser {
     /// 处理圆括号范围内的子表达式
     /// 不处理代表函数的圆括号
     fn parse_paren_expr(&self) -> Result<Expr> {
-        self.eat();  // eat '('
         let expr = self.parse_expr();
 
         match self.peek() {
@@ -111,46 +193,33 @@ impl Parser {
                         self.eat();  // eat ')'
                         expr
                     },
-                    _ => Err(FhirError::error("expected ')'")),
+                    _ => Err(self.error_at("缺少')'")),
                 }
             },
-            None => Err(FhirError::error("expected ')'")),
+            None => Err(self.error_at("缺少')'")),
         }
     }
 
-    /// 处理路径的索引
-    fn parse_path_index(&self) -> Result<usize> {
-        let index = match self.peek() {
-            Some(token) => {
-                match &token.token_type {
-                    TokenType::Number(value) => {
-                        self.eat();   // eat number
-                        value.parse()?
-                    },
-                    _ => return Err(FhirError::error("Expected ']' for index"))
-                }
-            },
-            None => return Err(FhirError::error("Expected ']' for index")),
-        };
-
+    /// 处理空集合`{}`，花括号之间不能有其他内容
+    fn parse_empty_expr(&self) -> Result<Expr> {
         match self.peek() {
-            Some(token) => {
-                match &token.token_type {
-                    TokenType::CloseBracket => {
-                        self.eat();  //eat ']'
-                    },
-                    _ => return Err(FhirError::error("Expected ']' for index"))
-                }
+            Some(Token { token_type: TokenType::CloseBrace, .. }) => {
+                self.eat();  // eat '}'
+                Ok(Expr::EmptyExpr)
             },
-            None => return Err(FhirError::error("Expected ']' for index"))
+            _ => Err(self.error_at("缺少'}'")),
         }
-        Ok(index)
     }
 
     /// 处理函数的参数
     fn parse_function_args(&self) -> Result<Option<Vec<Expr>>> {
         let mut args = vec![];
 
+        if let Some(Token { token_type: TokenType::CloseParen, .. }) = self.peek() {
+            self.eat();  // eat ')'
+            return Ok(None)
+        }
+
         loop {
             let arg = self.parse_expr()?;
             args.push(arg);
@@ -160,10 +229,10 @@ impl Parser {
                     match token.token_type {
                         TokenType::Colon => self.eat(),
                         TokenType::CloseParen => {self.eat(); break},
-                        _ => return Err(FhirError::error("Expected ')' or ',' in argument list"))
+                        _ => return Err(self.error_at("参数列表中缺少')'或','"))
                     }
                 },
-                None => return Err(FhirError::error("Expected ')' or ',' in argument list")),
+                None => return Err(self.error_at("参数列表中缺少')'或','")),
             }
         }
         Ok(if args.len() < 1 {None} else {Some(args)})
@@ -173,11 +242,14 @@ impl Parser {
         match &token.token_type {
             TokenType::Operator(op) => {
                 match op {
+                    Operator::Implies => (1, *op),
                     Operator::Or | Operator::Xor => (2, *op),
                     Operator::And => (3, *op),
+                    Operator::In | Operator::Contains => (4, *op),
+                    Operator::Union => (7, *op),
                     Operator::As | Operator::Is => (8, *op),
-                    Operator::Add | Operator::Sub => (9, *op),
-                    Operator::Mul | Operator::Div => (10, *op),
+                    Operator::Add | Operator::Sub | Operator::Concat => (9, *op),
+                    Operator::Mul | Operator::Div | Operator::IntDiv | Operator::Mod => (10, *op),
                     Operator::Dot => (13, *op),
                     _ => unreachable!()
                 }
@@ -203,6 +275,18 @@ impl Parser {
         }
     }
 
+    /// 在当前的词法单元处生成错误，已经没有词法单元时错误位置在表达式的末尾
+    fn error_at<M: Into<String>>(&self, message: M) -> FhirError {
+        let span = match self.peek() {
+            Some(token) => token.span(),
+            None => {
+                let end = self.tokens.last().map_or(0, |token| token.end);
+                end..end + 1
+            }
+        };
+        PathError::new(message, span).into()
+    }
+
     fn peek(&self) -> Option<&Token> {
         let current = self.current.get();
         self.tokens.get(current)
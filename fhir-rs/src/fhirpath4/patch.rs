@@ -0,0 +1,586 @@
+//! FHIRPath Patch
+//!
+//! 按照FHIR规范中的[FHIRPath Patch](https://hl7.org/fhir/fhirpatch.html)，将Parameters表示的
+//! `add`、`insert`、`delete`、`replace`、`move`操作应用到资源上。
+//! 操作的路径先在资源上计算，得到资源中被引用的元素，再按元素的地址在可修改的资源中找到对应的字段。
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::prelude::{Result, FhirError, Base, AnyType, AnyResource, Parameters, ParametersParameterBackboneElement,
+                     Date, DateTime, Time, Instant, Xhtml};
+use super::*;
+
+/// 补丁中的值：数据类型、资源，或者由多个部分组成的复杂类型（如BackboneElement）
+#[derive(Debug, Clone)]
+pub enum PatchValue {
+    Value(Box<AnyType>),
+    Resource(Box<AnyResource>),
+    Parts(Vec<(String, PatchValue)>),
+}
+
+impl PatchValue {
+    /// 从补丁参数的`value`部分读取值
+    pub fn from_part(part: &ParametersParameterBackboneElement) -> Result<Self> {
+        if let Some(value) = &part.value {
+            return Ok(PatchValue::Value(Box::new(value.clone())));
+        }
+        if let Some(resource) = &part.resource {
+            return Ok(PatchValue::Resource(Box::new(resource.clone())));
+        }
+        match &part.part {
+            Some(parts) => parts.iter()
+                .map(|part| Ok((part_name(part)?.to_string(), PatchValue::from_part(part)?)))
+                .collect::<Result<Vec<_>>>()
+                .map(PatchValue::Parts),
+            None => Err(FhirError::Message(format!("参数[{}]没有取值", part_name(part)?))),
+        }
+    }
+
+    /// 转换为具体类型的元素，`empty`用于创建由多个部分组成的元素
+    pub fn to_element<T: Patch + Clone + 'static>(&self, empty: impl FnOnce() -> T) -> Result<T> {
+        let value = match self {
+            PatchValue::Value(value) => value.as_any(),
+            PatchValue::Resource(resource) => resource.as_any(),
+            PatchValue::Parts(parts) => {
+                let mut element = empty();
+                let type_name = element.type_name().to_string();
+                for (name, part) in parts {
+                    let mut field = element.field_mut(name)
+                        .ok_or_else(|| FhirError::Message(format!("类型{}中没有元素[{}]", type_name, name)))?;
+                    let index = field.len();
+                    field.insert(index, part)?;
+                }
+                return Ok(element);
+            },
+        };
+
+        value.downcast_ref::<T>()
+            .cloned()
+            .ok_or_else(|| FhirError::Message(format!("补丁中的值{}不能作为{}类型的元素", self.type_name(), empty().type_name())))
+    }
+
+    fn type_name(&self) -> &str {
+        match self {
+            PatchValue::Value(value) => value.type_name(),
+            PatchValue::Resource(resource) => resource.type_name(),
+            PatchValue::Parts(_) => "Parts",
+        }
+    }
+}
+
+/// 可以被补丁修改的元素
+pub trait Patch: Base {
+    /// 所有字段
+    fn fields_mut(&mut self) -> Vec<Box<dyn PatchField<'_> + '_>>;
+
+    /// 按名称查找字段，可选类型的字段名可以带类型后缀，如`deceasedBoolean`
+    fn field_mut(&mut self, name: &str) -> Option<Box<dyn PatchField<'_> + '_>> {
+        self.fields_mut().into_iter().find(|field| field.accepts(name))
+    }
+}
+
+/// 可以作为字段取值的元素
+pub trait PatchItem: Clone + 'static {
+    /// 将补丁中的值转换为该类型
+    fn from_patch(value: &PatchValue) -> Result<Self>;
+
+    /// 用于定位和修改的元素，可选类型和资源为实际的取值，与FHIRPath计算结果中的元素一致
+    fn as_patch(&self) -> &dyn Patch;
+
+    fn as_patch_mut(&mut self) -> &mut dyn Patch;
+
+    /// 是否为可选类型（choice）
+    fn is_choice() -> bool {
+        false
+    }
+}
+
+/// 元素中的一个字段
+pub trait PatchField<'a> {
+    fn name(&self) -> &'static str;
+
+    /// 字段名是否匹配，可选类型的字段也匹配带类型后缀的名称
+    fn accepts(&self, name: &str) -> bool;
+
+    /// 字段中值的个数
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 是否可以有多个值
+    fn is_list(&self) -> bool;
+
+    fn item_mut(&mut self, index: usize) -> Option<&mut dyn Patch>;
+
+    fn into_item(self: Box<Self>, index: usize) -> Option<&'a mut dyn Patch>;
+
+    /// 在`index`处插入值，`index`等于值的个数时添加到最后
+    fn insert(&mut self, index: usize, value: &PatchValue) -> Result<()>;
+
+    fn replace(&mut self, index: usize, value: &PatchValue) -> Result<()>;
+
+    fn delete(&mut self, index: usize) -> Result<()>;
+
+    fn move_item(&mut self, source: usize, destination: usize) -> Result<()>;
+}
+
+/// 只能有一个值的字段
+pub struct SingleField<'a, T> {
+    name: &'static str,
+    value: &'a mut Option<T>,
+}
+
+impl<'a, T: PatchItem> SingleField<'a, T> {
+    pub fn new(name: &'static str, value: &'a mut Option<T>) -> Self {
+        SingleField { name, value }
+    }
+}
+
+impl<'a, T: PatchItem> PatchField<'a> for SingleField<'a, T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn accepts(&self, name: &str) -> bool {
+        accepts(self.name, T::is_choice(), name)
+    }
+
+    fn len(&self) -> usize {
+        usize::from(self.value.is_some())
+    }
+
+    fn is_list(&self) -> bool {
+        false
+    }
+
+    fn item_mut(&mut self, index: usize) -> Option<&mut dyn Patch> {
+        self.value.as_mut().filter(|_| index == 0).map(|value| value.as_patch_mut())
+    }
+
+    fn into_item(self: Box<Self>, index: usize) -> Option<&'a mut dyn Patch> {
+        let SingleField { value, .. } = *self;
+        value.as_mut().filter(|_| index == 0).map(|value| value.as_patch_mut())
+    }
+
+    fn insert(&mut self, index: usize, value: &PatchValue) -> Result<()> {
+        if self.value.is_some() {
+            return Err(FhirError::Message(format!("元素[{}]只能有一个值，且已经有值", self.name)));
+        }
+        check_index(self.name, index, 1)?;
+        *self.value = Some(T::from_patch(value)?);
+        Ok(())
+    }
+
+    fn replace(&mut self, index: usize, value: &PatchValue) -> Result<()> {
+        check_index(self.name, index, self.len())?;
+        *self.value = Some(T::from_patch(value)?);
+        Ok(())
+    }
+
+    fn delete(&mut self, index: usize) -> Result<()> {
+        check_index(self.name, index, self.len())?;
+        *self.value = None;
+        Ok(())
+    }
+
+    fn move_item(&mut self, _source: usize, _destination: usize) -> Result<()> {
+        Err(FhirError::Message(format!("元素[{}]不是列表，不能移动", self.name)))
+    }
+}
+
+/// 可以有多个值的字段，删除最后一个值后字段为空
+pub struct ListField<'a, T> {
+    name: &'static str,
+    value: &'a mut Option<Vec<T>>,
+}
+
+impl<'a, T: PatchItem> ListField<'a, T> {
+    pub fn new(name: &'static str, value: &'a mut Option<Vec<T>>) -> Self {
+        ListField { name, value }
+    }
+}
+
+impl<'a, T: PatchItem> PatchField<'a> for ListField<'a, T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn accepts(&self, name: &str) -> bool {
+        accepts(self.name, T::is_choice(), name)
+    }
+
+    fn len(&self) -> usize {
+        self.value.as_ref().map_or(0, Vec::len)
+    }
+
+    fn is_list(&self) -> bool {
+        true
+    }
+
+    fn item_mut(&mut self, index: usize) -> Option<&mut dyn Patch> {
+        self.value.as_mut().and_then(|list| list.get_mut(index)).map(|value| value.as_patch_mut())
+    }
+
+    fn into_item(self: Box<Self>, index: usize) -> Option<&'a mut dyn Patch> {
+        let ListField { value, .. } = *self;
+        value.as_mut().and_then(|list| list.get_mut(index)).map(|value| value.as_patch_mut())
+    }
+
+    fn insert(&mut self, index: usize, value: &PatchValue) -> Result<()> {
+        check_index(self.name, index, self.len() + 1)?;
+        let value = T::from_patch(value)?;
+        self.value.get_or_insert_with(Vec::new).insert(index, value);
+        Ok(())
+    }
+
+    fn replace(&mut self, index: usize, value: &PatchValue) -> Result<()> {
+        check_index(self.name, index, self.len())?;
+        let value = T::from_patch(value)?;
+        if let Some(list) = self.value.as_mut() {
+            list[index] = value;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, index: usize) -> Result<()> {
+        check_index(self.name, index, self.len())?;
+        if let Some(list) = self.value.as_mut() {
+            list.remove(index);
+            if list.is_empty() {
+                *self.value = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn move_item(&mut self, source: usize, destination: usize) -> Result<()> {
+        check_index(self.name, source, self.len())?;
+        check_index(self.name, destination, self.len())?;
+        if let Some(list) = self.value.as_mut() {
+            let value = list.remove(source);
+            list.insert(destination, value);
+        }
+        Ok(())
+    }
+}
+
+fn accepts(field: &str, is_choice: bool, name: &str) -> bool {
+    field == name || (is_choice && name.strip_prefix(field).is_some_and(|suffix| AnyType::CHOICES.contains(&suffix)))
+}
+
+fn check_index(name: &str, index: usize, len: usize) -> Result<()> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(FhirError::Message(format!("序号{}超出了元素[{}]的范围", index, name)))
+    }
+}
+
+impl<T: PatchItem> PatchItem for Box<T> {
+    fn from_patch(value: &PatchValue) -> Result<Self> {
+        T::from_patch(value).map(Box::new)
+    }
+
+    fn as_patch(&self) -> &dyn Patch {
+        self.as_ref().as_patch()
+    }
+
+    fn as_patch_mut(&mut self) -> &mut dyn Patch {
+        self.as_mut().as_patch_mut()
+    }
+}
+
+/// 简单类型的取值没有字段，从补丁中的值按字符串转换
+macro_rules! impl_patch_for_system {
+    (
+        $($ty:ident,)+
+    ) => {
+        $(
+            impl Patch for $ty {
+                fn fields_mut(&mut self) -> Vec<Box<dyn PatchField<'_> + '_>> {
+                    Vec::new()
+                }
+            }
+
+            impl PatchItem for $ty {
+                fn from_patch(value: &PatchValue) -> Result<Self> {
+                    let text = match value {
+                        PatchValue::Value(value) => value.as_executor().to_strings()?,
+                        other => return Err(FhirError::Message(format!("补丁中的值{}不能转换为{}", other.type_name(), stringify!($ty)))),
+                    };
+                    <$ty>::from_str(&text).map_err(|_| FhirError::Message(format!("[{}]不能转换为{}", text, stringify!($ty))))
+                }
+
+                fn as_patch(&self) -> &dyn Patch {
+                    self
+                }
+
+                fn as_patch_mut(&mut self) -> &mut dyn Patch {
+                    self
+                }
+            }
+        )+
+    };
+}
+
+impl_patch_for_system!(String, bool, usize, isize, i64, f64, Date, DateTime, Time, Instant, Xhtml,);
+
+/// 补丁中的一个操作
+#[derive(Debug, Clone)]
+pub enum PatchOperation {
+    /// 在`path`指向的元素中添加名为`name`的值
+    Add { path: String, name: String, value: PatchValue },
+    /// 在`path`指向的列表的`index`处插入值
+    Insert { path: String, index: usize, value: PatchValue },
+    /// 删除`path`指向的元素，没有匹配的元素时不做修改
+    Delete { path: String },
+    /// 替换`path`指向的元素
+    Replace { path: String, value: PatchValue },
+    /// 将`path`指向的列表中`source`处的值移动到`destination`处
+    Move { path: String, source: usize, destination: usize },
+}
+
+impl PatchOperation {
+    /// 从名为`operation`的参数读取操作
+    pub fn from_parameter(parameter: &ParametersParameterBackboneElement) -> Result<Self> {
+        let name = part_name(parameter)?;
+        if name != "operation" {
+            return Err(FhirError::Message(format!("补丁中的参数[{}]不是operation", name)));
+        }
+
+        let parts = parameter.part.as_deref().unwrap_or_default();
+        let part = |name: &str| {
+            parts.iter()
+                .find(|part| part_name(part).is_ok_and(|part_name| part_name == name))
+                .ok_or_else(|| FhirError::Message(format!("补丁操作中缺少[{}]", name)))
+        };
+        let text = |name: &str| -> Result<String> {
+            match &part(name)?.value {
+                Some(value) => value.as_executor().to_strings(),
+                None => Err(FhirError::Message(format!("补丁操作中的[{}]没有取值", name))),
+            }
+        };
+        let index = |name: &str| -> Result<usize> {
+            let value = match &part(name)?.value {
+                Some(value) => value.as_executor().to_integer()?,
+                None => return Err(FhirError::Message(format!("补丁操作中的[{}]没有取值", name))),
+            };
+            usize::try_from(value).map_err(|_| FhirError::Message(format!("补丁操作中的[{}]不能为负数", name)))
+        };
+        let value = || PatchValue::from_part(part("value")?);
+
+        match text("type")?.as_str() {
+            "add" => Ok(PatchOperation::Add { path: text("path")?, name: text("name")?, value: value()? }),
+            "insert" => Ok(PatchOperation::Insert { path: text("path")?, index: index("index")?, value: value()? }),
+            "delete" => Ok(PatchOperation::Delete { path: text("path")? }),
+            "replace" => Ok(PatchOperation::Replace { path: text("path")?, value: value()? }),
+            "move" => Ok(PatchOperation::Move { path: text("path")?, source: index("source")?, destination: index("destination")? }),
+            other => Err(FhirError::Message(format!("不支持的补丁操作[{}]", other))),
+        }
+    }
+
+    /// 操作的类型
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PatchOperation::Add { .. } => "add",
+            PatchOperation::Insert { .. } => "insert",
+            PatchOperation::Delete { .. } => "delete",
+            PatchOperation::Replace { .. } => "replace",
+            PatchOperation::Move { .. } => "move",
+        }
+    }
+
+    /// 在资源上执行操作，出错时资源可能已经被部分修改
+    pub fn apply<R: PatchItem + Executor>(&self, resource: &mut R) -> Result<()> {
+        match self {
+            PatchOperation::Add { path, name, value } => {
+                let trail = single(locate(resource, &Expr::parse(path.clone())?)?, path)?;
+                let node = node_at(resource.as_patch_mut(), &trail)?;
+                let type_name = node.type_name().to_string();
+                let mut field = node.field_mut(name)
+                    .ok_or_else(|| FhirError::Message(format!("类型{}中没有元素[{}]", type_name, name)))?;
+                let index = field.len();
+                field.insert(index, value)
+            },
+            PatchOperation::Insert { path, index, value } => {
+                list_field(resource, path)?.insert(*index, value)
+            },
+            PatchOperation::Delete { path } => {
+                let mut trails = locate(resource, &Expr::parse(path.clone())?)?;
+                match trails.pop() {
+                    None => Ok(()),
+                    Some(_) if !trails.is_empty() => Err(FhirError::Message(format!("路径[{}]匹配到多个元素", path))),
+                    Some(trail) => {
+                        let (mut field, index) = parent_field(resource, &trail)?;
+                        field.delete(index)
+                    },
+                }
+            },
+            PatchOperation::Replace { path, value } => {
+                let trail = single(locate(resource, &Expr::parse(path.clone())?)?, path)?;
+                let (mut field, index) = parent_field(resource, &trail)?;
+                field.replace(index, value)
+            },
+            PatchOperation::Move { path, source, destination } => {
+                list_field(resource, path)?.move_item(*source, *destination)
+            },
+        }
+    }
+}
+
+/// 执行失败的补丁操作
+#[derive(Debug, Clone)]
+pub struct PatchError {
+    /// 操作在补丁中的序号，从0开始
+    pub index: usize,
+    /// 操作的类型，无法读取操作时为`None`
+    pub operation: Option<&'static str>,
+    pub message: String,
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.operation {
+            Some(operation) => write!(f, "第{}个补丁操作[{}]失败：{}", self.index, operation, self.message),
+            None => write!(f, "第{}个补丁操作失败：{}", self.index, self.message),
+        }
+    }
+}
+
+/// 将FHIRPath Patch应用到资源上。操作按顺序执行，只有全部成功时才修改资源，否则返回每个失败的操作
+pub fn apply_patch<R: PatchItem + Executor>(resource: &mut R, patch: &Parameters) -> std::result::Result<(), Vec<PatchError>> {
+    let mut patched = resource.clone();
+    let mut errors = Vec::new();
+
+    for (index, parameter) in patch.parameter.iter().flatten().enumerate() {
+        let result = PatchOperation::from_parameter(parameter)
+            .map_err(|err| PatchError { index, operation: None, message: err.to_string() })
+            .and_then(|operation| {
+                operation.apply(&mut patched)
+                    .map_err(|err| PatchError { index, operation: Some(operation.kind()), message: err.to_string() })
+            });
+        if let Err(err) = result {
+            errors.push(err);
+        }
+    }
+
+    if errors.is_empty() {
+        *resource = patched;
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn part_name(part: &ParametersParameterBackboneElement) -> Result<&str> {
+    part.name.as_ref()
+        .and_then(|name| name.value.as_deref())
+        .ok_or_else(|| FhirError::error("补丁中的参数没有名称"))
+}
+
+/// 从资源到元素依次经过的字段名称和值的序号
+type Trail = Vec<(&'static str, usize)>;
+
+/// 元素的地址和类型，用于在可修改的资源中找到FHIRPath计算结果中的元素
+#[derive(PartialEq)]
+struct Address(*const (), String);
+
+impl Address {
+    fn of_executor(executor: &dyn Executor) -> Self {
+        Address(executor as *const dyn Executor as *const (), executor.type_name().to_string())
+    }
+
+    fn of_patch(patch: &dyn Patch) -> Self {
+        Address(patch as *const dyn Patch as *const (), patch.type_name().to_string())
+    }
+}
+
+/// 计算路径，找到结果中每个元素在资源中的位置
+fn locate<R: PatchItem + Executor>(resource: &mut R, expr: &Expr) -> Result<Vec<Trail>> {
+    let targets = expr.eval(&*resource)?
+        .into_iter()
+        .map(|item| match item {
+            Item::Borrowed(executor) => Ok(Address::of_executor(executor)),
+            Item::Owned(_) => Err(FhirError::Message(format!("路径[{}]的结果不是资源中的元素", expr))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let roots = [Address::of_executor(&*resource), Address::of_patch(resource.as_patch())];
+
+    targets.iter()
+        .map(|target| {
+            let mut trail = Trail::new();
+            if roots.contains(target) || search(resource.as_patch_mut(), target, &mut trail) {
+                Ok(trail)
+            } else {
+                Err(FhirError::Message(format!("路径[{}]的结果不是资源中的元素", expr)))
+            }
+        })
+        .collect()
+}
+
+fn search(node: &mut dyn Patch, target: &Address, trail: &mut Trail) -> bool {
+    for mut field in node.fields_mut() {
+        let name = field.name();
+        for index in 0..field.len() {
+            let Some(child) = field.item_mut(index) else { continue };
+            trail.push((name, index));
+            if Address::of_patch(&*child) == *target || search(child, target, trail) {
+                return true;
+            }
+            trail.pop();
+        }
+    }
+    false
+}
+
+fn single(mut trails: Vec<Trail>, path: &str) -> Result<Trail> {
+    match trails.len() {
+        1 => Ok(trails.remove(0)),
+        0 => Err(FhirError::Message(format!("路径[{}]没有匹配到元素", path))),
+        _ => Err(FhirError::Message(format!("路径[{}]匹配到多个元素", path))),
+    }
+}
+
+fn node_at<'a>(mut node: &'a mut dyn Patch, trail: &[(&'static str, usize)]) -> Result<&'a mut dyn Patch> {
+    for (name, index) in trail {
+        node = node.field_mut(name)
+            .and_then(|field| field.into_item(*index))
+            .ok_or_else(|| FhirError::Message(format!("资源中没有元素[{}]", name)))?;
+    }
+    Ok(node)
+}
+
+/// 元素所在的字段及其在字段中的序号
+fn parent_field<'a, R: PatchItem>(resource: &'a mut R, trail: &[(&'static str, usize)]) -> Result<(Box<dyn PatchField<'a> + 'a>, usize)> {
+    let Some(((name, index), parents)) = trail.split_last() else {
+        return Err(FhirError::error("补丁不能删除或替换资源本身"));
+    };
+    let field = node_at(resource.as_patch_mut(), parents)?
+        .field_mut(name)
+        .ok_or_else(|| FhirError::Message(format!("资源中没有元素[{}]", name)))?;
+    Ok((field, *index))
+}
+
+/// `insert`和`move`的路径指向列表本身，如`Patient.identifier`，列表为空时也能找到对应的字段
+fn list_field<'a, R: PatchItem + Executor>(resource: &'a mut R, path: &str) -> Result<Box<dyn PatchField<'a> + 'a>> {
+    let expr = Expr::parse(path.to_string())?;
+    let (container, name) = match &expr {
+        Expr::BinOpExpr { lhs, rhs, op: Operator::Dot, .. } => match rhs.as_ref() {
+            Expr::PathExpr { symbol, .. } => (Some(lhs.as_ref()), symbol),
+            _ => return Err(FhirError::Message(format!("路径[{}]不是列表元素", path))),
+        },
+        Expr::PathExpr { symbol, .. } => (None, symbol),
+        _ => return Err(FhirError::Message(format!("路径[{}]不是列表元素", path))),
+    };
+
+    let trail = match container {
+        Some(container) => single(locate(resource, container)?, path)?,
+        None => Trail::new(),
+    };
+    let node = node_at(resource.as_patch_mut(), &trail)?;
+    let type_name = node.type_name().to_string();
+    node.field_mut(name)
+        .filter(|field| field.is_list())
+        .ok_or_else(|| FhirError::Message(format!("类型{}中没有列表元素[{}]", type_name, name)))
+}
@@ -0,0 +1,271 @@
+//! FHIRPath中数量（Quantity）的运算
+//!
+//! Quantity及其派生类型（Age、Count、Distance、Duration）的数值和单位通过路径读取。
+//! 单位相同的两个数量可以直接比较；单位不同时，按内置的UCUM子集（质量、体积、长度、时间、浓度）
+//! 换算到基本单位后再比较，无法换算的单位之间不能比较。
+use std::cmp::Ordering;
+use crate::prelude::{Result, Decimal, Quantity, DecimalDt, StringDt, Primitive};
+use super::*;
+
+const QUANTITY_TYPES: [&str; 5] = ["Quantity", "Age", "Count", "Distance", "Duration"];
+
+/// 是否为数量类型
+pub(crate) fn is_quantity(executor: &dyn Executor) -> bool {
+    QUANTITY_TYPES.contains(&executor.type_name())
+}
+
+/// 日历时间单位，日期时间只能与这些单位的数量进行加减运算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CalendarUnit {
+    Year,
+    Month,
+    Week,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Millisecond,
+}
+
+impl CalendarUnit {
+    /// 识别日历时间关键字（如`years`）以及对应的UCUM单位（如`a`）
+    pub(crate) fn from_unit(unit: &str) -> Option<Self> {
+        match unit {
+            "year" | "years" | "a" => Some(CalendarUnit::Year),
+            "month" | "months" | "mo" => Some(CalendarUnit::Month),
+            "week" | "weeks" | "wk" => Some(CalendarUnit::Week),
+            "day" | "days" | "d" => Some(CalendarUnit::Day),
+            "hour" | "hours" | "h" => Some(CalendarUnit::Hour),
+            "minute" | "minutes" | "min" => Some(CalendarUnit::Minute),
+            "second" | "seconds" | "s" => Some(CalendarUnit::Second),
+            "millisecond" | "milliseconds" | "ms" => Some(CalendarUnit::Millisecond),
+            _ => None,
+        }
+    }
+
+    /// 是否为日历时间关键字，关键字可以直接跟在数值后面组成数量字面量
+    pub(crate) fn is_keyword(unit: &str) -> bool {
+        matches!(unit.trim_end_matches('s'), "year" | "month" | "week" | "day" | "hour" | "minute" | "second" | "millisecond")
+    }
+}
+
+/// 创建一个数量，用于数量字面量以及数量运算的结果
+pub(crate) fn new_quantity(value: Decimal, unit: &str) -> Quantity {
+    Quantity {
+        value: Some(DecimalDt::new(value)),
+        unit: Some(StringDt::new(unit)),
+        ..Default::default()
+    }
+}
+
+/// 读取数量的数值和单位，单位优先使用code，其次使用unit
+pub(crate) fn value_and_unit(executor: &dyn Executor) -> Result<Option<(Decimal, String)>> {
+    let value = executor.element(&"value".to_string(), &None)?.to_decimal()?;
+    let unit = match executor.element(&"code".to_string(), &None)?.to_strings()? {
+        Some(code) => Some(code),
+        None => executor.element(&"unit".to_string(), &None)?.to_strings()?,
+    };
+
+    Ok(value.map(|value| (value, unit.unwrap_or_default())))
+}
+
+/// 比较两个数量的大小，数值缺失或者单位无法换算时无法比较
+pub(crate) fn compare(lhs: &dyn Executor, rhs: &dyn Executor) -> Result<Option<Ordering>> {
+    match (value_and_unit(lhs)?, value_and_unit(rhs)?) {
+        (Some((l_value, l_unit)), Some((r_value, r_unit))) => {
+            match convert(r_value, &r_unit, &l_unit) {
+                Some(r_value) => Ok(compare_values(l_value, r_value)),
+                None => Ok(None),
+            }
+        },
+        _ => Ok(None),
+    }
+}
+
+/// 两个数量相加减，右侧数量换算为左侧的单位后运算，单位无法换算时结果为空
+pub(crate) fn add(lhs: &dyn Executor, rhs: &dyn Executor, sign: Decimal) -> Result<Option<Quantity>> {
+    match (value_and_unit(lhs)?, value_and_unit(rhs)?) {
+        (Some((l_value, l_unit)), Some((r_value, r_unit))) => {
+            Ok(convert(r_value, &r_unit, &l_unit).map(|r_value| new_quantity(l_value + sign * r_value, &l_unit)))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// 数量与数值相乘，数值可以是倒数，用于实现除法
+pub(crate) fn scale(quantity: &dyn Executor, factor: Decimal) -> Result<Option<Quantity>> {
+    Ok(value_and_unit(quantity)?.map(|(value, unit)| new_quantity(value * factor, &unit)))
+}
+
+/// 将数值从一个单位换算到另一个单位，单位相同时直接返回，量纲不同或者单位无法识别时返回`None`
+pub(crate) fn convert(value: Decimal, from: &str, to: &str) -> Option<Decimal> {
+    if from == to {
+        return Some(value)
+    }
+
+    let from = Unit::parse(from)?;
+    let to = Unit::parse(to)?;
+    match from.dimension == to.dimension {
+        true => Some(round_significant(value * from.factor / to.factor)),
+        false => None,
+    }
+}
+
+/// 保留15位有效数字，消除换算系数带来的浮点误差，如`2 'L'`换算为`2000 'mL'`而不是`2000.0000000000002 'mL'`
+fn round_significant(value: Decimal) -> Decimal {
+    format!("{:.14e}", value).parse().unwrap_or(value)
+}
+
+/// 换算后的数值在浮点误差范围内相等即视为相等，如`0.1 'g'`与`100 'mg'`
+fn compare_values(lhs: Decimal, rhs: Decimal) -> Option<Ordering> {
+    if (lhs - rhs).abs() <= 1e-12 * lhs.abs().max(rhs.abs()) {
+        return Some(Ordering::Equal)
+    }
+    lhs.partial_cmp(&rhs)
+}
+
+/// 量纲的下标：质量、长度、时间、物质的量、日历月
+const MASS: usize = 0;
+const LENGTH: usize = 1;
+const TIME: usize = 2;
+const AMOUNT: usize = 3;
+const CALENDAR_MONTH: usize = 4;
+
+/// 单位相对于基本单位（g、m、s、mol）的倍数及其量纲
+///
+/// 日历时间关键字`year`和`month`的长度不固定，只能互相换算，不能与UCUM的`a`、`mo`换算；
+/// 其它日历时间关键字与对应的UCUM单位相同，如`1 week = 1 'wk'`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Unit {
+    factor: Decimal,
+    dimension: [i32; 5],
+}
+
+impl Unit {
+    const ONE: Unit = Unit { factor: 1.0, dimension: [0; 5] };
+
+    fn base(factor: Decimal, index: usize, exponent: i32) -> Unit {
+        let mut dimension = [0; 5];
+        dimension[index] = exponent;
+        Unit { factor, dimension }
+    }
+
+    /// 解析单位，如`mg`、`mg/dL`、`mmol/L`、`m2`、`kg.m/s2`，只支持一个`/`
+    fn parse(unit: &str) -> Option<Unit> {
+        let (numerator, denominator) = match unit.split_once('/') {
+            Some((numerator, denominator)) => (numerator, Some(denominator)),
+            None => (unit, None),
+        };
+
+        let mut result = Unit::parse_product(numerator)?;
+        if let Some(denominator) = denominator {
+            result = result.mul(Unit::parse_product(denominator)?.pow(-1));
+        }
+        Some(result)
+    }
+
+    /// 解析以`.`相乘的若干项
+    fn parse_product(product: &str) -> Option<Unit> {
+        product.split('.').try_fold(Unit::ONE, |result, term| Some(result.mul(Unit::parse_term(term)?)))
+    }
+
+    /// 解析一项，项由带前缀的单位和可选的指数组成，如`cm3`、`s-1`
+    fn parse_term(term: &str) -> Option<Unit> {
+        let symbol = term.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '-');
+        let exponent = match &term[symbol.len()..] {
+            "" => 1,
+            exponent => exponent.parse().ok()?,
+        };
+
+        if symbol.is_empty() {
+            return match exponent {
+                1 => Some(Unit::ONE),
+                _ => None,
+            }
+        }
+
+        let unit = Unit::atom(symbol).or_else(|| {
+            let prefix = symbol.chars().next()?;
+            let atom = Unit::atom(&symbol[prefix.len_utf8()..]).filter(|_| Unit::is_metric(&symbol[prefix.len_utf8()..]))?;
+            Some(Unit { factor: Unit::prefix(prefix)? * atom.factor, dimension: atom.dimension })
+        })?;
+        Some(unit.pow(exponent))
+    }
+
+    /// 不带前缀的单位
+    fn atom(symbol: &str) -> Option<Unit> {
+        let unit = match symbol {
+            "1" => Unit::ONE,
+            "%" => Unit { factor: 0.01, dimension: [0; 5] },
+            "g" => Unit::base(1.0, MASS, 1),
+            "[lb_av]" => Unit::base(453.59237, MASS, 1),
+            "[oz_av]" => Unit::base(28.349523125, MASS, 1),
+            "m" => Unit::base(1.0, LENGTH, 1),
+            "[in_i]" => Unit::base(0.0254, LENGTH, 1),
+            "[ft_i]" => Unit::base(0.3048, LENGTH, 1),
+            "L" | "l" => Unit::base(0.001, LENGTH, 3),
+            "mol" => Unit::base(1.0, AMOUNT, 1),
+            "s" | "second" | "seconds" => Unit::base(1.0, TIME, 1),
+            "millisecond" | "milliseconds" => Unit::base(0.001, TIME, 1),
+            "min" | "minute" | "minutes" => Unit::base(60.0, TIME, 1),
+            "h" | "hour" | "hours" => Unit::base(3600.0, TIME, 1),
+            "d" | "day" | "days" => Unit::base(86400.0, TIME, 1),
+            "wk" | "week" | "weeks" => Unit::base(604800.0, TIME, 1),
+            "a" => Unit::base(31557600.0, TIME, 1),
+            "mo" => Unit::base(2629800.0, TIME, 1),
+            "year" | "years" => Unit::base(12.0, CALENDAR_MONTH, 1),
+            "month" | "months" => Unit::base(1.0, CALENDAR_MONTH, 1),
+            _ => return None,
+        };
+        Some(unit)
+    }
+
+    /// 可以带前缀的单位
+    fn is_metric(symbol: &str) -> bool {
+        matches!(symbol, "g" | "m" | "L" | "l" | "mol" | "s")
+    }
+
+    fn prefix(prefix: char) -> Option<Decimal> {
+        match prefix {
+            'k' => Some(1e3),
+            'h' => Some(1e2),
+            'd' => Some(1e-1),
+            'c' => Some(1e-2),
+            'm' => Some(1e-3),
+            'u' => Some(1e-6),
+            'n' => Some(1e-9),
+            'p' => Some(1e-12),
+            _ => None,
+        }
+    }
+
+    fn mul(self, other: Unit) -> Unit {
+        let mut dimension = self.dimension;
+        for (index, exponent) in other.dimension.iter().enumerate() {
+            dimension[index] += exponent;
+        }
+        Unit { factor: self.factor * other.factor, dimension }
+    }
+
+    fn pow(self, exponent: i32) -> Unit {
+        Unit {
+            factor: self.factor.powi(exponent),
+            dimension: self.dimension.map(|value| value * exponent),
+        }
+    }
+}
+
+#[test]
+pub fn test_ucum_convert() {
+    assert_eq!(convert(1.0, "kg", "g"), Some(1000.0));
+    assert_eq!(convert(72.0, "h", "days"), Some(3.0));
+    assert_eq!(convert(1.5, "g/L", "mg/dL"), Some(150.0));
+    assert_eq!(convert(2.0, "L", "mL"), Some(2000.0));
+    assert_eq!(convert(1.0, "m2", "cm2"), Some(10000.0));
+    assert_eq!(convert(1.0, "year", "months"), Some(12.0));
+    assert_eq!(convert(1.0, "year", "a"), None);
+    assert_eq!(convert(1.0, "mg", "mL"), None);
+    assert_eq!(convert(1.0, "mmol/L", "mg/dL"), None);
+    assert_eq!(convert(1.0, "[foo]", "[foo]"), Some(1.0));
+    assert_eq!(compare_values(0.1, 100.0 * 0.001), Some(Ordering::Equal));
+}
@@ -0,0 +1,195 @@
+//! FHIR类型的元数据
+//!
+//! 派生宏根据字段上的`#[fhir(name, min, max, choice)]`为每个类型生成[`TypeInfo`]，
+//! 静态分析表达式时据此检查路径是否有效，并推断结果的类型和基数。
+use std::fmt::{Debug, Formatter};
+use crate::prelude::{AnyType, AnyResource};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    /// System类型，如`System.String`
+    System,
+    /// FHIR简单类型，如`string`
+    Primitive,
+    /// FHIR复合类型，如`HumanName`
+    Complex,
+    /// 资源中的骨干元素
+    Backbone,
+    Resource,
+    /// 可选类型（choice）字段的类型，实际类型由字段的类型后缀决定
+    Choice,
+}
+
+/// 类型的元数据
+pub struct TypeInfo {
+    pub name: &'static str,
+    pub kind: TypeKind,
+    pub elements: &'static [ElementInfo],
+}
+
+/// 类型中元素的元数据
+pub struct ElementInfo {
+    pub name: &'static str,
+    pub min: usize,
+    /// 最大基数，`None`表示`*`
+    pub max: Option<usize>,
+    /// 可选类型字段允许的类型后缀，如`deceased`的`Boolean`、`DateTime`
+    pub choices: &'static [&'static str],
+    pub type_info: fn() -> &'static TypeInfo,
+}
+
+/// 提供类型的元数据
+pub trait Reflect {
+    fn type_info() -> &'static TypeInfo;
+}
+
+impl TypeInfo {
+    /// 按名称查找元素，可选类型字段可以带类型后缀，如`valueQuantity`；
+    /// 返回元素的定义及其可能的类型
+    pub fn element(&self, name: &str) -> Option<(&'static ElementInfo, Vec<&'static TypeInfo>)> {
+        for element in self.elements {
+            if element.name == name {
+                return Some((element, element.types()))
+            }
+
+            if let Some(suffix) = name.strip_prefix(element.name) {
+                if element.choices.contains(&suffix) {
+                    return Some((element, AnyType::choice_type_info(suffix).into_iter().collect()))
+                }
+            }
+        }
+        None
+    }
+
+    /// FHIRPath中的类型名，如`string`、`System.Integer`、`HumanName`
+    pub fn fhirpath_name(&self) -> String {
+        match self.kind {
+            TypeKind::System => format!("System.{}", self.name),
+            TypeKind::Primitive => {
+                let name = self.name.trim_end_matches("Dt");
+                let mut chars = name.chars();
+                match chars.next() {
+                    Some(first) => format!("{}{}", first.to_ascii_lowercase(), chars.as_str()),
+                    None => String::new(),
+                }
+            },
+            _ => self.name.to_string(),
+        }
+    }
+
+    /// 按FHIRPath的类型说明符查找类型，如`Patient`、`Quantity`、`FHIR.string`、`System.Integer`
+    pub fn lookup(specifier: &str) -> Option<&'static TypeInfo> {
+        let (namespace, name) = match specifier.split_once('.') {
+            Some((namespace, name)) => (Some(namespace), name),
+            None => (None, specifier),
+        };
+
+        match (namespace, name) {
+            (Some("System") | None, "Boolean") => Some(bool::type_info()),
+            (Some("System") | None, "String") => Some(String::type_info()),
+            (Some("System") | None, "Integer") => Some(isize::type_info()),
+            (Some("System") | None, "Decimal") => Some(f64::type_info()),
+            (Some("System") | None, "Date") => Some(crate::prelude::Date::type_info()),
+            (Some("System") | None, "DateTime") => Some(crate::prelude::DateTime::type_info()),
+            (Some("System") | None, "Time") => Some(crate::prelude::Time::type_info()),
+            (Some("System"), "Quantity") => AnyType::choice_type_info("Quantity"),
+            (Some("System"), _) => None,
+            (Some("FHIR") | None, name) if name.starts_with(|ch: char| ch.is_ascii_lowercase()) => {
+                let mut chars = name.chars();
+                let first = chars.next()?.to_ascii_uppercase();
+                AnyType::choice_type_info(&format!("{}{}", first, chars.as_str()))
+            },
+            (Some("FHIR") | None, name) => {
+                AnyType::choice_type_info(name).filter(|info| info.kind != TypeKind::Primitive)
+                    .or_else(|| AnyResource::type_info_of(name))
+                    .or_else(|| match name {
+                        "Extension" => Some(crate::prelude::Extension::type_info()),
+                        "Resource" | "DomainResource" => Some(AnyResource::type_info()),
+                        _ => None,
+                    })
+            },
+            _ => None,
+        }
+    }
+}
+
+impl ElementInfo {
+    /// 元素可能的类型，可选类型字段为所有允许的类型
+    pub fn types(&self) -> Vec<&'static TypeInfo> {
+        if self.choices.is_empty() {
+            vec![(self.type_info)()]
+        } else {
+            self.choices.iter().filter_map(|suffix| AnyType::choice_type_info(suffix)).collect()
+        }
+    }
+}
+
+impl Debug for TypeInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypeInfo")
+            .field("name", &self.name)
+            .field("kind", &self.kind)
+            .field("elements", &self.elements.iter().map(|element| element.name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Debug for ElementInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ElementInfo")
+            .field("name", &self.name)
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .field("choices", &self.choices)
+            .field("type", &(self.type_info)().name)
+            .finish()
+    }
+}
+
+macro_rules! impl_reflect_for_system {
+    (
+        $(($ty: ty, $name: literal),)+
+    ) => {
+        $(
+            impl Reflect for $ty {
+                fn type_info() -> &'static TypeInfo {
+                    static TYPE_INFO: TypeInfo = TypeInfo { name: $name, kind: TypeKind::System, elements: &[] };
+                    &TYPE_INFO
+                }
+            }
+        )+
+    };
+}
+
+impl_reflect_for_system! {
+    (bool, "Boolean"),
+    (String, "String"),
+    (isize, "Integer"),
+    (usize, "Integer"),
+    (i64, "Integer"),
+    (f64, "Decimal"),
+    (crate::prelude::Date, "Date"),
+    (crate::prelude::DateTime, "DateTime"),
+    (crate::prelude::Instant, "DateTime"),
+    (crate::prelude::Time, "Time"),
+    (crate::prelude::Xhtml, "String"),
+}
+
+#[test]
+pub fn test_type_lookup() {
+    use crate::prelude::{Patient, StringDt, Age};
+
+    let lookup = |specifier: &str| TypeInfo::lookup(specifier).map(|info| info.fhirpath_name());
+    assert_eq!(lookup("Patient"), Some("Patient".to_string()));
+    assert_eq!(lookup("FHIR.string"), Some("string".to_string()));
+    assert_eq!(lookup("String"), Some("System.String".to_string()));
+    assert_eq!(lookup("System.Quantity"), Some("Quantity".to_string()));
+    assert_eq!(lookup("Foo"), None);
+
+    let (element, types) = Patient::type_info().element("deceasedBoolean").unwrap();
+    assert_eq!((element.name, element.min, element.max), ("deceased", 0, Some(1)));
+    assert_eq!(types.len(), 1);
+    assert!(Patient::type_info().element("deceasedString").is_none());
+    assert_eq!(Age::type_info().element("value").unwrap().1[0].fhirpath_name(), "decimal");
+    assert_eq!(StringDt::type_info().element("value").unwrap().1[0].fhirpath_name(), "System.String");
+}
@@ -0,0 +1,83 @@
+//! 用户定义的FHIRPath函数
+//!
+//! 应用可以将本地的函数（如基于自有代码表的`memberOf()`、`ageInYears()`）注册到[`FunctionRegistry`]，
+//! 再通过[`EvalContext::with_functions`]交给计算上下文。与内置函数同名时，用户定义的函数优先。
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+use crate::prelude::Result;
+use super::*;
+
+/// 用户定义函数的实现，参数依次为输入集合、已经计算好的各个参数
+pub type UserFunctionImpl = dyn for<'a> Fn(Collection<'a>, &[Collection<'a>]) -> Result<Collection<'a>>;
+
+/// 用户定义的函数
+#[derive(Clone)]
+pub struct UserFunction {
+    pub name: String,
+    pub min_args: usize,
+    pub max_args: usize,
+    exec: Rc<UserFunctionImpl>,
+}
+
+impl UserFunction {
+    /// 检查参数个数是否符合函数的定义
+    pub fn check_arity(&self, count: usize) -> Result<()> {
+        function::check_arity(&self.name, self.min_args, self.max_args, count)
+    }
+
+    /// 以`executor`为当前元素计算各个参数，再调用函数
+    pub(crate) fn call<'a>(&self, input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+        self.check_arity(args.len())?;
+        let args = args.iter()
+            .map(|arg| arg.eval_with(executor, context))
+            .collect::<Result<Vec<_>>>()?;
+        (self.exec)(input, &args)
+    }
+}
+
+impl Debug for UserFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserFunction")
+            .field("name", &self.name)
+            .field("min_args", &self.min_args)
+            .field("max_args", &self.max_args)
+            .finish()
+    }
+}
+
+/// 用户定义函数的注册表
+#[derive(Debug, Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, UserFunction>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        FunctionRegistry::default()
+    }
+
+    /// 注册一个函数，`min_args`和`max_args`为参数个数的范围，同名的函数会被替换
+    pub fn register<N, F>(&mut self, name: N, min_args: usize, max_args: usize, exec: F)
+    where
+        N: Into<String>,
+        F: for<'a> Fn(Collection<'a>, &[Collection<'a>]) -> Result<Collection<'a>> + 'static,
+    {
+        let name = name.into();
+        let function = UserFunction { name: name.clone(), min_args, max_args, exec: Rc::new(exec) };
+        self.functions.insert(name, function);
+    }
+
+    /// 根据函数名查找函数
+    pub fn get(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
@@ -0,0 +1,149 @@
+//! FHIRPath中`resolve()`函数使用的引用解析器
+//!
+//! 计算上下文中默认带有[`ContainedResolver`]和[`BundleResolver`]，分别从`%resource`的内嵌资源
+//! 和`%rootResource`（Bundle）中查找被引用的资源；其它来源的资源可以通过
+//! [`EvalContext::with_resolver`]添加解析器，如[`MapResolver`]。
+use std::collections::HashMap;
+use crate::prelude::{Result, FhirError};
+use super::*;
+
+/// 引用解析器，根据引用查找被引用的资源
+pub trait ReferenceResolver {
+    /// 解析引用，引用可能是内嵌资源（`#p1`）、相对地址（`Patient/123`）或者绝对地址（`urn:uuid:...`），
+    /// 找不到被引用的资源时返回`None`
+    fn resolve<'a>(&'a self, reference: &str, context: &EvalContext<'a>) -> Result<Option<Item<'a>>>;
+}
+
+/// 从`%resource`的内嵌资源（contained）中查找，`#`表示`%resource`本身
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContainedResolver;
+
+impl ReferenceResolver for ContainedResolver {
+    fn resolve<'a>(&'a self, reference: &str, context: &EvalContext<'a>) -> Result<Option<Item<'a>>> {
+        let (resource, id) = match (context.resource(), reference.strip_prefix('#')) {
+            (Some(resource), Some(id)) => (resource, id),
+            _ => return Ok(None),
+        };
+
+        if id.is_empty() {
+            return Ok(Some(Item::Borrowed(resource)))
+        }
+
+        for contained in resource.element(&"contained".to_string(), &None)?.into_iter() {
+            if resource_id(contained.as_ref())?.as_deref() == Some(id) {
+                return Ok(Some(contained))
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// 从`%rootResource`（Bundle）的条目中查找，先按`fullUrl`匹配，再按资源的类型和id匹配相对地址
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BundleResolver;
+
+impl ReferenceResolver for BundleResolver {
+    fn resolve<'a>(&'a self, reference: &str, context: &EvalContext<'a>) -> Result<Option<Item<'a>>> {
+        let bundle = match context.root_resource() {
+            Some(bundle) if bundle.type_name() == "Bundle" => bundle,
+            _ => return Ok(None),
+        };
+
+        let relative = relative_reference(reference);
+        for entry in bundle.element(&"entry".to_string(), &None)?.into_iter() {
+            let full_url = entry.element(&"fullUrl".to_string(), &None)?.to_strings()?;
+            let resource = entry.element(&"resource".to_string(), &None)?.into_iter().next();
+
+            if let Some(resource) = resource {
+                if full_url.as_deref() == Some(reference) {
+                    return Ok(Some(resource))
+                }
+
+                if let Some((type_name, id)) = relative {
+                    let matched = match &full_url {
+                        Some(full_url) => relative_reference(full_url) == Some((type_name, id)),
+                        None => false,
+                    };
+                    if matched || resource.type_name() == type_name && resource_id(resource.as_ref())?.as_deref() == Some(id) {
+                        return Ok(Some(resource))
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// 内存中的资源表，可以按完整的引用地址或者`类型/id`查找
+#[derive(Debug, Default)]
+pub struct MapResolver {
+    resources: HashMap<String, Box<dyn Executor>>,
+}
+
+impl MapResolver {
+    pub fn new() -> Self {
+        MapResolver::default()
+    }
+
+    /// 以指定的引用地址添加资源
+    pub fn insert<K: Into<String>, R: Executor + 'static>(&mut self, reference: K, resource: R) {
+        self.resources.insert(reference.into(), Box::new(resource));
+    }
+
+    /// 以`类型/id`作为引用地址添加资源，资源必须有id
+    pub fn add<R: Executor + 'static>(&mut self, resource: R) -> Result<()> {
+        let id = resource_id(&resource)?
+            .ok_or_else(|| FhirError::Message(format!("没有id的{}资源不能被引用", resource.type_name())))?;
+        let reference = format!("{}/{}", resource.type_name(), id);
+        self.insert(reference, resource);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+}
+
+impl ReferenceResolver for MapResolver {
+    fn resolve<'a>(&'a self, reference: &str, _context: &EvalContext<'a>) -> Result<Option<Item<'a>>> {
+        let resource = self.resources.get(reference).or_else(|| {
+            let (type_name, id) = relative_reference(reference)?;
+            self.resources.get(&format!("{}/{}", type_name, id))
+        });
+        Ok(resource.map(|resource| Item::Borrowed(resource.as_ref())))
+    }
+}
+
+/// 引用地址中的资源类型和id，如`http://example.org/fhir/Patient/123/_history/2`中的`Patient`和`123`
+pub(crate) fn relative_reference(reference: &str) -> Option<(&str, &str)> {
+    let reference = match reference.find("/_history/") {
+        Some(pos) => &reference[..pos],
+        None => reference,
+    };
+
+    let mut segments = reference.rsplit('/');
+    let id = segments.next()?;
+    let type_name = segments.next()?;
+    let is_type = type_name.starts_with(|ch: char| ch.is_ascii_uppercase())
+        && type_name.chars().all(|ch| ch.is_ascii_alphanumeric());
+    match is_type && !id.is_empty() {
+        true => Some((type_name, id)),
+        false => None,
+    }
+}
+
+fn resource_id(resource: &dyn Executor) -> Result<Option<String>> {
+    resource.element(&"id".to_string(), &None)?.to_strings()
+}
+
+#[test]
+pub fn test_relative_reference() {
+    assert_eq!(relative_reference("Patient/123"), Some(("Patient", "123")));
+    assert_eq!(relative_reference("http://example.org/fhir/Patient/123/_history/2"), Some(("Patient", "123")));
+    assert_eq!(relative_reference("urn:uuid:04121321-4af5-424c-a0e1-ed3aab1c349d"), None);
+    assert_eq!(relative_reference("#p1"), None);
+}
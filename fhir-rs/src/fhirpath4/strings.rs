@@ -0,0 +1,217 @@
+//! FHIRPath的字符串函数
+//!
+//! 输入集合必须只包含一个元素，该元素通过`Convert::to_strings`转换为字符串后再处理；
+//! 输入集合为空或者任何一个参数计算结果为空时，函数的结果为空。
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+use regex::Regex;
+use crate::prelude::{Result, FhirError, Integer};
+use super::*;
+
+/// 计算字符串类型的参数，参数在函数调用的上下文中计算
+fn string_arg(arg: &Expr, executor: &dyn Executor, context: &EvalContext) -> Result<Option<String>> {
+    arg.eval_with(executor, context)?.to_strings()
+}
+
+/// 计算整数类型的参数
+fn integer_arg(arg: &Expr, executor: &dyn Executor, context: &EvalContext) -> Result<Option<Integer>> {
+    arg.eval_with(executor, context)?.to_integer()
+}
+
+/// 对输入字符串和一个字符串参数执行运算
+fn with_string_arg<T, F>(input: &Collection, arg: &Expr, executor: &dyn Executor, context: &EvalContext, op: F) -> Result<Option<T>>
+where F: FnOnce(String, String) -> Result<T> {
+    match (input.to_strings()?, string_arg(arg, executor, context)?) {
+        (Some(value), Some(arg)) => Ok(Some(op(value, arg)?)),
+        _ => Ok(None),
+    }
+}
+
+fn boolean_or_empty<'a>(value: Option<bool>) -> Collection<'a> {
+    value.map_or_else(Collection::new, Collection::new_boolean)
+}
+
+fn string_or_empty<'a>(value: Option<String>) -> Collection<'a> {
+    value.map_or_else(Collection::new, Collection::new_string)
+}
+
+pub(crate) fn starts_with<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let bl = with_string_arg(&input, &args[0], executor, context, |value, prefix| Ok(value.starts_with(&prefix)))?;
+    Ok(boolean_or_empty(bl))
+}
+
+pub(crate) fn ends_with<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let bl = with_string_arg(&input, &args[0], executor, context, |value, suffix| Ok(value.ends_with(&suffix)))?;
+    Ok(boolean_or_empty(bl))
+}
+
+pub(crate) fn contains<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let bl = with_string_arg(&input, &args[0], executor, context, |value, substring| Ok(value.contains(&substring)))?;
+    Ok(boolean_or_empty(bl))
+}
+
+/// 子字符串首次出现的位置（按字符计算），不存在时为-1
+pub(crate) fn index_of<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let index = with_string_arg(&input, &args[0], executor, context, |value, substring| {
+        Ok(match value.find(&substring) {
+            Some(offset) => value[..offset].chars().count() as Integer,
+            None => -1,
+        })
+    })?;
+
+    Ok(index.map_or_else(Collection::new, Collection::new_integer))
+}
+
+/// 从指定位置开始截取字符串，位置超出范围时结果为空；未指定长度时截取到末尾
+pub(crate) fn substring<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let (value, start) = match (input.to_strings()?, integer_arg(&args[0], executor, context)?) {
+        (Some(value), Some(start)) => (value, start),
+        _ => return Ok(Collection::new()),
+    };
+
+    let chars: Vec<char> = value.chars().collect();
+    if start < 0 || start as usize >= chars.len() {
+        return Ok(Collection::new())
+    }
+
+    let length = match args.get(1) {
+        Some(arg) => match integer_arg(arg, executor, context)? {
+            Some(length) => length.max(0) as usize,
+            None => chars.len(),
+        },
+        None => chars.len(),
+    };
+
+    let end = chars.len().min(start as usize + length);
+    Ok(Collection::new_string(chars[start as usize..end].iter().collect()))
+}
+
+pub(crate) fn upper<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(string_or_empty(input.to_strings()?.map(|value| value.to_uppercase())))
+}
+
+pub(crate) fn lower<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(string_or_empty(input.to_strings()?.map(|value| value.to_lowercase())))
+}
+
+/// 替换所有出现的子字符串，子字符串为空时在每个字符的前后插入替换内容
+pub(crate) fn replace<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    match (input.to_strings()?, string_arg(&args[0], executor, context)?, string_arg(&args[1], executor, context)?) {
+        (Some(value), Some(pattern), Some(substitution)) => {
+            Ok(Collection::new_string(value.replace(&pattern, &substitution)))
+        },
+        _ => Ok(Collection::new()),
+    }
+}
+
+/// 字符串中是否存在匹配正则表达式的内容
+pub(crate) fn matches<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let bl = with_string_arg(&input, &args[0], executor, context, |value, regex| {
+        Ok(Regex::new(&regex)?.is_match(&value))
+    })?;
+
+    Ok(boolean_or_empty(bl))
+}
+
+/// 替换所有匹配正则表达式的内容，替换内容中可以使用`$1`引用分组
+pub(crate) fn replace_matches<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    match (input.to_strings()?, string_arg(&args[0], executor, context)?, string_arg(&args[1], executor, context)?) {
+        (Some(value), Some(regex), Some(substitution)) => {
+            let regex = Regex::new(&regex)?;
+            Ok(Collection::new_string(regex.replace_all(&value, substitution.as_str()).into_owned()))
+        },
+        _ => Ok(Collection::new()),
+    }
+}
+
+/// 字符串的长度（按字符计算）
+pub(crate) fn length<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let length = input.to_strings()?.map(|value| value.chars().count() as Integer);
+    Ok(length.map_or_else(Collection::new, Collection::new_integer))
+}
+
+pub(crate) fn to_chars<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let mut collection = Collection::new();
+    if let Some(value) = input.to_strings()? {
+        for ch in value.chars() {
+            collection.push(Box::new(ch.to_string()));
+        }
+    }
+
+    Ok(collection)
+}
+
+pub(crate) fn split<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let parts = with_string_arg(&input, &args[0], executor, context, |value, separator| {
+        Ok(value.split(&separator).map(String::from).collect::<Vec<String>>())
+    })?;
+
+    let mut collection = Collection::new();
+    for part in parts.unwrap_or_default() {
+        collection.push(Box::new(part));
+    }
+
+    Ok(collection)
+}
+
+/// 将输入集合中的所有字符串连接为一个字符串，未指定分隔符时直接连接
+pub(crate) fn join<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    if input.empty() {
+        return Ok(Collection::new())
+    }
+
+    let separator = match args.first() {
+        Some(arg) => string_arg(arg, executor, context)?.unwrap_or_default(),
+        None => String::new(),
+    };
+
+    let parts = input.iter()
+        .map(|part| part.to_strings())
+        .collect::<Result<Vec<String>>>()?;
+    Ok(Collection::new_string(parts.join(&separator)))
+}
+
+pub(crate) fn trim<'a>(input: Collection<'a>, _args: &[Expr], _executor: &'a dyn Executor, _context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    Ok(string_or_empty(input.to_strings()?.map(|value| value.trim().to_string())))
+}
+
+/// 按指定的格式编码字符串，支持`base64`、`urlbase64`、`hex`
+pub(crate) fn encode<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let encoded = with_string_arg(&input, &args[0], executor, context, |value, format| {
+        match format.as_str() {
+            "base64" => Ok(STANDARD.encode(value)),
+            "urlbase64" => Ok(URL_SAFE.encode(value)),
+            "hex" => Ok(value.bytes().map(|byte| format!("{:02x}", byte)).collect()),
+            other => Err(FhirError::Message(format!("不支持的编码格式[{}]", other))),
+        }
+    })?;
+
+    Ok(string_or_empty(encoded))
+}
+
+/// 按指定的格式解码字符串，支持`base64`、`urlbase64`、`hex`
+pub(crate) fn decode<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let decoded = with_string_arg(&input, &args[0], executor, context, |value, format| {
+        let bytes = match format.as_str() {
+            "base64" => STANDARD.decode(value)?,
+            "urlbase64" => URL_SAFE.decode(value)?,
+            "hex" => decode_hex(&value)?,
+            other => return Err(FhirError::Message(format!("不支持的解码格式[{}]", other))),
+        };
+
+        Ok(String::from_utf8(bytes)?)
+    })?;
+
+    Ok(string_or_empty(decoded))
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    if !value.len().is_multiple_of(2) || !value.is_ascii() {
+        return Err(FhirError::Message(format!("[{}]不是有效的十六进制字符串", value)))
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&value[idx..idx + 2], 16).map_err(FhirError::from))
+        .collect()
+}
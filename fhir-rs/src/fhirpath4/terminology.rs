@@ -0,0 +1,300 @@
+//! FHIRPath中的术语函数`memberOf()`、`subsumes()`、`subsumedBy()`
+//!
+//! 术语函数通过计算上下文中的[`TerminologyProvider`]回答值集成员和编码包含关系，
+//! [`LocalTerminology`]根据加载的ValueSet和CodeSystem在本地完成判断，不需要术语服务器。
+use std::collections::HashMap;
+use regex::Regex;
+use crate::prelude::{Result, FhirError, ValueSet, CodeSystem, CodeSystemConceptBackboneElement,
+                     ValueSetComposeIncludeBackboneElement, ValueSetComposeIncludeFilterBackboneElement,
+                     ValueSetExpansionContainsBackboneElement};
+use super::*;
+
+/// 术语服务，无法判断（如值集或者代码系统未知）时返回`None`
+pub trait TerminologyProvider {
+    /// 编码是否属于值集，`system`为`None`时只按编码匹配
+    fn member_of(&self, system: Option<&str>, code: &str, value_set: &str) -> Result<Option<bool>>;
+
+    /// 代码系统中的编码`parent`是否包含编码`child`，编码包含其自身
+    fn subsumes(&self, system: &str, parent: &str, child: &str) -> Result<Option<bool>>;
+}
+
+/// 值集引用同一个值集时的最大嵌套深度，超过后视为无法判断
+const MAX_DEPTH: usize = 16;
+
+/// 本地术语库，值集和代码系统都按url（不含版本）索引
+#[derive(Debug, Default)]
+pub struct LocalTerminology {
+    value_sets: HashMap<String, ValueSet>,
+    /// 代码系统中每个编码的上级编码
+    code_systems: HashMap<String, HashMap<String, Option<String>>>,
+}
+
+impl LocalTerminology {
+    pub fn new() -> Self {
+        LocalTerminology::default()
+    }
+
+    /// 加载值集，值集必须有url
+    pub fn add_value_set(&mut self, value_set: ValueSet) -> Result<()> {
+        let url = value_set.url.as_ref().and_then(|url| url.value.clone())
+            .ok_or_else(|| FhirError::error("没有url的ValueSet不能被引用"))?;
+        self.value_sets.insert(url, value_set);
+        Ok(())
+    }
+
+    /// 加载代码系统，代码系统必须有url，编码的层级来自嵌套的concept
+    pub fn add_code_system(&mut self, code_system: CodeSystem) -> Result<()> {
+        let url = code_system.url.as_ref().and_then(|url| url.value.clone())
+            .ok_or_else(|| FhirError::error("没有url的CodeSystem不能被引用"))?;
+        let mut concepts = HashMap::new();
+        collect_concepts(code_system.concept.as_deref().unwrap_or_default(), None, &mut concepts);
+        self.code_systems.insert(url, concepts);
+        Ok(())
+    }
+
+    fn member_of_value_set(&self, system: Option<&str>, code: &str, url: &str, depth: usize) -> Option<bool> {
+        let url = url.split('|').next().unwrap_or(url);
+        let value_set = self.value_sets.get(url)?;
+        if depth > MAX_DEPTH {
+            return None
+        }
+
+        if let Some(contains) = value_set.expansion.as_ref().and_then(|expansion| expansion.contains.as_ref()) {
+            return Some(expansion_contains(contains, system, code))
+        }
+
+        let compose = value_set.compose.as_ref()?;
+        let included = any_of(compose.include.iter().flatten()
+            .map(|include| self.include_matches(include, system, code, depth)));
+        match included {
+            Some(true) => {
+                let excluded = any_of(compose.exclude.iter().flatten()
+                    .map(|exclude| self.include_matches(exclude, system, code, depth)));
+                excluded.map(|excluded| !excluded)
+            },
+            other => other,
+        }
+    }
+
+    /// 编码是否满足值集中的一条include（或exclude）规则
+    fn include_matches(&self, include: &ValueSetComposeIncludeBackboneElement, system: Option<&str>, code: &str, depth: usize) -> Option<bool> {
+        let mut result = Some(true);
+
+        for value_set in include.value_set.iter().flatten() {
+            if let Some(url) = value_set.value.as_deref() {
+                result = all_of([result, self.member_of_value_set(system, code, url, depth + 1)]);
+            }
+        }
+
+        let include_system = match include.system.as_ref().and_then(|system| system.value.as_deref()) {
+            Some(include_system) => include_system,
+            None => return result,
+        };
+        if system.is_some_and(|system| system != include_system) {
+            return Some(false)
+        }
+
+        if let Some(concepts) = &include.concept {
+            let listed = concepts.iter().any(|concept| concept.code.as_ref().and_then(|code| code.value.as_deref()) == Some(code));
+            return all_of([result, Some(listed)])
+        }
+
+        if let Some(filters) = &include.filter {
+            let matched = filters.iter().map(|filter| self.filter_matches(include_system, filter, code));
+            return all_of(std::iter::once(result).chain(matched))
+        }
+
+        let concepts = self.code_systems.get(include_system);
+        all_of([result, concepts.map(|concepts| concepts.contains_key(code))])
+    }
+
+    /// 编码是否满足过滤条件，只支持按编码（`concept`、`code`）过滤
+    fn filter_matches(&self, system: &str, filter: &ValueSetComposeIncludeFilterBackboneElement, code: &str) -> Option<bool> {
+        let property = filter.property.as_ref().and_then(|property| property.value.as_deref());
+        let op = filter.op.as_ref().and_then(|op| op.value.as_deref())?;
+        let value = filter.value.as_ref().and_then(|value| value.value.as_deref())?;
+        if !matches!(property, Some("concept" | "code")) {
+            return None
+        }
+
+        match op {
+            "=" => Some(code == value),
+            "is-a" => self.is_a(system, value, code),
+            "descendent-of" => self.is_a(system, value, code).map(|is_a| is_a && code != value),
+            "is-not-a" => self.is_a(system, value, code).map(|is_a| !is_a),
+            "generalizes" => self.is_a(system, code, value),
+            "child-of" => {
+                let concepts = self.code_systems.get(system)?;
+                Some(concepts.get(code)?.as_deref() == Some(value))
+            },
+            "in" => Some(value.split(',').any(|item| item.trim() == code)),
+            "not-in" => Some(value.split(',').all(|item| item.trim() != code)),
+            "regex" => Regex::new(&format!("^(?:{})$", value)).ok().map(|regex| regex.is_match(code)),
+            _ => None,
+        }
+    }
+
+    /// 编码`code`是否为`parent`或者其下级编码
+    fn is_a(&self, system: &str, parent: &str, code: &str) -> Option<bool> {
+        let concepts = self.code_systems.get(system)?;
+        if !concepts.contains_key(parent) {
+            return None
+        }
+
+        let mut current = Some(code);
+        while let Some(code) = current {
+            if code == parent {
+                return Some(true)
+            }
+            current = concepts.get(code)?.as_deref();
+        }
+        Some(false)
+    }
+}
+
+impl TerminologyProvider for LocalTerminology {
+    fn member_of(&self, system: Option<&str>, code: &str, value_set: &str) -> Result<Option<bool>> {
+        Ok(self.member_of_value_set(system, code, value_set, 0))
+    }
+
+    fn subsumes(&self, system: &str, parent: &str, child: &str) -> Result<Option<bool>> {
+        Ok(self.is_a(system, parent, child))
+    }
+}
+
+fn collect_concepts(concepts: &[CodeSystemConceptBackboneElement], parent: Option<&str>, result: &mut HashMap<String, Option<String>>) {
+    for concept in concepts {
+        if let Some(code) = concept.code.as_ref().and_then(|code| code.value.as_deref()) {
+            result.insert(code.to_string(), parent.map(str::to_string));
+            collect_concepts(concept.concept.as_deref().unwrap_or_default(), Some(code), result);
+        }
+    }
+}
+
+fn expansion_contains(contains: &[ValueSetExpansionContainsBackboneElement], system: Option<&str>, code: &str) -> bool {
+    contains.iter().any(|item| {
+        let item_system = item.system.as_ref().and_then(|system| system.value.as_deref());
+        let item_code = item.code.as_ref().and_then(|code| code.value.as_deref());
+        let matched = item_code == Some(code) && (system.is_none() || system == item_system);
+        matched || expansion_contains(item.contains.as_deref().unwrap_or_default(), system, code)
+    })
+}
+
+/// 三值逻辑的或：任何一个为true则为true，否则有无法判断的则无法判断
+fn any_of(results: impl Iterator<Item = Option<bool>>) -> Option<bool> {
+    let mut result = Some(false);
+    for item in results {
+        match item {
+            Some(true) => return Some(true),
+            None => result = None,
+            Some(false) => {},
+        }
+    }
+    result
+}
+
+/// 三值逻辑的与：任何一个为false则为false，否则有无法判断的则无法判断
+fn all_of(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut result = Some(true);
+    for item in results {
+        match item {
+            Some(false) => return Some(false),
+            None => result = None,
+            Some(true) => {},
+        }
+    }
+    result
+}
+
+/// 元素中的编码及其代码系统，支持Coding、CodeableConcept以及code、string等字符串类型
+fn codings(item: &dyn Executor) -> Result<Vec<(Option<String>, String)>> {
+    let coding = |coding: &dyn Executor| -> Result<Option<(Option<String>, String)>> {
+        let system = coding.element(&"system".to_string(), &None)?.to_strings()?;
+        let code = coding.element(&"code".to_string(), &None)?.to_strings()?;
+        Ok(code.map(|code| (system, code)))
+    };
+
+    match item.type_name() {
+        "Coding" => Ok(coding(item)?.into_iter().collect()),
+        "CodeableConcept" => {
+            let mut result = vec![];
+            for part in item.element(&"coding".to_string(), &None)?.iter() {
+                result.extend(coding(part)?);
+            }
+            Ok(result)
+        },
+        _ if system_type(item) == SystemType::String => Ok(vec![(None, item.to_strings()?)]),
+        other => Err(FhirError::Message(format!("类型{}不是编码，不能进行术语运算", other))),
+    }
+}
+
+fn single_item<'a, 'b>(input: &'b Collection<'a>) -> Result<Option<&'b dyn Executor>> {
+    match input.count() {
+        0 => Ok(None),
+        1 => Ok(input.iter().next()),
+        _ => Err(FhirError::error("集合内元素数量大于1")),
+    }
+}
+
+fn provider<'a>(context: &EvalContext<'a>) -> Result<&'a dyn TerminologyProvider> {
+    context.terminology().ok_or_else(|| FhirError::error("计算上下文中没有设置术语服务"))
+}
+
+/// 编码是否属于参数指定的值集，CodeableConcept中任何一个编码属于值集即可
+pub(crate) fn member_of<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let item = match single_item(&input)? {
+        Some(item) => item,
+        None => return Ok(Collection::new()),
+    };
+    let value_set = match args[0].eval_with(executor, context)?.to_strings()? {
+        Some(value_set) => value_set,
+        None => return Ok(Collection::new()),
+    };
+
+    let result = item_member_of(provider(context)?, item, &value_set)?;
+    Ok(result.map_or_else(Collection::new, Collection::new_boolean))
+}
+
+/// 元素中是否有编码属于值集，元素中没有编码时为false，同时用于校验元素的值域绑定
+pub(crate) fn item_member_of(provider: &dyn TerminologyProvider, item: &dyn Executor, value_set: &str) -> Result<Option<bool>> {
+    let mut results = vec![];
+    for (system, code) in codings(item)? {
+        results.push(provider.member_of(system.as_deref(), &code, value_set)?);
+    }
+    Ok(any_of(results.into_iter()))
+}
+
+/// 输入中的编码是否包含参数中的编码
+pub(crate) fn subsumes<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let other = args[0].eval_with(executor, context)?;
+    subsumption(&input, &other, context)
+}
+
+/// 输入中的编码是否被参数中的编码包含
+pub(crate) fn subsumed_by<'a>(input: Collection<'a>, args: &[Expr], executor: &'a dyn Executor, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let other = args[0].eval_with(executor, context)?;
+    subsumption(&other, &input, context)
+}
+
+/// 两侧的编码必须属于同一个代码系统，CodeableConcept中任何一对编码满足包含关系即可
+fn subsumption<'a>(parent: &Collection<'a>, child: &Collection<'a>, context: &EvalContext<'a>) -> Result<Collection<'a>> {
+    let (parent, child) = match (single_item(parent)?, single_item(child)?) {
+        (Some(parent), Some(child)) => (codings(parent)?, codings(child)?),
+        _ => return Ok(Collection::new()),
+    };
+
+    let provider = provider(context)?;
+    let mut results = vec![];
+    for (parent_system, parent_code) in &parent {
+        for (child_system, child_code) in &child {
+            match (parent_system, child_system) {
+                (Some(parent_system), Some(child_system)) if parent_system == child_system => {
+                    results.push(provider.subsumes(parent_system, parent_code, child_code)?);
+                },
+                (Some(_), Some(_)) => results.push(Some(false)),
+                _ => results.push(None),
+            }
+        }
+    }
+    Ok(any_of(results.into_iter()).map_or_else(Collection::new, Collection::new_boolean))
+}
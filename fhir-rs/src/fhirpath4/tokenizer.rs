@@ -1,3 +1,4 @@
+use std::ops::Range;
 use std::fmt::Display;
 use crate::prelude::{Result, FhirError};
 
@@ -7,6 +8,13 @@ pub enum Operator {
     Sub,
     Mul,
     Div,
+    IntDiv,
+    Mod,
+    Concat,
+    Union,
+    In,
+    Contains,
+    Implies,
     And,
     Or,
     Xor,
@@ -22,6 +30,14 @@ pub enum Operator {
     Unkown,
 }
 
+impl Operator {
+    /// 是否为以单词表示的操作符，这些单词出现在操作数的位置上时作为标识符处理，如`expansion.contains`
+    pub fn is_keyword(&self) -> bool {
+        matches!(self, Operator::IntDiv | Operator::Mod | Operator::In | Operator::Contains | Operator::Implies
+            | Operator::And | Operator::Or | Operator::Xor | Operator::As | Operator::Is)
+    }
+}
+
 impl Display for Operator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -29,6 +45,13 @@ impl Display for Operator {
             Operator::Sub => write!(f, "-"),
             Operator::Mul => write!(f, "*"),
             Operator::Div => write!(f, "/"),
+            Operator::IntDiv => write!(f, "div"),
+            Operator::Mod => write!(f, "mod"),
+            Operator::Concat => write!(f, "&"),
+            Operator::Union => write!(f, "|"),
+            Operator::In => write!(f, "in"),
+            Operator::Contains => write!(f, "contains"),
+            Operator::Implies => write!(f, "implies"),
             Operator::And => write!(f, "and"),
             Operator::Or => write!(f, "or"),
             Operator::Xor => write!(f, "xor"),
@@ -36,10 +59,10 @@ impl Display for Operator {
             Operator::Is => write!(f, "is"),
             Operator::Dot => write!(f, "."),
             Operator::Eq => write!(f, "="),
-            Operator::Gt => write!(f, ">="),
-            Operator::Ge => write!(f, ">"),
-            Operator::Lt => write!(f, "<="),
-            Operator::Le => write!(f, "<"),
+            Operator::Gt => write!(f, ">"),
+            Operator::Ge => write!(f, ">="),
+            Operator::Lt => write!(f, "<"),
+            Operator::Le => write!(f, "<="),
             Operator::Ne => write!(f, "!="),
             Operator::Unkown => write!(f, "??"),
         }
@@ -54,20 +77,33 @@ pub enum TokenType {
     CloseParen,
     OpenBracket,
     CloseBracket,
+    OpenBrace,
+    CloseBrace,
     Symbol(String),
     Text(String),
     DateTime(String),
     Number(String),
+    Variable(String),
+    Invocation(String),
     Operator(Operator),
     Comparator(Operator),
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Token {
-    pub pos:usize,
+    /// 词法单元在表达式中的起始位置（字节偏移）
+    pub pos: usize,
+    /// 词法单元在表达式中的结束位置（不含）
+    pub end: usize,
     pub token_type: TokenType,
 }
 
+impl Token {
+    pub fn span(&self) -> Range<usize> {
+        self.pos..self.end
+    }
+}
+
 pub struct Tokenizer<'l>{
     input: &'l [u8],
     index: usize,
@@ -87,24 +123,32 @@ impl<'l> Tokenizer<'l> {
         let mut tokens = Vec::new();
 
         loop {
+            let start = self.index;
             match self.next_char() {
                 Some(ch) => {
-                    let token = match ch {
+                    let token_type = match ch {
                         b' ' | b'\n' | b'\t' | b'\r' => continue,
-                        b'[' => Token{ pos: self.index, token_type: TokenType::OpenBracket },
-                        b']' => Token{ pos: self.index, token_type: TokenType::CloseBracket },
-                        b'(' => Token{ pos: self.index, token_type: TokenType::OpenParen },
-                        b')' => Token{ pos: self.index, token_type: TokenType::CloseParen },
-                        b',' => Token{ pos: self.index, token_type: TokenType::Colon },
-                        b'+' | b'-' | b'*' | b'/' | b'.' => self.parse_operator(ch)?,
-                        b'=' | b'!' | b'>' | b'<' | b'~' => self.parse_equality(ch)?,
-                        b'@' => self.parse_datetime(ch)?,
-                        b'\"' | b'\'' => self.parse_text(ch)?,
-                        b'0'..=b'9' => self.parse_number(ch)?,
-                        b'a'..=b'z' | b'A'..=b'Z' => self.parse_symbol(ch)?,
-                        _ => return Err(FhirError::Message(format!("表达式中出现了非法的字符[{}]", &ch)))
+                        b'[' => Ok(TokenType::OpenBracket),
+                        b']' => Ok(TokenType::CloseBracket),
+                        b'{' => Ok(TokenType::OpenBrace),
+                        b'}' => Ok(TokenType::CloseBrace),
+                        b'(' => Ok(TokenType::OpenParen),
+                        b')' => Ok(TokenType::CloseParen),
+                        b',' => Ok(TokenType::Colon),
+                        b'+' | b'-' | b'*' | b'/' | b'&' | b'|' | b'.' => self.parse_operator(ch),
+                        b'=' | b'!' | b'>' | b'<' | b'~' => self.parse_equality(ch),
+                        b'@' => self.parse_datetime(ch),
+                        b'%' => self.parse_variable(ch),
+                        b'$' => self.parse_invocation(ch),
+                        b'\"' | b'\'' => self.parse_text(ch),
+                        b'0'..=b'9' => self.parse_number(ch),
+                        b'a'..=b'z' | b'A'..=b'Z' => self.parse_symbol(ch),
+                        b'`' => self.parse_delimited_symbol(ch),
+                        _ => Err(FhirError::Message(format!("表达式中出现了非法的字符[{}]", char::from(ch)))),
                     };
-                    tokens.push(token);
+
+                    let token_type = token_type.map_err(|err| err.at(start..self.index))?;
+                    tokens.push(Token { pos: start, end: self.index, token_type });
                 }
                 None => break,
             }
@@ -135,63 +179,173 @@ impl<'l> Tokenizer<'l> {
         }
     }
 
-    // fn parse_escape(&self) {
+    /// 处理以@开头的日期时间，如@2015-02-04T14:34:28.123+08:00
+    fn parse_datetime(&mut self, _ch: u8) -> Result<TokenType> {
+        let mut scratch = vec![];
 
-    // }
+        while let Some(ch) = self.peek() {
+            match ch {
+                b'0'..=b'9' | b'-' | b':' | b'T' | b'+' | b'Z' => {
+                    scratch.push(ch);
+                    self.eat_char();
+                },
+                // 毫秒前的小数点后面必须是数字，否则是路径操作符
+                b'.' if matches!(self.input.get(self.index + 1), Some(b'0'..=b'9')) => {
+                    scratch.push(ch);
+                    self.eat_char();
+                },
+                _ => break,
+            }
+        }
 
-    // TODO: The time format has not been processed yet
-    fn parse_datetime(&mut self, _ch: u8) -> Result<Token> {
-        Ok(Token{ pos: self.index, token_type: TokenType::WhiteSpace })
+        match String::from_utf8(scratch) {
+            Ok(datetime) if !datetime.is_empty() => Ok(TokenType::DateTime(datetime)),
+            Ok(_) => Err(FhirError::error("表达式中的@后面没有有效的日期时间")),
+            Err(_) => Err(FhirError::error("不是有效的UTF8字符")),
+        }
     }
 
-    fn parse_text(&mut self, _ch: u8) -> Result<Token> {
+    /// 处理以%开头的环境变量，如%resource、%vs-administrative-gender、%`vs-administrative-gender`
+    fn parse_variable(&mut self, _ch: u8) -> Result<TokenType> {
         let mut scratch = vec![];
 
-        loop {
-            match self.peek() {
-                Some(ch) => {
+        match self.peek() {
+            Some(quote @ (b'`' | b'\'')) => {
+                self.eat_char();
+                loop {
+                    match self.next_char() {
+                        Some(ch) if ch == quote => break,
+                        Some(ch) => scratch.push(ch),
+                        None => return Err(FhirError::error("环境变量名称缺少结束的引号")),
+                    }
+                }
+            },
+            _ => {
+                while let Some(ch) = self.peek() {
                     match ch {
-                        b'\'' | b'\"' => {
-                            self.eat_char();
-                            break;
-                        },
-                        _ => {
+                        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'-' => {
                             scratch.push(ch);
                             self.eat_char();
                         },
+                        _ => break,
                     }
                 }
-                None => break,
+            },
+        }
+
+        match String::from_utf8(scratch) {
+            Ok(name) if !name.is_empty() => Ok(TokenType::Variable(name)),
+            Ok(_) => Err(FhirError::error("表达式中的%后面没有有效的环境变量名称")),
+            Err(_) => Err(FhirError::error("不是有效的UTF8字符")),
+        }
+    }
+
+    /// 处理以$开头的调用，只有$this、$index、$total
+    fn parse_invocation(&mut self, _ch: u8) -> Result<TokenType> {
+        let mut scratch = vec![];
+
+        while let Some(ch @ (b'a'..=b'z' | b'A'..=b'Z')) = self.peek() {
+            scratch.push(ch);
+            self.eat_char();
+        }
+
+        match String::from_utf8(scratch) {
+            Ok(name) => {
+                match name.as_str() {
+                    "this" | "index" | "total" => Ok(TokenType::Invocation(name)),
+                    other => Err(FhirError::Message(format!("无效的调用[${}]", other))),
+                }
+            },
+            Err(_) => Err(FhirError::error("不是有效的UTF8字符")),
+        }
+    }
+
+    /// 处理字符串，以开始的引号结束，其中的转义字符在此处还原，如`'a\'b'`
+    fn parse_text(&mut self, quote: u8) -> Result<TokenType> {
+        let mut scratch = vec![];
+
+        loop {
+            match self.next_char() {
+                Some(ch) if ch == quote => break,
+                Some(b'\\') => self.parse_escape(&mut scratch)?,
+                Some(ch) => scratch.push(ch),
+                None => return Err(FhirError::error("字符串缺少结束的引号")),
             }
         }
 
         match String::from_utf8(scratch) {
-            Ok(text) => Ok(Token{ pos: self.index, token_type: TokenType::Text(text) }),
+            Ok(text) => Ok(TokenType::Text(text)),
             Err(_) => Err(FhirError::error("不是有效的UTF8字符")),
         }
     }
 
-    fn parse_operator(&mut self, ch: u8) -> Result<Token> {
+    /// 还原`\\`之后的转义字符：`\\ \' \" \` \/ \f \n \r \t`以及`\uXXXX`
+    fn parse_escape(&mut self, scratch: &mut Vec<u8>) -> Result<()> {
+        let ch = match self.next_char() {
+            Some(ch @ (b'\\' | b'\'' | b'"' | b'`' | b'/')) => ch,
+            Some(b'f') => 0x0c,
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b'u') => {
+                let text = self.parse_unicode()?;
+                scratch.extend_from_slice(text.as_bytes());
+                return Ok(())
+            },
+            Some(other) => return Err(FhirError::Message(format!("字符串中有无效的转义字符[\\{}]", char::from(other)))),
+            None => return Err(FhirError::error("字符串缺少结束的引号")),
+        };
+
+        scratch.push(ch);
+        Ok(())
+    }
+
+    /// 处理`\u`之后的4位十六进制数，UTF-16的代理对由连续的两个`\uXXXX`组成
+    fn parse_unicode(&mut self) -> Result<String> {
+        let mut units = vec![self.parse_hex4()?];
+        if (0xD800..0xDC00).contains(&units[0]) && self.input[self.index..].starts_with(b"\\u") {
+            self.index += 2;
+            units.push(self.parse_hex4()?);
+        }
+
+        char::decode_utf16(units).collect::<std::result::Result<String, _>>()
+            .map_err(|_| FhirError::error("字符串中有无效的Unicode转义字符"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u16> {
+        let digits = match self.input.get(self.index..self.index + 4) {
+            Some(digits) if digits.iter().all(u8::is_ascii_hexdigit) => digits,
+            _ => return Err(FhirError::error("字符串中的\\u后面必须是4位十六进制数")),
+        };
+        self.index += 4;
+
+        let digits = std::str::from_utf8(digits).map_err(|_| FhirError::error("不是有效的UTF8字符"))?;
+        u16::from_str_radix(digits, 16).map_err(|_| FhirError::error("字符串中的\\u后面必须是4位十六进制数"))
+    }
+
+    fn parse_operator(&mut self, ch: u8) -> Result<TokenType> {
         match ch {
-            b'+' => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Add) }),
-            b'-' => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Sub) }),
-            b'*' => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Mul) }),
-            b'/' => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Div) }),
-            b'.' => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Dot) }),
+            b'+' => Ok(TokenType::Operator(Operator::Add)),
+            b'-' => Ok(TokenType::Operator(Operator::Sub)),
+            b'*' => Ok(TokenType::Operator(Operator::Mul)),
+            b'/' => Ok(TokenType::Operator(Operator::Div)),
+            b'&' => Ok(TokenType::Operator(Operator::Concat)),
+            b'|' => Ok(TokenType::Operator(Operator::Union)),
+            b'.' => Ok(TokenType::Operator(Operator::Dot)),
             _ => unreachable!()
         }
     }
 
-    fn parse_equality(&mut self, ch: u8) -> Result<Token> {
+    fn parse_equality(&mut self, ch: u8) -> Result<TokenType> {
         match ch {
-            b'=' | b'~' => Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Eq) }),
+            b'=' | b'~' => Ok(TokenType::Comparator(Operator::Eq)),
             b'!' => {
                 match self.peek() {
                     Some(next) => {
                         match next {
                             b'=' | b'~' => {
                                 self.eat_char();
-                                Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Ne) })
+                                Ok(TokenType::Comparator(Operator::Ne))
                             },
                             _ => Err(FhirError::error("表达式中存在无效的操作符[!]")),
                         }
@@ -201,44 +355,39 @@ impl<'l> Tokenizer<'l> {
             },
             b'>' => {
                 match self.peek() {
-                    Some(next) => {
-                        match next {
-                            b'=' => {
-                                self.eat_char();
-                                Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Gt) })
-                            },
-                            _ => Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Ge) }),
-                        }
-                    }
-                    None => Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Ge) }),
+                    Some(b'=') => {
+                        self.eat_char();
+                        Ok(TokenType::Comparator(Operator::Ge))
+                    },
+                    _ => Ok(TokenType::Comparator(Operator::Gt)),
                 }
             }
             b'<' => {
                 match self.peek() {
-                    Some(next) => {
-                        match next {
-                            b'=' => {
-                                self.eat_char();
-                                Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Lt) })
-                            },
-                            _ => Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Le) }),
-                        }
-                    }
-                    None => Ok(Token{ pos: self.index, token_type: TokenType::Comparator(Operator::Le) }),
+                    Some(b'=') => {
+                        self.eat_char();
+                        Ok(TokenType::Comparator(Operator::Le))
+                    },
+                    _ => Ok(TokenType::Comparator(Operator::Lt)),
                 }
             }
             _ => unreachable!()
         }
     }
 
-    fn parse_number(&mut self, ch: u8) -> Result<Token> {
+    fn parse_number(&mut self, ch: u8) -> Result<TokenType> {
         let mut scratch = vec![ch];
 
         loop {
             match self.peek() {
                 Some(ch) => {
                     match ch {
-                        b'0'..=b'9' | b'.' => {
+                        b'0'..=b'9' => {
+                            scratch.push(ch);
+                            self.eat_char();
+                        },
+                        // 小数点后面必须是数字，否则是路径操作符，如`5.toString()`
+                        b'.' if !scratch.contains(&b'.') && matches!(self.input.get(self.index + 1), Some(b'0'..=b'9')) => {
                             scratch.push(ch);
                             self.eat_char();
                         },
@@ -250,12 +399,31 @@ impl<'l> Tokenizer<'l> {
         }
 
         match String::from_utf8(scratch) {
-            Ok(number) => Ok(Token{ pos: self.index, token_type: TokenType::Number(number) }),
+            Ok(number) => Ok(TokenType::Number(number)),
+            Err(_) => Err(FhirError::error("不是有效的UTF8字符")),
+        }
+    }
+
+    /// 处理以反引号包围的标识符，如`` text.`div` ``，与关键字同名时也作为标识符
+    fn parse_delimited_symbol(&mut self, _ch: u8) -> Result<TokenType> {
+        let mut scratch = vec![];
+
+        loop {
+            match self.next_char() {
+                Some(b'`') => break,
+                Some(ch) => scratch.push(ch),
+                None => return Err(FhirError::error("标识符缺少结束的反引号")),
+            }
+        }
+
+        match String::from_utf8(scratch) {
+            Ok(symbol) if !symbol.is_empty() => Ok(TokenType::Symbol(symbol)),
+            Ok(_) => Err(FhirError::error("反引号中没有有效的标识符")),
             Err(_) => Err(FhirError::error("不是有效的UTF8字符")),
         }
     }
 
-    fn parse_symbol(&mut self, ch: u8) -> Result<Token> {
+    fn parse_symbol(&mut self, ch: u8) -> Result<TokenType> {
         let mut scratch = vec![ch];
         loop {
             match self.peek() {
@@ -275,12 +443,17 @@ impl<'l> Tokenizer<'l> {
         match String::from_utf8(scratch) {
             Ok(symbol) => {
                 match symbol.as_str() {
-                    "and" => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::And) }),
-                    "or" => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Or) }),
-                    "xor" => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Xor) }),
-                    "as" => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::As) }),
-                    "is" => Ok(Token{ pos: self.index, token_type: TokenType::Operator(Operator::Is) }),
-                    _ => Ok(Token{ pos: self.index, token_type: TokenType::Symbol(symbol) })
+                    "and" => Ok(TokenType::Operator(Operator::And)),
+                    "or" => Ok(TokenType::Operator(Operator::Or)),
+                    "xor" => Ok(TokenType::Operator(Operator::Xor)),
+                    "as" => Ok(TokenType::Operator(Operator::As)),
+                    "is" => Ok(TokenType::Operator(Operator::Is)),
+                    "div" => Ok(TokenType::Operator(Operator::IntDiv)),
+                    "mod" => Ok(TokenType::Operator(Operator::Mod)),
+                    "in" => Ok(TokenType::Operator(Operator::In)),
+                    "contains" => Ok(TokenType::Operator(Operator::Contains)),
+                    "implies" => Ok(TokenType::Operator(Operator::Implies)),
+                    _ => Ok(TokenType::Symbol(symbol))
                 }
             },
             Err(_) => Err(FhirError::error("不是有效的UTF8字符")),
@@ -305,8 +478,103 @@ pub fn test_tokenizer() {
     assert_eq!(list[8].token_type, TokenType::OpenBracket);
     assert_eq!(list[9].token_type, TokenType::Number("2".to_string()));
     assert_eq!(list[10].token_type, TokenType::CloseBracket);
-    assert_eq!(list[11].token_type, TokenType::WhiteSpace);
-    assert_eq!(list[12].token_type, TokenType::Comparator(Operator::Gt));
-    assert_eq!(list[13].token_type, TokenType::WhiteSpace);
-    assert_eq!(list[14].token_type, TokenType::Text("abc.fgh>10".into()));
+    assert_eq!(list[11].token_type, TokenType::Comparator(Operator::Ge));
+    assert_eq!(list[12].token_type, TokenType::Text("abc.fgh>10".into()));
+}
+
+#[test]
+pub fn test_tokenize_comparator_and_datetime() {
+    let mut tokenizer = Tokenizer::new("birthDate > @1974-12-25 and birthDate <= @2000");
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[1].token_type, TokenType::Comparator(Operator::Gt));
+    assert_eq!(list[2].token_type, TokenType::DateTime("1974-12-25".into()));
+    assert_eq!(list[5].token_type, TokenType::Comparator(Operator::Le));
+    assert_eq!(list[6].token_type, TokenType::DateTime("2000".into()));
+}
+
+#[test]
+pub fn test_tokenize_arithmetic() {
+    let mut tokenizer = Tokenizer::new("7 div 2 + 7 mod 2 & 'a'");
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[1].token_type, TokenType::Operator(Operator::IntDiv));
+    assert_eq!(list[3].token_type, TokenType::Operator(Operator::Add));
+    assert_eq!(list[5].token_type, TokenType::Operator(Operator::Mod));
+    assert_eq!(list[7].token_type, TokenType::Operator(Operator::Concat));
+}
+
+#[test]
+pub fn test_tokenize_variable() {
+    let mut tokenizer = Tokenizer::new("%resource.name.where($index > 0) and %`vs-administrative-gender` and %vs-contact-role");
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[0].token_type, TokenType::Variable("resource".to_string()));
+    assert_eq!(list[6].token_type, TokenType::Invocation("index".to_string()));
+    assert_eq!(list[11].token_type, TokenType::Variable("vs-administrative-gender".to_string()));
+    assert_eq!(list[13].token_type, TokenType::Variable("vs-contact-role".to_string()));
+}
+
+#[test]
+pub fn test_tokenize_membership() {
+    let mut tokenizer = Tokenizer::new("name | name implies 'a' in given and given contains 'b' and given.contains('c')");
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[1].token_type, TokenType::Operator(Operator::Union));
+    assert_eq!(list[3].token_type, TokenType::Operator(Operator::Implies));
+    assert_eq!(list[5].token_type, TokenType::Operator(Operator::In));
+    assert_eq!(list[9].token_type, TokenType::Operator(Operator::Contains));
+    assert_eq!(list[14].token_type, TokenType::Operator(Operator::Contains));
+}
+
+#[test]
+pub fn test_tokenize_delimited_symbol() {
+    let mut tokenizer = Tokenizer::new("text.`div`.exists() and `contains`");
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[2].token_type, TokenType::Symbol("div".to_string()));
+    assert_eq!(list[2].span(), 5..10);
+    assert_eq!(list[8].token_type, TokenType::Symbol("contains".to_string()));
+}
+
+#[test]
+pub fn test_tokenize_escaped_text() {
+    let mut tokenizer = Tokenizer::new(r#"'a\'b' + '\\d+\t\u00e9\uD83D\uDE00' + "say \"hi\"""#);
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[0].token_type, TokenType::Text("a'b".to_string()));
+    assert_eq!(list[2].token_type, TokenType::Text("\\d+\té😀".to_string()));
+    assert_eq!(list[4].token_type, TokenType::Text("say \"hi\"".to_string()));
+
+    assert!(Tokenizer::new(r"'\d+'").tokenize().is_err());
+    assert!(Tokenizer::new(r"'\u00'").tokenize().is_err());
+    assert!(Tokenizer::new("'abc").tokenize().is_err());
+}
+
+#[test]
+pub fn test_tokenize_empty_collection() {
+    let mut tokenizer = Tokenizer::new("iif(active, name, { })");
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[6].token_type, TokenType::OpenBrace);
+    assert_eq!(list[7].token_type, TokenType::CloseBrace);
+}
+
+#[test]
+pub fn test_tokenize_number_invocation() {
+    let mut tokenizer = Tokenizer::new("1.5.toString() + 5.toQuantity()");
+
+    let list = tokenizer.tokenize().unwrap();
+
+    assert_eq!(list[0].token_type, TokenType::Number("1.5".to_string()));
+    assert_eq!(list[1].token_type, TokenType::Operator(Operator::Dot));
+    assert_eq!(list[6].token_type, TokenType::Number("5".to_string()));
+    assert_eq!(list[7].token_type, TokenType::Operator(Operator::Dot));
 }
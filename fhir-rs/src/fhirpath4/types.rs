@@ -0,0 +1,109 @@
+//! FHIRPath中的基础类型（System类型）
+//!
+//! FHIR的简单类型（如`StringDt`、`IntegerDt`）和RUST的基础类型（如`String`、`isize`）
+//! 在运算时都按照其对应的System类型处理。
+use crate::prelude::{Result, FhirError};
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemType {
+    Boolean,
+    String,
+    Integer,
+    Decimal,
+    Date,
+    DateTime,
+    Time,
+    Quantity,
+    Other,
+}
+
+/// 判断元素对应的System类型
+pub fn system_type(executor: &dyn Executor) -> SystemType {
+    match executor.type_name() {
+        "Boolean" | "BooleanDt" => SystemType::Boolean,
+        "String" | "StringDt" | "CodeDt" | "IdDt" | "UriDt" | "UrlDt" | "CanonicalDt" | "OidDt" | "UuidDt"
+            | "MarkdownDt" | "Base64BinaryDt" | "Xhtml" | "XhtmlDt" => SystemType::String,
+        "Integer" | "PositiveInt" | "Integer64" | "IntegerDt" | "PositiveIntDt" | "UnsignedIntDt" | "Integer64Dt" => SystemType::Integer,
+        "Decimal" | "DecimalDt" => SystemType::Decimal,
+        "Date" | "DateDt" => SystemType::Date,
+        "DateTime" | "DateTimeDt" | "Instant" | "InstantDt" => SystemType::DateTime,
+        "Time" | "TimeDt" => SystemType::Time,
+        _ if quantity::is_quantity(executor) => SystemType::Quantity,
+        _ => SystemType::Other,
+    }
+}
+
+/// 是否为FHIR的简单类型，如`StringDt`，简单类型可能只有扩展而没有值
+pub fn is_primitive(executor: &dyn Executor) -> bool {
+    executor.type_name().ends_with("Dt")
+}
+
+/// 从表达式中取出类型说明符，如`Quantity`、`FHIR.string`、`System.Integer`
+pub(crate) fn type_specifier(expr: &Expr) -> Result<String> {
+    match expr {
+        Expr::PathExpr { symbol, .. } => Ok(symbol.clone()),
+        Expr::BinOpExpr { lhs, rhs, op: Operator::Dot, .. } => {
+            Ok(format!("{}.{}", type_specifier(lhs)?, type_specifier(rhs)?))
+        },
+        other => Err(FhirError::Message(format!("[{}]不是有效的类型说明符", other))),
+    }
+}
+
+/// 判断元素是否为指定的类型
+///
+/// FHIR的简单类型以小写字母开头，如`string`对应`StringDt`；
+/// System类型以大写字母开头，如`String`、`Integer`对应RUST的基础类型。
+/// 元素的类型派生自指定的类型时也视为该类型，如`Age`是`Quantity`。
+pub(crate) fn is_type(executor: &dyn Executor, specifier: &str) -> bool {
+    let (namespace, name) = match specifier.split_once('.') {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, specifier),
+    };
+
+    let is_primitive = name.starts_with(|ch: char| ch.is_ascii_lowercase());
+    let expected = match (namespace, is_primitive) {
+        (Some("System") | Some("FHIR") | None, false) => name.to_string(),
+        (Some("FHIR") | None, true) => {
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(first) => format!("{}{}Dt", first.to_ascii_uppercase(), chars.as_str()),
+                None => return false,
+            }
+        },
+        _ => return false,
+    };
+
+    let mut type_name = Some(executor.type_name());
+    while let Some(current) = type_name {
+        if current == expected {
+            return true
+        }
+        type_name = base_type(current);
+    }
+
+    false
+}
+
+/// 类型的父类型，如`Age`派生自`Quantity`，`code`派生自`string`
+fn base_type(type_name: &str) -> Option<&'static str> {
+    match type_name {
+        "Age" | "Count" | "Distance" | "Duration" | "MoneyQuantity" | "SimpleQuantity" => Some("Quantity"),
+        "CodeDt" | "IdDt" | "MarkdownDt" => Some("StringDt"),
+        "UrlDt" | "CanonicalDt" | "OidDt" | "UuidDt" => Some("UriDt"),
+        "PositiveIntDt" | "UnsignedIntDt" => Some("IntegerDt"),
+        _ => None,
+    }
+}
+
+#[test]
+pub fn test_is_type() {
+    use crate::prelude::{Age, CodeDt, Primitive};
+
+    assert!(is_type(&Age::default(), "Quantity"));
+    assert!(is_type(&Age::default(), "FHIR.Age"));
+    assert!(!is_type(&Age::default(), "System.Quantity.value"));
+    assert!(is_type(&CodeDt::new("male"), "string"));
+    assert!(!is_type(&CodeDt::new("male"), "System.string"));
+    assert!(is_type(&"male".to_string(), "System.String"));
+}
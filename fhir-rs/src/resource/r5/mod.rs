@@ -331,7 +331,7 @@ macro_rules! any_resources {
         }
 
         impl Executor for AnyResource {
-            fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection> {
+            fn element(&self, symbol: &String, index: &Option<usize>) -> Result<Collection<'_>> {
                 match self {
                     $(
                     AnyResource::$resource(resource) => resource.element(symbol, index),
@@ -339,7 +339,7 @@ macro_rules! any_resources {
                 }
             }
 
-            fn to_collection(&self, index: &Option<usize>) -> Collection {
+            fn to_collection(&self, index: &Option<usize>) -> Collection<'_> {
                 match self {
                     $(
                     AnyResource::$resource(resource) => resource.to_collection(index),
@@ -391,6 +391,61 @@ macro_rules! any_resources {
                 }
             }
         }
+
+        impl AnyResource {
+            /// 资源名称对应的类型元数据
+            pub fn type_info_of(name: &str) -> Option<&'static TypeInfo> {
+                match name {
+                    $(stringify!($resource) => Some(<$resource as Reflect>::type_info()),)+
+                    _ => None,
+                }
+            }
+
+            /// 实际的资源，用于按具体类型取出资源
+            pub fn as_any(&self) -> &dyn std::any::Any {
+                match self {
+                    $(AnyResource::$resource(resource) => resource,)+
+                }
+            }
+        }
+
+        impl PatchItem for AnyResource {
+            fn from_patch(value: &PatchValue) -> Result<Self> {
+                match value {
+                    PatchValue::Resource(resource) => Ok(resource.as_ref().clone()),
+                    _ => Err(FhirError::error("补丁中的值不是资源")),
+                }
+            }
+
+            fn as_patch(&self) -> &dyn Patch {
+                match self {
+                    $(AnyResource::$resource(resource) => resource,)+
+                }
+            }
+
+            fn as_patch_mut(&mut self) -> &mut dyn Patch {
+                match self {
+                    $(AnyResource::$resource(resource) => resource,)+
+                }
+            }
+        }
+
+        /// 未知类型的资源只有所有资源共有的元素
+        impl Reflect for AnyResource {
+            fn type_info() -> &'static TypeInfo {
+                static TYPE_INFO: TypeInfo = TypeInfo {
+                    name: "Resource",
+                    kind: TypeKind::Resource,
+                    elements: &[
+                        ElementInfo { name: "id", min: 0, max: Some(1), choices: &[], type_info: <String as Reflect>::type_info },
+                        ElementInfo { name: "meta", min: 0, max: Some(1), choices: &[], type_info: <Meta as Reflect>::type_info },
+                        ElementInfo { name: "implicitRules", min: 0, max: Some(1), choices: &[], type_info: <UriDt as Reflect>::type_info },
+                        ElementInfo { name: "language", min: 0, max: Some(1), choices: &[], type_info: <CodeDt as Reflect>::type_info },
+                    ],
+                };
+                &TYPE_INFO
+            }
+        }
     }
 }
 
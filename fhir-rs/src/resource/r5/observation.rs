@@ -70,8 +70,8 @@ pub struct Observation {
     #[fhir(name="performer", min="0", max="*", summary=true, modifier=false, choice="")]
     pub performer: Option<Vec<Reference>>,
     /// Actual result
-    #[fhir(name="value", min="0", max="1", summary=true, modifier=false, choice="")]
-    pub value: Option<Reference>,
+    #[fhir(name="value", min="0", max="1", summary=true, modifier=false, choice="Quantity|CodeableConcept|String|Boolean|Integer|Range|Ratio|SampledData|Time|DateTime|Period|Attachment|Reference")]
+    pub value: Option<AnyType>,
     /// Why the result is missing
     #[fhir(name="dataAbsentReason", min="0", max="1", summary=false, modifier=false, choice="")]
     pub data_absent_reason: Option<CodeableConcept>,
@@ -181,8 +181,8 @@ pub struct ObservationComponentBackboneElement {
     #[fhir(name="code", min="1", max="1", summary=true, modifier=false, choice="")]
     pub code: Option<CodeableConcept>,
     /// Actual component result
-    #[fhir(name="value", min="0", max="1", summary=true, modifier=false, choice="")]
-    pub value: Option<Reference>,
+    #[fhir(name="value", min="0", max="1", summary=true, modifier=false, choice="Quantity|CodeableConcept|String|Boolean|Integer|Range|Ratio|SampledData|Time|DateTime|Period|Attachment|Reference")]
+    pub value: Option<AnyType>,
     /// Why the component result is missing
     #[fhir(name="dataAbsentReason", min="0", max="1", summary=false, modifier=false, choice="")]
     pub data_absent_reason: Option<CodeableConcept>,
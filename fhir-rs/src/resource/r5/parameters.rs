@@ -35,8 +35,8 @@ pub struct ParametersParameterBackboneElement {
     #[fhir(name="name", min="1", max="1", summary=true, modifier=false, choice="")]
     pub name: Option<StringDt>,
     /// If parameter is a data type
-    #[fhir(name="value", min="0", max="1", summary=true, modifier=false, choice="")]
-    pub value: Option<Meta>,
+    #[fhir(name="value", min="0", max="1", summary=true, modifier=false, choice="String|Id|Base64Binary|Markdown|Uri|Url|Oid|Uuid|Canonical|Code|Boolean|DateTime|Date|Time|Instant|UnsignedInt|PositiveInt|Integer|Integer64|Decimal|Address|Age|Annotation|Attachment|CodeableConcept|CodeableReference|Coding|ContactPoint|Count|Distance|Duration|HumanName|Identifier|Money|Period|Quantity|Range|Ratio|RatioRange|Reference|SampledData|Signature|Timing|ContactDetail|DataRequirement|Expression|ParameterDefinition|RelatedArtifact|TriggerDefinition|UsageContext|Availability|ExtendedContactDetail|Dosage|Meta")]
+    pub value: Option<AnyType>,
     /// If parameter is a whole resource
     #[fhir(name="resource", min="0", max="1", summary=true, modifier=false, choice="")]
     pub resource: Option<AnyResource>,
@@ -0,0 +1,108 @@
+//! 元素的取值与ElementDefinition中`fixed[x]`、`pattern[x]`的比较
+//!
+//! 复杂类型按类型的元数据逐个比较子元素：fixed要求子元素完全相同，
+//! pattern只要求期望值中出现的子元素在实例中都能找到匹配的值；简单类型只比较取值。
+//! 比较的结果是差异的列表，每一项为`路径: 说明`，如`coding[0].code: 期望为[C]，实际为[N]`。
+use crate::prelude::*;
+
+/// 元素是否与期望值完全相同（`exact`），或者包含期望值中的所有内容
+pub(crate) fn matches(value: &dyn Executor, expected: &dyn Executor, exact: bool) -> bool {
+    differences(value, expected, exact).is_empty()
+}
+
+/// 元素与期望值之间的所有差异，符合要求时为空
+pub(crate) fn differences(value: &dyn Executor, expected: &dyn Executor, exact: bool) -> Vec<String> {
+    let mut diffs = vec![];
+    compare(value, expected, TypeInfo::lookup(expected.type_name()), exact, "", &mut diffs);
+    diffs
+}
+
+fn compare(value: &dyn Executor, expected: &dyn Executor, type_info: Option<&'static TypeInfo>, exact: bool, path: &str, diffs: &mut Vec<String>) {
+    let type_info = match type_info {
+        Some(type_info) if matches!(type_info.kind, TypeKind::Complex | TypeKind::Backbone | TypeKind::Resource) => type_info,
+        _ => {
+            if !same_value(value, expected) {
+                diffs.push(format!("{}期望为[{}]，实际为[{}]", label(path), display(expected), display(value)));
+            }
+            return
+        },
+    };
+
+    if value.type_name() != expected.type_name() {
+        diffs.push(format!("{}期望类型为[{}]，实际为[{}]", label(path), expected.type_name(), value.type_name()));
+        return
+    }
+
+    for element in type_info.elements {
+        let name = element.name.to_string();
+        let child = match path {
+            "" => name.clone(),
+            path => format!("{}.{}", path, &name),
+        };
+        let (values, expected) = match (value.element(&name, &None), expected.element(&name, &None)) {
+            (Ok(values), Ok(expected)) => (values, expected),
+            _ => {
+                diffs.push(format!("{}无法读取元素的值", label(&child)));
+                continue
+            },
+        };
+
+        // 可选类型的子元素按实际的类型比较
+        let types = element.types();
+        let child_type = |item: &dyn Executor| match types.as_slice() {
+            [type_info] => Some(*type_info),
+            _ => TypeInfo::lookup(item.type_name()),
+        };
+        let indexed = |index: usize| match element.max {
+            Some(1) => child.clone(),
+            _ => format!("{}[{}]", &child, index),
+        };
+
+        if exact {
+            if values.count() != expected.count() {
+                diffs.push(format!("{}期望有{}个值，实际为{}个", label(&child), expected.count(), values.count()));
+                continue
+            }
+
+            for (index, (value, expected)) in values.iter().zip(expected.iter()).enumerate() {
+                compare(value, expected, child_type(expected), true, &indexed(index), diffs);
+            }
+        } else {
+            for (index, expected) in expected.iter().enumerate() {
+                // 期望值与所有实际值都不匹配时，给出与差异最少的值之间的比较结果
+                let closest = values.iter()
+                    .map(|value| {
+                        let mut candidate = vec![];
+                        compare(value, expected, child_type(expected), false, &indexed(index), &mut candidate);
+                        candidate
+                    })
+                    .min_by_key(Vec::len);
+
+                match closest {
+                    Some(candidate) => diffs.extend(candidate),
+                    None => diffs.push(format!("{}缺少期望的值[{}]", label(&indexed(index)), display(expected))),
+                }
+            }
+        }
+    }
+}
+
+/// 简单类型只比较取值，无法直接比较的类型（如精度不同的日期）按字符串比较
+fn same_value(value: &dyn Executor, expected: &dyn Executor) -> bool {
+    match expected.eq(value) {
+        Ok(bl) => bl,
+        Err(_) => matches!((value.to_strings(), expected.to_strings()), (Ok(lhs), Ok(rhs)) if lhs == rhs),
+    }
+}
+
+fn label(path: &str) -> String {
+    match path {
+        "" => String::new(),
+        path => format!("{}: ", path),
+    }
+}
+
+/// 复杂类型无法转换为字符串，显示类型的名称
+fn display(item: &dyn Executor) -> String {
+    item.to_strings().unwrap_or_else(|_| item.type_name().to_string())
+}
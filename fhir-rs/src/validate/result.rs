@@ -29,6 +29,8 @@ pub struct ValidateResultItem {
     pub path: String,
     pub expression: String,
     pub message: String,
+    /// 产生该结果的约束的key，如`ele-1`、`dom-2`
+    pub key: Option<String>,
 }
 
 impl ValidateResultItem {
@@ -37,9 +39,15 @@ impl ValidateResultItem {
             status,
             path: path.clone(),
             expression: expression.clone(),
-            message
+            message,
+            key: None,
         }
     }
+
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
 }
 
 impl Into<OperationOutcomeIssueBackboneElement> for ValidateResultItem {
@@ -51,7 +59,10 @@ impl Into<OperationOutcomeIssueBackboneElement> for ValidateResultItem {
             modifier_extension: None,
             severity: Some(self.status.into()),
             code: Some(CodeDt::new("processing")),
-            details: None,
+            details: self.key.map(|key| CodeableConcept {
+                coding: Some(vec![Coding { code: Some(CodeDt::new(key)), ..Default::default() }]),
+                ..Default::default()
+            }),
             diagnostics: Some(StringDt::new(self.message)),
             location: Some(vec![StringDt::new(self.path)]),
             expression: Some(vec![StringDt::new(self.expression)]),
@@ -97,6 +108,10 @@ impl ValidateResult {
             self.items.push(item)
         }
     }
+
+    pub fn items(&self) -> &[ValidateResultItem] {
+        &self.items
+    }
 }
 
 impl Into<OperationOutcome> for ValidateResult {
@@ -1,30 +1,117 @@
 use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
 use crate::prelude::*;
+use super::pattern;
 
+/// 切片区分器的类型
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum SlicingType {
-    TYP,
+    /// value：区分器路径上的值与切片中定义的fixed[x]或者pattern[x]相同
     VAL,
+    /// exists：区分器路径上的值是否存在，由切片中定义的基数决定
+    EXS,
+    /// pattern：已废弃，与value相同，按pattern[x]匹配
+    PAT,
+    /// type：区分器路径上的值的类型
+    TYP,
+    /// profile：区分器路径上的值符合的Profile
+    PRF,
+    /// position：按元素在集合中的位置区分
+    POS,
     OTH,
 }
 
 impl From<String> for SlicingType {
     fn from(value: String) -> Self {
         match value.as_str() {
-            "type" => Self::TYP,
             "value" => Self::VAL,
+            "exists" => Self::EXS,
+            "pattern" => Self::PAT,
+            "type" => Self::TYP,
+            "profile" => Self::PRF,
+            "position" => Self::POS,
             _ => Self::OTH,
         }
     }
 }
 
 #[derive(Debug)]
-pub struct Slicing {
+pub struct Discriminator {
     pub typ: SlicingType,
     pub path: String,
 }
 
+/// 切片规则，是否允许不属于任何切片的元素
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlicingRules {
+    Closed,
+    Open,
+    /// 不属于任何切片的元素只能出现在所有切片元素之后
+    OpenAtEnd,
+}
+
+impl From<String> for SlicingRules {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "closed" => Self::Closed,
+            "openAtEnd" => Self::OpenAtEnd,
+            _ => Self::Open,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Slicing {
+    pub discriminators: Vec<Discriminator>,
+    pub rules: SlicingRules,
+    pub ordered: bool,
+    /// 按父元素分组，每个父元素下被切片的元素在集合中的范围，如`Patient.name.given`按每个`name`分组
+    pub groups: Vec<Range<usize>>,
+    /// 按定义的顺序，每个切片匹配到的元素在集合中的位置
+    pub slices: Vec<(String, Vec<usize>)>,
+}
+
+impl Slicing {
+    fn new(slicing: &ElementDefinitionSlicingElement, groups: Vec<Range<usize>>) -> Self {
+        let discriminators = slicing.discriminator.iter().flatten()
+            .map(|discriminator| Discriminator {
+                typ: discriminator.type_.as_ref().and_then(|typ| typ.value.clone()).unwrap_or_default().into(),
+                path: discriminator.path.as_ref().and_then(|path| path.value.clone()).unwrap_or_default(),
+            })
+            .collect();
+
+        Self {
+            discriminators,
+            rules: slicing.rules.as_ref().and_then(|rules| rules.value.clone()).unwrap_or_default().into(),
+            ordered: slicing.ordered.as_ref().and_then(|ordered| ordered.value).unwrap_or(false),
+            groups,
+            slices: vec![],
+        }
+    }
+
+    /// 每个父元素下属于切片的元素数量
+    fn group_counts(&self, indexes: &[usize]) -> Vec<usize> {
+        self.groups.iter()
+            .map(|group| indexes.iter().filter(|index| group.contains(index)).count())
+            .collect()
+    }
+}
+
+/// 根据区分器判断元素是否属于切片
+enum Matcher<'a> {
+    Value(CompiledPath, &'a AnyType, bool),
+    Exists(CompiledPath, bool),
+    Type(CompiledPath, Vec<String>),
+    /// 区分器路径、取元素声明的Profile（`meta.profile`）的路径以及允许的Profile
+    Profile(CompiledPath, CompiledPath, Vec<String>),
+    /// 每个父元素下属于切片的位置范围
+    Position(Vec<Range<usize>>),
+}
+
 #[derive(Debug)]
 pub struct Validator {
     pub root: ElementDefinition,
@@ -32,6 +119,10 @@ pub struct Validator {
     pub current: Cell<usize>,
     pub slicing: RefCell<HashMap<String, Slicing>>,
     pub empty_collection: RefCell<HashSet<String>>,
+    pub paths: Arc<PathCache>,
+    pub terminology: Option<Arc<LocalTerminology>>,
+    pub resolver: Option<Rc<MapResolver>>,
+    pub profiles: HashMap<String, Arc<StructureDefinition>>,
 }
 
 impl Validator {
@@ -49,6 +140,10 @@ impl Validator {
                             current: Cell::new(0),
                             slicing: RefCell::new(HashMap::new()),
                             empty_collection : RefCell::new(HashSet::new()),
+                            paths: Arc::new(PathCache::default()),
+                            terminology: None,
+                            resolver: None,
+                            profiles: HashMap::new(),
                         }
                     },
                     None => unreachable!(),
@@ -58,15 +153,48 @@ impl Validator {
         }
     }
 
+    /// 使用共享的表达式缓存，多个Validator校验同一批资源时只需编译一次表达式
+    pub fn with_path_cache(mut self, paths: Arc<PathCache>) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// 使用本地术语库校验元素的值域绑定，未设置时跳过值域验证
+    pub fn with_terminology(mut self, terminology: Arc<LocalTerminology>) -> Self {
+        self.terminology = Some(terminology);
+        self
+    }
+
+    /// 校验引用时，除了内嵌资源和Bundle中的资源，还从这里查找被引用的资源
+    pub fn with_resolver(mut self, resolver: MapResolver) -> Self {
+        self.resolver = Some(Rc::new(resolver));
+        self
+    }
+
+    /// 添加元素类型中声明的Profile（如Identifier、Extension的Profile），按url查找，用于递归校验复杂类型
+    pub fn with_profile(mut self, profile: StructureDefinition) -> Self {
+        if let Some(url) = profile.url.as_ref().and_then(|url| url.value.clone()) {
+            self.profiles.insert(url, Arc::new(profile));
+        }
+        self
+    }
+
     /// 校验整个资源的入口函数
     /// 异常情况表示是Profile的问题，资源的问题则输出到OperationOutcome中
     pub fn validate<R: Resource + Executor>(&mut self, resource: &R) -> Result<ValidateResult> {
+        self.validate_executor(resource)
+    }
+
+    /// 校验资源或者复杂类型的元素，复杂类型的Profile以类型名称作为根元素的路径，如`Identifier.system`
+    fn validate_executor(&mut self, resource: &dyn Executor) -> Result<ValidateResult> {
         let mut validate_result = ValidateResult::new();
+        validate_result.add_result_item(self.validate_root(resource)?);
 
         while let Some(element) = self.next() {
             let rss: Vec<ValidateResultItem> = self.validate_element(resource, element)?;
             validate_result.add_result_item(rss);
         }
+        validate_result.add_result_item(self.validate_slicing_rules());
 
         Ok(validate_result)
     }
@@ -234,7 +362,7 @@ impl Validator {
         }
     }
 
-    fn validate_element<R: Resource + Executor>(&self, resource: &R, element: &ElementDefinition) -> Result<Vec<ValidateResultItem>> {
+    fn validate_element(&self, resource: &dyn Executor, element: &ElementDefinition) -> Result<Vec<ValidateResultItem>> {
         if self.is_slice_element(&element) {
             self.validate_slice_element(resource, element)
         } else {
@@ -255,7 +383,8 @@ impl Validator {
                     if path.starts_with(col) {return Ok(rss)}
                 }
 
-                let expr = Expr::parse(path.clone())?;
+                // 可选类型的元素，如value[x]，按元素名称取值
+                let expr = self.paths.get_or_compile(&path.replace("[x]", ""))?;
                 let collection = expr.eval(resource)?;
 
                 // 最小值约束
@@ -288,45 +417,26 @@ impl Validator {
                     }
                 }
 
-                // 如果存在约束，则执行约束
-                // 目前存在几种约束：
-                // 1. dom-x: 这种约束针对整个资源
-                // 2. ele-x: 这种约束针对的是对应的元素
-                if let Some(constraints) = &element.constraint {
-                    for constraint in constraints {
-                        if let Some(key) = &constraint.key {
-                            let id = key.clone().value.unwrap();
-                            match id.as_str() {
-                                "ele-1" => {
-                                    if self.constraint_ele_1(&collection) {
-                                        rss.push(ValidateResultItem::new(ValidateStatus::Error, &path, &path, "违反约束[ele-1], 元素不能为空。".to_string()));
-                                    } else {
-                                        rss.push(ValidateResultItem::new(ValidateStatus::Success, &path, &path, "符合约束[ele-1]要求。".to_string()));
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                }
+                // 执行元素定义中的所有约束，如ele-1、dom-x、ext-1以及Profile中定义的约束
+                rss.extend(self.validate_constraints(resource, element, &path, &collection));
 
-                // 如果存在Slicing元素，其中定义了切片的规则（过滤条件）
+                // 如果存在Slicing元素，其中定义了切片的规则（区分器），随后的切片元素按区分器筛选集合
                 if let Some(slicing) = &element.slicing {
-                    if let Some(discriminators) = &slicing.discriminator {
-                        let discriminator = &discriminators[0];
-                        let typ = discriminator.type_.clone().unwrap().value.unwrap();
-                        let filter_path = discriminator.path.clone().unwrap().value.unwrap();
-                        
-                        self.push_slicing(path.clone(), Slicing{typ: typ.into(), path: filter_path});
-                    }
+                    let groups = self.slicing_groups(resource, &path, collection.count())?;
+                    self.push_slicing(path.clone(), Slicing::new(slicing, groups));
                 }
 
                 // binding
-                // 缺少术语系统支持，暂时无法实现对binding的验证
-                if let Some(_binding) = &element.binding {
-                    rss.push(ValidateResultItem::new(ValidateStatus::Skip, &path, &path, "系统暂时不支持值域验证。".to_string()));
+                if let Some(binding) = &element.binding {
+                    rss.extend(self.validate_binding(binding, &path, &collection));
                 }
 
+                // fixed[x]和pattern[x]
+                rss.extend(self.validate_value(element, &path, &collection));
+
+                // 可选类型、引用的目标以及类型的Profile
+                rss.extend(self.validate_types(resource, element, &path, &collection)?);
+
                 if collection.count() == 0 {
                     empty_collection.insert(path);
                 }
@@ -340,141 +450,589 @@ impl Validator {
     fn validate_slice_element(&self, resource: &dyn Executor, element: &ElementDefinition) -> Result<Vec<ValidateResultItem>> {
         let mut rss = vec![];
 
-        match &element.path {
-            Some(path) => {
-                let path = path.value.clone().unwrap();
-                println!("path => {}", &path);
+        let path = match &element.path {
+            Some(path) => path.value.clone().unwrap(),
+            None => return Err(FhirError::error("StructureDefinition.element中未指定path元素")),
+        };
+        // 所有的切片元素都有SliceName，没有时属于Profile的问题
+        let slice_name = match &element.slice_name {
+            Some(slice_name) => slice_name.value.clone().unwrap(),
+            None => return Err(FhirError::Message(format!("切片元素[{}]中未指定sliceName", &path))),
+        };
+        let root = format!("{}:{}", &path, &slice_name);
+        debug!("slice => {}", &root);
 
-                // 如果存在SliceName,则要首先找到过滤条件，然后才能对过滤后的Collection进行验证
-                match &element.slice_name {
-                    Some(slice_name) => {
-                        // 根据SliceName查找过滤条件对应的值。（过滤条件在Slicing数组中)
-                        let slice_name = slice_name.clone().value.unwrap();
-                        let (key, value) = self.lookup_filter(&path, &slice_name)?;
-
-                        let path_exec = match value {
-                            AnyType::String(val) => format!("{}.where({} = '{}')", &path, &key, &val),
-                            AnyType::Uri(val) => format!("{}.where({} = '{}')", &path, &key, &val),
-                            AnyType::Coding(val) => {
-                                format!("{path}.where({key}.system = '{}' and {key}.code = '{}')", &val.system.unwrap(), &val.code.unwrap())
-                            },
-                            AnyType::CodeableConcept(val) => {
-                                if let Some(vec) = val.coding {
-                                    let coding = &vec[0];
-                                    format!("{path}.where({key}.coding.system = '{}' and {key}.coding.code = '{}')", &coding.system.clone().unwrap(), &coding.code.clone().unwrap())
-                                } else if let Some(text) = val.text{
-                                    format!("{path}.where({key} = '{}')", &text)
-                                } else {
-                                    return Err(FhirError::Message(format!("+dfd fdfsdfs")))
-                                }
-                            },
-                            _ => unimplemented!(),
-                        };
-
-                        info!("Slicing filter path => {}", &path_exec);
-                        let expr = Expr::parse(path_exec.clone())?;
-                        let collection = expr.eval(resource)?;
-
-                        // 最小值约束
-                        if let Some(min) = &element.min {
-                            let min = min.value.unwrap();
-                            if collection.count() < min {
-                                rss.push(ValidateResultItem::new(ValidateStatus::Error, &path, &path_exec, format!("低于期望的最小值[{}]", min)))
-                            } else {
-                                rss.push(ValidateResultItem::new(ValidateStatus::Success, &path, &path_exec, format!("符合期望的最小值[{}]", min)))
-                            }
-                        }
+        // 重新切片（reslice）和默认切片暂不支持，跳过切片及其子元素
+        if slice_name.contains('/') || slice_name == "@default" {
+            self.skip_children(&root);
+            rss.push(ValidateResultItem::new(ValidateStatus::Skip, &path, &root, format!("暂不支持切片[{}]", &slice_name)));
+            return Ok(rss)
+        }
 
-                        // 最大值约束
-                        if let Some(max) = &element.max {
-                            let max = max.clone().value.unwrap();
-                            let max = match max.as_str() {
-                                "*" => usize::max_value(),
-                                other => {
-                                    match other.parse() {
-                                        Ok(number) => number,
-                                        Err(_) => return Err(FhirError::Message(format!("最大值不是有效的数值[{}]", other))),
-                                    }
-                                }
-                            };
-
-                            if collection.count() > max {
-                                rss.push(ValidateResultItem::new(ValidateStatus::Error, &path, &path_exec, format!("不符合期望的最大值[{}]", max)))
-                            } else {
-                                rss.push(ValidateResultItem::new(ValidateStatus::Success, &path, &path_exec, format!("符合期望的最大值[{}]", max)))
-                            }
-                        }
+        let collection = self.paths.get_or_compile(&path.replace("[x]", ""))?.eval(resource)?;
+        let indexes = match self.slice_indexes(resource, element, &path, &root, &collection) {
+            Ok(indexes) => indexes,
+            Err(err) => {
+                self.skip_children(&root);
+                rss.push(ValidateResultItem::new(ValidateStatus::Skip, &path, &root, format!("无法确定切片[{}]的元素: {}", &slice_name, err)));
+                return Ok(rss)
+            },
+        };
 
-                        let rs = self.validate_slicing_element_with_collection(resource, &path, &slice_name, &path_exec)?;
-                        rss.extend(rs);
-                    },
-                    // 所有的切片元素，都应该由递归函数处理，理论上不会到达这里
-                    None => unreachable!(),
-                }
+        // 切片的基数按每个父元素分别计算
+        let counts = match self.slicing.borrow_mut().get_mut(&path) {
+            Some(slicing) => {
+                slicing.slices.push((slice_name.clone(), indexes.clone()));
+                slicing.group_counts(&indexes)
+            },
+            None => vec![indexes.len()],
+        };
 
-                Ok(rss)
+        // 最小值约束
+        if let Some(min) = &element.min {
+            let min = min.value.unwrap();
+            if counts.iter().any(|count| *count < min) {
+                rss.push(ValidateResultItem::new(ValidateStatus::Error, &path, &root, format!("低于期望的最小值[{}]", min)))
+            } else {
+                rss.push(ValidateResultItem::new(ValidateStatus::Success, &path, &root, format!("符合期望的最小值[{}]", min)))
             }
-            None => Err(FhirError::error("")),
         }
+
+        // 最大值约束
+        if let Some(max) = max_of(element)? {
+            if counts.iter().any(|count| *count > max) {
+                rss.push(ValidateResultItem::new(ValidateStatus::Error, &path, &root, format!("大于期望的最大值[{}]", max)))
+            } else {
+                rss.push(ValidateResultItem::new(ValidateStatus::Success, &path, &root, format!("符合期望的最大值[{}]", max)))
+            }
+        }
+
+        let items: Vec<&dyn Executor> = collection.iter().enumerate()
+            .filter(|(index, _)| indexes.contains(index))
+            .map(|(_, item)| item)
+            .collect();
+        rss.extend(self.validate_slice_children(resource, &path, &root, &items)?);
+
+        Ok(rss)
     }
 
-    fn validate_slicing_element_with_collection(&self, resource: &dyn Executor, path: &String, slice_name: &String, path_exec: &String) -> Result<Vec<ValidateResultItem>> {
+    /// 校验切片的子元素，子元素的路径相对于切片中的每个元素计算
+    /// Encounter.identifier:BID.system中，root = Encounter.identifier:BID，相对路径为system
+    fn validate_slice_children(&self, resource: &dyn Executor, path: &String, root: &String, items: &[&dyn Executor]) -> Result<Vec<ValidateResultItem>> {
         let mut rss = vec![];
+        let prefix = format!("{}.", root);
 
-        while let Some(element) = self.next() {
-            let root = format!("{}:{}", &path, &slice_name);
+        while let Some(element) = self.peek().filter(|element| element.id.as_ref().is_some_and(|id| id.starts_with(&prefix))) {
+            self.next();
+            let id = element.id.clone().unwrap();
+            let relative = &id[prefix.len()..];
+            debug!("slice path => {}", &id);
+
+            // 切片内部的切片暂不支持
+            if relative.contains(':') {
+                continue
+            }
+
+            let element_path = format!("{}.{}", path, relative);
+            let expr = self.paths.get_or_compile(&relative.replace("[x]", ""))?;
+            let min = element.min.as_ref().and_then(|min| min.value);
+            let max = max_of(element)?;
 
-            match &element.id {
-                Some(id) => {
-                    if !id.starts_with(&root) {
-                        break;
+            let mut collection = Collection::new();
+            for item in items {
+                let context = EvalContext::new(*item).with_resource(resource).with_root_resource(resource);
+                let resp = expr.eval_with(*item, &context)?;
+
+                // 最小值约束
+                if let Some(min) = min {
+                    if resp.count() < min {
+                        rss.push(ValidateResultItem::new(ValidateStatus::Error, &element_path, &id, format!("低于期望的最小值[{}]", min)))
+                    } else {
+                        rss.push(ValidateResultItem::new(ValidateStatus::Success, &element_path, &id, format!("符合期望的最小值[{}]", min)))
                     }
+                }
 
-                    let other = id.replace(&root, &path_exec);
-                    println!("slice path => {}", &other);
+                // 最大值约束
+                if let Some(max) = max {
+                    if resp.count() > max {
+                        rss.push(ValidateResultItem::new(ValidateStatus::Error, &element_path, &id, format!("大于期望的最大值[{}]", max)))
+                    } else {
+                        rss.push(ValidateResultItem::new(ValidateStatus::Success, &element_path, &id, format!("符合期望的最大值[{}]", max)))
+                    }
+                }
 
-                    let expr = Expr::parse(other.clone())?;
-                    let resp = expr.eval(resource)?;
+                collection.combine(resp);
+            }
 
-                    // 最小值约束
-                    if let Some(min) = &element.min {
-                        let min = min.value.unwrap();
-                        if resp.count() < min {
-                            rss.push(ValidateResultItem::new(ValidateStatus::Error, &path, &other, format!("低于期望的最小值[{}]", min)))
-                        } else {
-                            rss.push(ValidateResultItem::new(ValidateStatus::Success, &path, &other, format!("符合期望的最小值[{}]", min)))
-                        }
+            rss.extend(self.validate_constraints(resource, element, &element_path, &collection));
+            if let Some(binding) = &element.binding {
+                rss.extend(self.validate_binding(binding, &element_path, &collection));
+            }
+            rss.extend(self.validate_value(element, &element_path, &collection));
+            rss.extend(self.validate_types(resource, element, &element_path, &collection)?);
+        }
+
+        Ok(rss)
+    }
+
+    /// 按切片的所有区分器筛选集合，返回属于该切片的元素在集合中的位置
+    fn slice_indexes(&self, resource: &dyn Executor, element: &ElementDefinition, path: &String, root: &String, collection: &Collection) -> Result<Vec<usize>> {
+        let matchers = self.slice_matchers(element, path, root)?;
+
+        let mut indexes = vec![];
+        for (index, item) in collection.iter().enumerate() {
+            let context = EvalContext::new(item).with_resource(resource).with_root_resource(resource);
+            let mut matched = true;
+            for matcher in &matchers {
+                if !matcher.matches(index, item, &context)? {
+                    matched = false;
+                    break
+                }
+            }
+
+            if matched {
+                indexes.push(index)
+            }
+        }
+
+        Ok(indexes)
+    }
+
+    /// 根据切片定义中区分器路径对应的元素，确定每个区分器的匹配条件
+    fn slice_matchers<'a>(&'a self, element: &'a ElementDefinition, path: &String, root: &String) -> Result<Vec<Matcher<'a>>> {
+        let slicings = self.slicing.borrow();
+        let slicing = match slicings.get(path) {
+            Some(slicing) => slicing,
+            None => return Err(FhirError::Message(format!("未找到切片[{}]需要的Slicing信息", root))),
+        };
+        if slicing.discriminators.is_empty() {
+            return Err(FhirError::Message(format!("[{}]的Slicing中未定义区分器", path)))
+        }
+
+        let mut matchers = vec![];
+        for discriminator in &slicing.discriminators {
+            if let SlicingType::POS = discriminator.typ {
+                // 在每个父元素下，排在之前的切片所占的位置之后
+                let max = max_of(element)?;
+                let ranges = slicing.groups.iter()
+                    .map(|group| {
+                        let consumed = slicing.slices.iter()
+                            .flat_map(|(_, indexes)| indexes)
+                            .filter(|index| group.contains(index))
+                            .count();
+                        let start = group.start + consumed;
+                        start..max.map_or(group.end, |max| start.saturating_add(max).min(group.end))
+                    })
+                    .collect();
+                matchers.push(Matcher::Position(ranges));
+                continue
+            }
+
+            // 区分器路径指向被引用的资源时，在引用元素上查找定义
+            let (target, resolved) = match discriminator.path.strip_suffix("resolve()") {
+                Some(target) => (target.trim_end_matches('.'), true),
+                None => (discriminator.path.as_str(), false),
+            };
+            let definition = match target {
+                "$this" | "" => Some(element),
+                target => self.lookup_discriminator(root, target),
+            };
+            let definition = match definition {
+                Some(definition) => definition,
+                None => return Err(FhirError::Message(format!("找不到区分器路径[{}]对应的元素定义", &discriminator.path))),
+            };
+            let expr = self.paths.get_or_compile(&discriminator.path)?;
+
+            let matcher = match discriminator.typ {
+                SlicingType::VAL | SlicingType::PAT => {
+                    let exact = matches!(discriminator.typ, SlicingType::VAL);
+                    match (&definition.fixed, &definition.pattern) {
+                        (Some(fixed), _) if exact => Matcher::Value(expr, fixed, true),
+                        (_, Some(pattern)) => Matcher::Value(expr, pattern, false),
+                        (Some(fixed), None) => Matcher::Value(expr, fixed, false),
+                        (None, None) => return Err(FhirError::Message(format!("在定义中没有找到区分器[{}]需要的fixed或者pattern元素", &discriminator.path))),
+                    }
+                },
+                SlicingType::EXS => {
+                    let required = definition.min.as_ref().and_then(|min| min.value).unwrap_or(0) > 0;
+                    let prohibited = max_of(definition)? == Some(0);
+                    match (required, prohibited) {
+                        (true, false) => Matcher::Exists(expr, true),
+                        (false, true) => Matcher::Exists(expr, false),
+                        _ => return Err(FhirError::Message(format!("区分器[{}]对应的元素基数必须是1..或者0..0", &discriminator.path))),
+                    }
+                },
+                SlicingType::TYP => {
+                    let types: Vec<String> = definition.type_.iter().flatten()
+                        .flat_map(|typ| match resolved {
+                            true => canonicals(&typ.target_profile),
+                            false => typ.code.as_ref().and_then(|code| code.value.clone()).into_iter().collect(),
+                        })
+                        .collect();
+                    match resolved {
+                        true => Matcher::Profile(expr, self.paths.get_or_compile("meta.profile")?, types),
+                        false => Matcher::Type(expr, types),
                     }
+                },
+                SlicingType::PRF => {
+                    let profiles = definition.type_.iter().flatten()
+                        .flat_map(|typ| match resolved {
+                            true => canonicals(&typ.target_profile),
+                            false => canonicals(&typ.profile),
+                        })
+                        .collect();
+                    Matcher::Profile(expr, self.paths.get_or_compile("meta.profile")?, profiles)
+                },
+                SlicingType::POS => unreachable!(),
+                SlicingType::OTH => return Err(FhirError::Message(format!("不支持的区分器类型，路径为[{}]", &discriminator.path))),
+            };
+            matchers.push(matcher);
+        }
 
-                    // 最大值约束
-                    if let Some(max) = &element.max {
-                        let max = max.clone().value.unwrap();
-                        let max = match max.as_str() {
-                            "*" => usize::max_value(),
-                            other => {
-                                match other.parse() {
-                                    Ok(number) => number,
-                                    Err(_) => return Err(FhirError::Message(format!("最大值不是有效的数值[{}]", other))),
-                                }
-                            }
-                        };
+        Ok(matchers)
+    }
+
+    /// 按父元素将被切片的集合分组，如`Patient.name.given`在每个`name`下分别切片；
+    /// 父元素是资源本身时只有一组
+    fn slicing_groups(&self, resource: &dyn Executor, path: &str, count: usize) -> Result<Vec<Range<usize>>> {
+        let path = path.replace("[x]", "");
+        let (parent, child) = match path.rsplit_once('.') {
+            Some((parent, child)) if parent.contains('.') => (parent, child),
+            _ => return Ok(vec![Range { start: 0, end: count }]),
+        };
+
+        let child = self.paths.get_or_compile(child)?;
+        let mut groups = vec![];
+        let mut start = 0;
+        for item in self.paths.get_or_compile(parent)?.eval(resource)?.iter() {
+            let end = start + child.eval(item)?.count();
+            groups.push(start..end);
+            start = end;
+        }
+
+        Ok(groups)
+    }
+
+    /// 所有切片处理完成后，检查切片规则closed、openAtEnd以及ordered
+    fn validate_slicing_rules(&self) -> Vec<ValidateResultItem> {
+        let mut rss = vec![];
+        let slicings = self.slicing.borrow();
+        let mut paths: Vec<&String> = slicings.keys().collect();
+        paths.sort();
+
+        for path in paths {
+            let slicing = &slicings[path];
+            let mut errors = vec![];
+
+            // 每个元素所属的切片（按切片定义的顺序）
+            let count = slicing.groups.last().map_or(0, |group| group.end);
+            let mut owners: Vec<Vec<usize>> = vec![vec![]; count];
+            for (ordinal, (_, indexes)) in slicing.slices.iter().enumerate() {
+                for index in indexes {
+                    if let Some(owner) = owners.get_mut(*index) {
+                        owner.push(ordinal)
+                    }
+                }
+            }
+
+            for (index, owner) in owners.iter().enumerate() {
+                if owner.len() > 1 {
+                    let names: Vec<&str> = owner.iter().map(|ordinal| slicing.slices[*ordinal].0.as_str()).collect();
+                    errors.push(format!("第{}个元素同时属于多个切片[{}]", index, names.join(", ")));
+                }
+            }
 
-                        if resp.count() > max {
-                            rss.push(ValidateResultItem::new(ValidateStatus::Error, &path, &other, format!("大于期望的最大值[{}]", max)))
-                        } else {
-                            rss.push(ValidateResultItem::new(ValidateStatus::Success, &path, &other, format!("符合期望的最大值[{}]", max)))
+            match slicing.rules {
+                SlicingRules::Closed => {
+                    for (index, _) in owners.iter().enumerate().filter(|(_, owner)| owner.is_empty()) {
+                        errors.push(format!("第{}个元素不属于任何切片，切片规则为closed", index));
+                    }
+                },
+                SlicingRules::OpenAtEnd => {
+                    for group in &slicing.groups {
+                        let owners = &owners[group.clone()];
+                        let last = owners.iter().rposition(|owner| !owner.is_empty());
+                        let first_open = owners.iter().position(|owner| owner.is_empty());
+                        if let (Some(last), Some(first_open)) = (last, first_open) {
+                            if first_open < last {
+                                errors.push(format!("第{}个元素不属于任何切片，切片规则为openAtEnd，这类元素只能出现在最后", group.start + first_open));
+                            }
                         }
                     }
                 },
-                None => unreachable!(),
+                SlicingRules::Open => {},
+            }
+
+            // 顺序只在同一个父元素下比较
+            if slicing.ordered {
+                for group in &slicing.groups {
+                    let ordinals: Vec<usize> = owners[group.clone()].iter().filter_map(|owner| owner.first().copied()).collect();
+                    if let Some(position) = ordinals.windows(2).position(|pair| pair[0] > pair[1]) {
+                        errors.push(format!("切片[{}]的元素出现在切片[{}]之后，切片的顺序与定义不一致",
+                                            &slicing.slices[ordinals[position + 1]].0, &slicing.slices[ordinals[position]].0));
+                    }
+                }
+            }
+
+            match errors.is_empty() {
+                true => rss.push(ValidateResultItem::new(ValidateStatus::Success, path, path, "符合切片规则要求。".to_string())),
+                false => rss.extend(errors.into_iter().map(|error| ValidateResultItem::new(ValidateStatus::Error, path, path, error))),
             }
         }
+
+        rss
+    }
+
+    /// 校验根元素上的约束，如dom-x这类针对整个资源的约束
+    fn validate_root(&self, resource: &dyn Executor) -> Result<Vec<ValidateResultItem>> {
+        match &self.root.path {
+            Some(path) => {
+                let path = path.value.clone().unwrap();
+                let collection = self.paths.get_or_compile(&path)?.eval(resource)?;
+                Ok(self.validate_constraints(resource, &self.root, &path, &collection))
+            },
+            None => Err(FhirError::error("StructureDefinition.element中未指定path元素")),
+        }
+    }
+
+    /// 以集合中的每个元素为上下文执行约束的表达式，结果不为true时按约束的severity报告
+    /// 表达式无法编译或者执行时跳过该约束
+    fn validate_constraints(&self, resource: &dyn Executor, element: &ElementDefinition, path: &String, collection: &Collection) -> Vec<ValidateResultItem> {
+        let mut rss = vec![];
+
+        for constraint in element.constraint.iter().flatten() {
+            let (key, expression) = match (&constraint.key, &constraint.expression) {
+                (Some(key), Some(expression)) => (key.value.clone().unwrap_or_default(), expression.value.clone().unwrap_or_default()),
+                _ => continue,
+            };
+            let human = constraint.human.as_ref().and_then(|human| human.value.clone()).unwrap_or_default();
+            let status = match constraint.severity.as_ref().and_then(|severity| severity.value.as_deref()) {
+                Some("warning") => ValidateStatus::Warn,
+                _ => ValidateStatus::Error,
+            };
+
+            let expr = match self.paths.get_or_compile(&expression) {
+                Ok(expr) => expr,
+                Err(err) => {
+                    rss.push(ValidateResultItem::new(ValidateStatus::Skip, path, &expression, format!("无法执行约束[{}]: {}", key, err)).with_key(&key));
+                    continue
+                },
+            };
+
+            for item in collection.iter() {
+                let context = EvalContext::new(item).with_resource(resource).with_root_resource(resource);
+                let rs = match expr.eval_with(item, &context).and_then(|result| result.to_boolean()) {
+                    Ok(Some(true)) => ValidateResultItem::new(ValidateStatus::Success, path, &expression, format!("符合约束[{}]要求。", key)),
+                    Ok(_) => ValidateResultItem::new(status.clone(), path, &expression, format!("违反约束[{}]: {}", key, human)),
+                    Err(err) => ValidateResultItem::new(ValidateStatus::Skip, path, &expression, format!("无法执行约束[{}]: {}", key, err)),
+                };
+                rss.push(rs.with_key(&key));
+            }
+        }
+
+        rss
+    }
+
+    /// 按绑定强度校验元素的编码是否属于值集
+    /// required不符合时为错误，extensible为警告，preferred为提示，example不做验证
+    fn validate_binding(&self, binding: &ElementDefinitionBindingElement, path: &String, collection: &Collection) -> Vec<ValidateResultItem> {
+        let mut rss = vec![];
+
+        let strength = binding.strength.as_ref().and_then(|strength| strength.value.clone()).unwrap_or_default();
+        let status = match strength.as_str() {
+            "required" => ValidateStatus::Error,
+            "extensible" => ValidateStatus::Warn,
+            "preferred" => ValidateStatus::Info,
+            _ => return rss,
+        };
+
+        let value_set = match binding.value_set.as_ref().and_then(|value_set| value_set.value.clone()) {
+            Some(value_set) => value_set,
+            None => {
+                rss.push(ValidateResultItem::new(ValidateStatus::Skip, path, path, "值域绑定中未指定值集。".to_string()));
+                return rss
+            },
+        };
+
+        let terminology = match &self.terminology {
+            Some(terminology) => terminology,
+            None => {
+                rss.push(ValidateResultItem::new(ValidateStatus::Skip, path, path, "未设置术语库，暂时无法进行值域验证。".to_string()));
+                return rss
+            },
+        };
+
+        for item in collection.iter() {
+            let rs = match item_member_of(terminology.as_ref(), item, &value_set) {
+                Ok(Some(true)) => ValidateResultItem::new(ValidateStatus::Success, path, path, format!("符合值域[{}]要求。", &value_set)),
+                Ok(Some(false)) => ValidateResultItem::new(status.clone(), path, path, format!("编码不在{}绑定的值域[{}]中。", &strength, &value_set)),
+                Ok(None) => ValidateResultItem::new(ValidateStatus::Skip, path, path, format!("术语库中缺少值域[{}]需要的值集或者代码系统。", &value_set)),
+                Err(err) => ValidateResultItem::new(ValidateStatus::Skip, path, path, format!("无法验证值域[{}]: {}", &value_set, err)),
+            };
+            rss.push(rs);
+        }
+
+        rss
+    }
+
+    /// 校验元素的取值是否与fixed[x]完全相同，或者包含pattern[x]中的所有内容
+    fn validate_value(&self, element: &ElementDefinition, path: &String, collection: &Collection) -> Vec<ValidateResultItem> {
+        let (expected, exact, kind) = match (&element.fixed, &element.pattern) {
+            (Some(fixed), _) => (fixed, true, "固定值"),
+            (None, Some(pattern)) => (pattern, false, "模式值"),
+            (None, None) => return vec![],
+        };
+
+        collection.iter()
+            .map(|item| {
+                let diffs = pattern::differences(item, expected.as_executor(), exact);
+                match diffs.is_empty() {
+                    true => ValidateResultItem::new(ValidateStatus::Success, path, path, format!("符合{}要求。", kind)),
+                    false => ValidateResultItem::new(ValidateStatus::Error, path, path, format!("与{}不一致: {}", kind, diffs.join("；"))),
+                }
+            })
+            .collect()
+    }
+
+    /// 校验元素的类型：可选类型的元素只能使用允许的类型，引用的目标必须符合targetProfile，
+    /// 类型中声明了profile时，按Profile递归校验元素
+    fn validate_types(&self, resource: &dyn Executor, element: &ElementDefinition, path: &String, collection: &Collection) -> Result<Vec<ValidateResultItem>> {
+        let mut rss = vec![];
+        let types = match &element.type_ {
+            Some(types) if !types.is_empty() => types,
+            _ => return Ok(rss),
+        };
+        let is_choice = path.ends_with("[x]");
+
+        for item in collection.iter() {
+            let matched = types.iter().find(|typ| {
+                typ.code.as_ref().and_then(|code| code.value.as_ref())
+                    .is_some_and(|code| is_type(&Collection::new_ref(item), code))
+            });
+
+            let typ = match (matched, is_choice) {
+                (Some(typ), true) => {
+                    rss.push(ValidateResultItem::new(ValidateStatus::Success, path, path, format!("符合类型[{}]要求。", type_code(item))));
+                    typ
+                },
+                (Some(typ), false) => typ,
+                (None, true) => {
+                    let codes: Vec<String> = types.iter().filter_map(|typ| typ.code.as_ref().and_then(|code| code.value.clone())).collect();
+                    rss.push(ValidateResultItem::new(ValidateStatus::Error, path, path, format!("类型[{}]不在允许的类型[{}]中。", type_code(item), codes.join(", "))));
+                    continue
+                },
+                // 非可选类型的元素，类型由资源的结构保证
+                (None, false) => continue,
+            };
+
+            if item.type_name() == "Reference" && typ.target_profile.as_ref().is_some_and(|profiles| !profiles.is_empty()) {
+                rss.push(self.validate_reference(resource, item, typ, path)?);
+            }
+
+            for profile in canonicals(&typ.profile) {
+                rss.extend(self.validate_profile(item, &profile, path)?);
+            }
+        }
+
         Ok(rss)
     }
 
-    /// 是否违反了约束ele-1
-    fn constraint_ele_1(&self, collection: &Collection) -> bool {
-        collection.first().is_none()
+    /// 按引用地址、内嵌资源或者Bundle中的资源确定被引用资源的类型，检查是否符合targetProfile
+    fn validate_reference(&self, resource: &dyn Executor, item: &dyn Executor, typ: &ElementDefinitionTypeElement, path: &String) -> Result<ValidateResultItem> {
+        let target_profiles = canonicals(&typ.target_profile);
+        let reference = item.element(&"reference".to_string(), &None)?.to_strings()?;
+
+        let mut context = EvalContext::new(item).with_resource(resource).with_root_resource(resource);
+        if let Some(resolver) = &self.resolver {
+            context = context.with_resolver(resolver.as_ref());
+        }
+        // 无法解析的引用属于资源的问题，只影响该引用的校验结果
+        let target = match reference.as_ref().map(|reference| context.resolve(reference)).transpose() {
+            Ok(target) => target.flatten(),
+            Err(err) => return Ok(ValidateResultItem::new(ValidateStatus::Skip, path, path,
+                format!("无法解析引用[{}]: {}", reference.unwrap_or_default(), err))),
+        };
+
+        // 被引用的资源声明的Profile
+        let (type_name, declared) = match &target {
+            Some(target) => {
+                let declared = self.paths.get_or_compile("meta.profile")?.eval(target.as_ref())?;
+                let declared: Vec<String> = declared.iter().filter_map(|profile| profile.to_strings().ok()).collect();
+                (Some(target.type_name().to_string()), declared)
+            },
+            None => {
+                let type_name = match reference.as_deref().and_then(relative_reference) {
+                    Some((type_name, _)) => Some(type_name.to_string()),
+                    None => item.element(&"type".to_string(), &None)?.to_strings()?,
+                };
+                (type_name, vec![])
+            },
+        };
+
+        let type_name = match type_name {
+            Some(type_name) => type_name,
+            None => return Ok(ValidateResultItem::new(ValidateStatus::Skip, path, path,
+                format!("无法确定引用[{}]的目标类型。", reference.unwrap_or_default()))),
+        };
+
+        let allowed = target_profiles.iter().any(|profile| {
+            if declared.contains(profile) {
+                return true
+            }
+
+            match profile.strip_prefix(CORE_PROFILE) {
+                Some(core) => core == type_name || core == "Resource",
+                None => self.profiles.get(profile).and_then(|definition| definition.type_.as_ref()).and_then(|typ| typ.value.as_deref()) == Some(type_name.as_str()),
+            }
+        });
+
+        if allowed {
+            return Ok(ValidateResultItem::new(ValidateStatus::Success, path, path, format!("引用的目标类型[{}]符合要求。", &type_name)))
+        }
+
+        // 未加载的自定义Profile无法判断目标是否符合，与validate_profile一样跳过
+        let missing: Vec<&str> = target_profiles.iter()
+            .filter(|profile| !profile.starts_with(CORE_PROFILE) && !self.profiles.contains_key(profile.as_str()))
+            .map(String::as_str)
+            .collect();
+        Ok(match missing.is_empty() {
+            true => ValidateResultItem::new(ValidateStatus::Error, path, path,
+                format!("引用的目标类型[{}]不在允许的范围[{}]中。", &type_name, target_profiles.join(", "))),
+            false => ValidateResultItem::new(ValidateStatus::Skip, path, path, format!("缺少Profile[{}]的定义，无法校验。", missing.join(", "))),
+        })
+    }
+
+    /// 按类型中声明的Profile递归校验复杂类型的元素，结果中的路径以当前元素的路径开头
+    fn validate_profile(&self, item: &dyn Executor, url: &String, path: &String) -> Result<Vec<ValidateResultItem>> {
+        let profile = match self.profiles.get(url) {
+            Some(profile) => profile,
+            None => return Ok(vec![ValidateResultItem::new(ValidateStatus::Skip, path, path, format!("缺少Profile[{}]的定义，无法校验。", url))]),
+        };
+        // 只有差异（differential）的Profile需要先生成snapshot
+        if profile.snapshot.as_ref().and_then(|snapshot| snapshot.element.as_ref()).is_none_or(Vec::is_empty) {
+            return Ok(vec![ValidateResultItem::new(ValidateStatus::Skip, path, path, format!("Profile[{}]中没有snapshot，无法校验。", url))])
+        }
+
+        let mut validator = Validator::new(profile.as_ref().clone()).with_path_cache(self.paths.clone());
+        validator.terminology = self.terminology.clone();
+        validator.resolver = self.resolver.clone();
+        validator.profiles = self.profiles.clone();
+
+        let root = validator.root.path.as_ref().and_then(|root| root.value.clone()).unwrap_or_default();
+        let rebase = |text: &String| match text.strip_prefix(&root) {
+            Some(rest) if rest.is_empty() || rest.starts_with(['.', ':']) => format!("{}{}", path, rest),
+            _ => text.clone(),
+        };
+
+        let outcome = validator.validate_executor(item)?;
+        Ok(outcome.items().iter()
+            .map(|item| {
+                let mut item = item.clone();
+                item.path = rebase(&item.path);
+                item.expression = rebase(&item.expression);
+                item
+            })
+            .collect())
     }
 
     fn is_slice_element(&self, element: &ElementDefinition) -> bool {
@@ -486,6 +1044,18 @@ impl Validator {
         }
     }
 
+    fn peek(&self) -> Option<&ElementDefinition> {
+        self.elements.get(self.current.get())
+    }
+
+    /// 跳过切片的所有子元素
+    fn skip_children(&self, root: &String) {
+        let prefix = format!("{}.", root);
+        while self.peek().and_then(|element| element.id.as_ref()).is_some_and(|id| id.starts_with(&prefix)) {
+            self.next();
+        }
+    }
+
     pub fn next(&self) -> Option<&ElementDefinition> {
         let current = self.current.get();
         if current < self.elements.len() {
@@ -497,32 +1067,6 @@ impl Validator {
         }
     }
 
-    fn lookup_filter(&self, path: &String, slice_name: &String) -> Result<(String, AnyType)> {
-        let slicings = self.slicing.borrow();
-        let root = format!("{}:{}", &path, &slice_name);
-
-        match slicings.get(&path.clone()) {
-            Some(slicing) => {
-                match slicing.typ {
-                    SlicingType::TYP => todo!(),
-                    SlicingType::VAL => {
-                        match self.lookup_by_path(&root, &slicing.path) {
-                            Some(element) => {
-                                match &element.pattern {
-                                    Some(pattern) => Ok((slicing.path.clone(), pattern.clone())),
-                                    None => return Err(FhirError::error("在定义中没有找到限定值pattern元素")),
-                                }
-                            },
-                            None => return Err(FhirError::error("找不到SliceName的限定条件")),
-                        }
-                    },
-                    SlicingType::OTH => todo!(),
-                }
-            },
-            None => return Err(FhirError::Message(format!("未找到切片[{}]需要的Slicing信息", &slice_name))),
-        }
-    }
-
     /// 处理Slice时，查找Slice的过滤条件
     /// Encounter.identifier:BID.system
     /// 其中： 
@@ -545,8 +1089,95 @@ impl Validator {
         None
     }
 
+    /// 查找区分器路径对应的元素定义，路径中的类型筛选`ofType()`对应可选类型的元素`[x]`
+    fn lookup_discriminator(&self, root: &String, path: &str) -> Option<&ElementDefinition> {
+        let path = match path.find(".ofType(") {
+            Some(position) => &path[..position],
+            None => path,
+        };
+
+        self.lookup_by_path(root, &path.to_string())
+            .or_else(|| self.lookup_by_path(root, &format!("{}[x]", path)))
+    }
+
     fn push_slicing(&self, path: String, slicing: Slicing) {
         let mut slicings = self.slicing.borrow_mut();
         slicings.insert(path, slicing);
     }
 }
+
+impl Matcher<'_> {
+    fn matches(&self, index: usize, item: &dyn Executor, context: &EvalContext) -> Result<bool> {
+        let (expr, values) = match self {
+            Matcher::Position(ranges) => return Ok(ranges.iter().any(|range| range.contains(&index))),
+            Matcher::Value(expr, ..) | Matcher::Exists(expr, _) | Matcher::Type(expr, _) | Matcher::Profile(expr, ..) => {
+                (expr, expr.eval_with(item, context)?)
+            },
+        };
+        debug!("discriminator {} => {} item(s)", expr.text(), values.count());
+
+        match self {
+            Matcher::Value(_, expected, exact) => {
+                Ok(values.iter().any(|value| pattern::matches(value, expected.as_executor(), *exact)))
+            },
+            Matcher::Exists(_, required) => Ok(values.empty() != *required),
+            Matcher::Type(_, types) => {
+                Ok(values.count() == 1 && types.iter().any(|typ| is_type(&values, typ)))
+            },
+            Matcher::Profile(_, meta_profile, profiles) => {
+                let value = match values.count() {
+                    1 => values.iter().next().unwrap(),
+                    _ => return Ok(false),
+                };
+                let declared = meta_profile.eval(value)?;
+                let declared: Vec<String> = declared.iter().filter_map(|profile| profile.to_strings().ok()).collect();
+
+                Ok(profiles.iter().any(|profile| {
+                    declared.contains(profile) || profile.strip_prefix(CORE_PROFILE).is_some_and(|typ| is_type(&values, typ))
+                }))
+            },
+            Matcher::Position(_) => unreachable!(),
+        }
+    }
+}
+
+const CORE_PROFILE: &str = "http://hl7.org/fhir/StructureDefinition/";
+
+/// 类型代码可以是类型名称或者类型的URL
+fn is_type(values: &Collection, code: &str) -> bool {
+    let code = code.rsplit('/').next().unwrap_or(code);
+    values.iter().all(|value| {
+        matches!(Collection::new_ref(value).is(code).and_then(|resp| resp.to_boolean()), Ok(Some(true)))
+    })
+}
+
+/// 元素在StructureDefinition中使用的类型代码，如`dateTime`、`HumanName`
+fn type_code(item: &dyn Executor) -> String {
+    match item.type_name().strip_suffix("Dt") {
+        Some(name) => {
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(first) => format!("{}{}", first.to_ascii_lowercase(), chars.as_str()),
+                None => String::new(),
+            }
+        },
+        None => item.type_name().to_string(),
+    }
+}
+
+fn canonicals(canonicals: &Option<Vec<CanonicalDt>>) -> Vec<String> {
+    canonicals.iter().flatten().filter_map(|canonical| canonical.value.clone()).collect()
+}
+
+/// 元素定义的最大值，`*`表示不限制
+fn max_of(element: &ElementDefinition) -> Result<Option<usize>> {
+    let max = match element.max.as_ref().and_then(|max| max.value.as_ref()) {
+        Some(max) => max,
+        None => return Ok(None),
+    };
+
+    match max.as_str() {
+        "*" => Ok(None),
+        max => max.parse().map(Some).map_err(|_| FhirError::Message(format!("元素的最大值[{}]不是有效的数字", max))),
+    }
+}
@@ -23,4 +23,18 @@ pub fn validate_profile() -> Result<()> {
     let profile: StructureDefinition = from_xml(profile_str)?;
     println!("{:?}", profile.snapshot.unwrap().element.unwrap().get(0).unwrap().pattern);
     Ok(())
-}
\ No newline at end of file
+}
+#[test]
+pub fn serialize_datetime_timezone() -> Result<()> {
+    let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+
+    let json = to_json(&patient)?;
+    assert!(json.contains(r#""deceasedDateTime":"2019-11-11T12:23:34Z""#));
+    assert!(json.contains(r#""valueDateTime":"1974-12-25T14:35:45-05:00""#));
+    let xml = to_xml(&patient)?;
+    assert!(xml.contains(r#"<deceasedDateTime value="2019-11-11T12:23:34Z" />"#));
+
+    let patient: Patient = from_json(&json)?;
+    assert_eq!(to_json(&patient)?, json);
+    Ok(())
+}
@@ -20,12 +20,12 @@ mod fhirpath {
         let encounter_str = include_str!("encounter_example_02.xml");
         let encounter: Encounter = from_xml(encounter_str)?;
 
-        let mut expr = PathExpression::parse("Encounter.meta".to_string())?;
-        let collection = encounter.path(&mut expr)?;
+        let expr = Expr::parse("Encounter.meta".to_string())?;
+        let collection = expr.eval(&encounter)?;
     
         println!("Result count: {}", &collection.count());
         
-        for item in collection.0 {
+        for item in collection.iter() {
             println!("Item: {:?}", item);
         }
         Ok(())
@@ -36,12 +36,12 @@ mod fhirpath {
         let patient_str = include_str!("patient-example.xml");
         let patient: Patient = from_xml(patient_str)?;
     
-        let mut expr = PathExpression::parse("Patient.gender".to_string())?;
-        let collection = patient.path(&mut expr)?;
+        let expr = Expr::parse("Patient.gender".to_string())?;
+        let collection = expr.eval(&patient)?;
      
         println!("Result count: {}", &collection.count());
         
-        for item in collection.0 {
+        for item in collection.iter() {
             println!("Item: {:?}", item);
         }
         Ok(())
@@ -54,8 +54,8 @@ mod fhirpath {
         let patient_str = include_str!("patient-example.xml");
         let patient: Patient = from_xml(patient_str)?;
     
-        let mut expr = PathExpression::parse("Patient.name.given".to_string())?;
-        let collection = patient.path(&mut expr)?;
+        let expr = Expr::parse("Patient.name.given".to_string())?;
+        let collection = expr.eval(&patient)?;
     
         assert_eq!(collection.count(), 5);
         Ok(())
@@ -66,8 +66,8 @@ mod fhirpath {
         let patient_str = include_str!("patient-example.xml");
         let patient: Patient = from_xml(patient_str)?;
     
-        let mut expr = PathExpression::parse("Patient.name[2].given".to_string())?;
-        let collection = patient.path(&mut expr)?;
+        let expr = Expr::parse("Patient.name[2].given".to_string())?;
+        let collection = expr.eval(&patient)?;
         
         assert_eq!(collection.count(), 2);
         Ok(())
@@ -78,10 +78,1162 @@ mod fhirpath {
         let patient_str = include_str!("patient-example.xml");
         let patient: Patient = from_xml(patient_str)?;
     
-        let mut expr = PathExpression::parse("Patient.name.where(given = 'abc' )".to_string())?;
-        let collection = patient.path(&mut expr)?;
+        let expr = Expr::parse("Patient.name.where(given = 'abc' )".to_string())?;
+        let collection = expr.eval(&patient)?;
         
         assert_eq!(collection.count(), 0);
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn eval_patient(expression: &str) -> Result<Collection<'static>> {
+        let patient_str = include_str!("patient-example.xml");
+        let patient: Patient = from_xml(patient_str)?;
+
+        let expr = Expr::parse(expression.to_string())?;
+        Ok(expr.eval(&patient)?.into_owned())
+    }
+
+    #[test]
+    pub fn test_existence_functions() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name.exists()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.empty()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.photo.empty()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.exists(given = 'Jim')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.exists(given = 'Tom')")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name.all(given.exists())")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.all(family.exists())")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.photo.all(data.exists())")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_boolean_functions() -> Result<()> {
+        assert_eq!(eval_patient("Patient.active.allTrue()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.active.anyTrue()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.active.allFalse()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.active.anyFalse()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.photo.allTrue()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.photo.anyTrue()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.active.not()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.photo.exists().not()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.photo.not()")?.to_boolean()?, None);
+        assert_eq!(eval_patient("Patient.`name`.`given`.exists()")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_count_and_distinct() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name.given.count()")?.to_integer()?, Some(5));
+        assert_eq!(eval_patient("Patient.name.given.distinct().count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("Patient.name.given.isDistinct()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name.family.isDistinct()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.count() = 3")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_subset_and_superset() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name[1].given.subsetOf(Patient.name.given)")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.given.subsetOf(Patient.name[0].given)")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name.given.supersetOf(Patient.name[2].given)")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_select_and_of_type() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name.select(given)")?.count(), 5);
+        assert_eq!(eval_patient("Patient.name.given.ofType(string)")?.count(), 5);
+        assert_eq!(eval_patient("Patient.name.given.ofType(FHIR.string)")?.count(), 5);
+        assert_eq!(eval_patient("Patient.name.ofType(HumanName)")?.count(), 3);
+        assert_eq!(eval_patient("Patient.name.ofType(Address)")?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_repeat() -> Result<()> {
+        fn contains(code: &str, children: Vec<ValueSetExpansionContainsBackboneElement>) -> ValueSetExpansionContainsBackboneElement {
+            ValueSetExpansionContainsBackboneElement {
+                code: Some(CodeDt::new(code)),
+                contains: if children.is_empty() { None } else { Some(children) },
+                ..Default::default()
+            }
+        }
+
+        let value_set = ValueSet::default()
+            .set_expansion(ValueSetExpansionBackboneElement {
+                contains: Some(vec![
+                    contains("a", vec![contains("a1", vec![]), contains("a2", vec![])]),
+                    contains("b", vec![]),
+                ]),
+                ..Default::default()
+            });
+
+        let expr = Expr::parse("ValueSet.expansion.repeat(contains).code".to_string())?;
+        assert_eq!(expr.eval(&value_set)?.count(), 4);
+
+        let expr = Expr::parse("ValueSet.expansion.contains.select(contains).code".to_string())?;
+        assert_eq!(expr.eval(&value_set)?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_function_arity() -> Result<()> {
+        assert!(eval_patient("Patient.name.where()").is_err());
+        assert!(eval_patient("Patient.name.count(given)").is_err());
+        assert!(eval_patient("Patient.name.unknown()").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_ordering_comparison() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name.count() > 2")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.count() >= 3")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.count() < 3")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name.count() <= 2.5")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name[0].family > 'Blacksmith'")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name[0].family < 'Blacksmith'")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.photo.title > 'A'")?.count(), 0);
+        assert!(eval_patient("Patient.name.given > 'A'").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_datetime_comparison() -> Result<()> {
+        assert_eq!(eval_patient("Patient.birthDate < @2000")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate > @1974-12-24")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate >= @1974-12-25")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate > @1974-12")?.count(), 0);
+        assert_eq!(eval_patient("Patient.birthDate > @1973-12")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.deceased > Patient.birthDate")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.deceased <= @2019-11-11T12:23:34Z")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.deceased > @2019-11-11T20:23:33+08:00")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_quantity_comparison() -> Result<()> {
+        fn quantity(value: f64, code: &str) -> Quantity {
+            Quantity {
+                value: Some(DecimalDt::new(value)),
+                code: Some(CodeDt::new(code)),
+                ..Default::default()
+            }
+        }
+
+        let observation = Observation::default()
+            .add_reference_range(ObservationReferenceRangeBackboneElement {
+                low: Some(quantity(3.5, "mmol/L")),
+                high: Some(quantity(5.5, "mmol/L")),
+                ..Default::default()
+            })
+            .add_reference_range(ObservationReferenceRangeBackboneElement {
+                low: Some(quantity(3.5, "mmol/L")),
+                high: Some(quantity(100.0, "mg/dL")),
+                ..Default::default()
+            });
+
+        let expr = Expr::parse("Observation.referenceRange[0].low < Observation.referenceRange[0].high".to_string())?;
+        assert_eq!(expr.eval(&observation)?.to_boolean()?, Some(true));
+
+        let expr = Expr::parse("Observation.referenceRange[0].low >= Observation.referenceRange[1].low".to_string())?;
+        assert_eq!(expr.eval(&observation)?.to_boolean()?, Some(true));
+
+        let expr = Expr::parse("Observation.referenceRange[1].low < Observation.referenceRange[1].high".to_string())?;
+        assert_eq!(expr.eval(&observation)?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_arithmetic() -> Result<()> {
+        assert_eq!(eval_patient("1 + 2 * 3")?.to_integer()?, Some(7));
+        assert_eq!(eval_patient("Patient.name.count() - 5")?.to_integer()?, Some(-2));
+        assert_eq!(eval_patient("1 + 0.5")?.to_decimal()?, Some(1.5));
+        assert_eq!(eval_patient("5 / 2")?.to_decimal()?, Some(2.5));
+        assert_eq!(eval_patient("5 div 2")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("5 mod 2")?.to_integer()?, Some(1));
+        assert_eq!(eval_patient("5.5 mod 2")?.to_decimal()?, Some(1.5));
+        assert_eq!(eval_patient("5 / 0")?.count(), 0);
+        assert_eq!(eval_patient("5 div 0")?.count(), 0);
+        assert_eq!(eval_patient("5 mod 0")?.count(), 0);
+        assert_eq!(eval_patient("Patient.photo.title + 1")?.count(), 0);
+        assert!(eval_patient("'a' * 2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_string_concatenation() -> Result<()> {
+        assert_eq!(eval_patient("'a' + 'b'")?.to_strings()?, Some("ab".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].family + ', ' + Patient.name[0].given[0]")?.to_strings()?, Some("Chalmers, Peter".to_string()));
+        assert_eq!(eval_patient("Patient.photo.title + 'b'")?.count(), 0);
+        assert_eq!(eval_patient("Patient.photo.title & 'b'")?.to_strings()?, Some("b".to_string()));
+        assert_eq!(eval_patient("'a' & 'b'")?.to_strings()?, Some("ab".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_datetime_arithmetic() -> Result<()> {
+        assert_eq!(eval_patient("Patient.birthDate + 1 month = @1975-01-25")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate - 1 year = @1973-12-25")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate + 7 days = @1975-01-01")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2019-11-11 - 18 years > Patient.birthDate")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2019-01-31 + 1 month = @2019-02-28")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2019-11-11T12:00:00Z + 13 hours = @2019-11-12T01:00:00Z")?.to_boolean()?, Some(true));
+        assert!(eval_patient("Patient.birthDate + 1 hour").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_type_operators() -> Result<()> {
+        assert_eq!(eval_patient("Patient.deceased is dateTime")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.deceased is FHIR.boolean")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.deceased.is(dateTime)")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("(Patient.deceased as dateTime) > Patient.birthDate")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.deceased.as(boolean)")?.count(), 0);
+        assert_eq!(eval_patient("Patient.deceased.ofType(boolean)")?.count(), 0);
+        assert_eq!(eval_patient("Patient.deceased.ofType(dateTime)")?.count(), 1);
+        assert_eq!(eval_patient("Patient.photo is Attachment")?.count(), 0);
+        assert_eq!(eval_patient("1 is Integer")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("1.5 is System.Decimal")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2015-02-04 is System.Date")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2015-02 is Date")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2015-02-04 is System.DateTime")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("@2015-02-04T is DateTime")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2015-02-04T14:34:28Z is DateTime")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("(@2015-02-04 + 1 day) is Date")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("(@2015-02-04 + 1 day).toString()")?.to_strings()?, Some("2015-02-05".to_string()));
+        assert_eq!(analyze(&Analyzer::for_type("Patient")?, "@2015-02-04")?.result.to_string(), "System.Date[1..1]");
+        assert_eq!(eval_patient("'a' is Integer")?.to_boolean()?, Some(false));
+        assert!(eval_patient("Patient.name is HumanName").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_choice_navigation() -> Result<()> {
+        assert_eq!(eval_patient("Patient.deceasedDateTime")?.count(), 1);
+        assert_eq!(eval_patient("Patient.deceasedBoolean")?.count(), 0);
+        assert!(eval_patient("Patient.deceasedQuantity").is_err());
+
+        let observation = Observation::default()
+            .set_value(AnyType::Quantity(Quantity {
+                value: Some(DecimalDt::new(42.0)),
+                code: Some(CodeDt::new("a")),
+                ..Default::default()
+            }));
+
+        let expr = Expr::parse("Observation.value is Quantity".to_string())?;
+        assert_eq!(expr.eval(&observation)?.to_boolean()?, Some(true));
+
+        let expr = Expr::parse("(Observation.value as Quantity).value".to_string())?;
+        assert_eq!(expr.eval(&observation)?.to_decimal()?, Some(42.0));
+
+        let expr = Expr::parse("Observation.valueQuantity.value > 18".to_string())?;
+        assert_eq!(expr.eval(&observation)?.to_boolean()?, Some(true));
+
+        let expr = Expr::parse("Observation.valueString".to_string())?;
+        assert_eq!(expr.eval(&observation)?.count(), 0);
+
+        let expr = Expr::parse("Observation.valueAge".to_string())?;
+        assert!(expr.eval(&observation).is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_string_functions() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name[0].family.startsWith('Cha')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name[0].family.endsWith('Cha')")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name[0].family.contains('alm')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name[0].family.indexOf('m')")?.to_integer()?, Some(4));
+        assert_eq!(eval_patient("Patient.name[0].family.indexOf('x')")?.to_integer()?, Some(-1));
+        assert_eq!(eval_patient("Patient.name[0].family.substring(3)")?.to_strings()?, Some("lmers".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].family.substring(1, 2)")?.to_strings()?, Some("ha".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].family.substring(20)")?.count(), 0);
+        assert_eq!(eval_patient("Patient.name[0].family.upper()")?.to_strings()?, Some("CHALMERS".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].family.lower()")?.to_strings()?, Some("chalmers".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].family.length()")?.to_integer()?, Some(8));
+        assert_eq!(eval_patient("Patient.name[0].family.toChars().count()")?.to_integer()?, Some(8));
+        assert_eq!(eval_patient("' abc '.trim()")?.to_strings()?, Some("abc".to_string()));
+        assert_eq!(eval_patient("Patient.photo.title.upper()")?.count(), 0);
+        assert_eq!(eval_patient("Patient.name[0].family.startsWith(Patient.photo.title)")?.count(), 0);
+        assert!(eval_patient("Patient.name.family.upper()").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_string_replace_and_regex() -> Result<()> {
+        assert_eq!(eval_patient("'abcabc'.replace('bc', 'x')")?.to_strings()?, Some("axax".to_string()));
+        assert_eq!(eval_patient("'abc'.replace('', 'x')")?.to_strings()?, Some("xaxbxcx".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].family.matches('^Ch[a-z]+$')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name[0].family.matches('[0-9]')")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient(r"'2019-11-11'.replaceMatches('(\\d+)-(\\d+)-(\\d+)', '$3/$2/$1')")?.to_strings()?, Some("11/11/2019".to_string()));
+        assert_eq!(eval_patient(r"'123'.matches('\\d+')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient(r"'a\'b'.length()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient(r"'a\tb\u00e9' = 'a' & '\t' & 'bé'")?.to_boolean()?, Some(true));
+        assert!(eval_patient(r"'123'.matches('\d+')").is_err());
+        assert!(eval_patient("'abc'.matches('[')").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_string_split_and_join() -> Result<()> {
+        assert_eq!(eval_patient("'a,b,c'.split(',').count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("Patient.name[0].given.join(' ')")?.to_strings()?, Some("Peter James".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].given.join()")?.to_strings()?, Some("PeterJames".to_string()));
+        assert_eq!(eval_patient("'a,b,c'.split(',').join('|')")?.to_strings()?, Some("a|b|c".to_string()));
+        assert_eq!(eval_patient("Patient.photo.title.join(',')")?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_string_encoding() -> Result<()> {
+        assert_eq!(eval_patient("'hello'.encode('base64')")?.to_strings()?, Some("aGVsbG8=".to_string()));
+        assert_eq!(eval_patient("'aGVsbG8='.decode('base64')")?.to_strings()?, Some("hello".to_string()));
+        assert_eq!(eval_patient("'??>'.encode('urlbase64')")?.to_strings()?, Some("Pz8-".to_string()));
+        assert_eq!(eval_patient("'Pz8-'.decode('urlbase64')")?.to_strings()?, Some("??>".to_string()));
+        assert_eq!(eval_patient("'hello'.encode('hex')")?.to_strings()?, Some("68656c6c6f".to_string()));
+        assert_eq!(eval_patient("'68656c6c6f'.decode('hex')")?.to_strings()?, Some("hello".to_string()));
+        assert!(eval_patient("'hello'.encode('rot13')").is_err());
+        assert!(eval_patient("'xyz'.decode('hex')").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_environment_variables() -> Result<()> {
+        assert_eq!(eval_patient("%resource.name.count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("%context.birthDate = Patient.birthDate")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("%rootResource.id = Patient.id")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("%ucum")?.to_strings()?, Some("http://unitsofmeasure.org".to_string()));
+        assert_eq!(eval_patient("%sct")?.to_strings()?, Some("http://snomed.info/sct".to_string()));
+        assert_eq!(eval_patient("%vs-administrative-gender")?.to_strings()?, Some("http://hl7.org/fhir/ValueSet/administrative-gender".to_string()));
+        assert_eq!(eval_patient("%`ext-patient-birthTime`")?.to_strings()?, Some("http://hl7.org/fhir/StructureDefinition/patient-birthTime".to_string()));
+        assert!(eval_patient("%unknown").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_user_variables() -> Result<()> {
+        let patient_str = include_str!("patient-example.xml");
+        let patient: Patient = from_xml(patient_str)?;
+
+        let context = EvalContext::new(&patient)
+            .with_variable("family", Collection::new_string("Chalmers".to_string()))
+            .with_variable("limit", Collection::new_integer(2));
+
+        let expr = Expr::parse("Patient.name.where(family = %family).count()".to_string())?;
+        assert_eq!(expr.eval_with(&patient, &context)?.to_integer()?, Some(1));
+
+        let expr = Expr::parse("Patient.name.where($index < %limit).select(%family).count()".to_string())?;
+        assert_eq!(expr.eval_with(&patient, &context)?.to_integer()?, Some(2));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_this_and_index() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name[0].given.where($this = 'James')")?.to_strings()?, Some("James".to_string()));
+        assert_eq!(eval_patient("Patient.name[0].given.where($index = 1)")?.to_strings()?, Some("James".to_string()));
+        assert_eq!(eval_patient("Patient.name.select($index).count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("Patient.name.where($index > 0).exists($this.use = 'maiden')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name[0].given.all($this.length() > $index)")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("$this.name.count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("$index")?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_aggregate() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name[0].given.aggregate($total + $this.length(), 0)")?.to_integer()?, Some(10));
+        assert_eq!(eval_patient("Patient.name[0].given.aggregate($total & $this)")?.to_strings()?, Some("PeterJames".to_string()));
+        assert_eq!(eval_patient("Patient.name.aggregate($total + $index, 0)")?.to_integer()?, Some(3));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_union() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name.given | Patient.name.family")?.count(), 5);
+        assert_eq!(eval_patient("(Patient.name.given | Patient.name.given).count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("Patient.photo | Patient.name[0].given")?.count(), 2);
+        assert_eq!(eval_patient("1 | 2 | 1 | 3")?.count(), 3);
+        assert_eq!(eval_patient("(1 | 2) = (2 | 1)")?.to_boolean()?, Some(false));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_membership() -> Result<()> {
+        assert_eq!(eval_patient("'Jim' in Patient.name.given")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("'Tom' in Patient.name.given")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name.given contains 'James'")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.photo contains 'James'")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.photo.title in Patient.name.given")?.count(), 0);
+        assert_eq!(eval_patient("Patient.gender in ('male' | 'female')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.where('Jim' in given).use")?.to_strings()?, Some("usual".to_string()));
+        assert!(eval_patient("Patient.name.given in 'Jim'").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_implies() -> Result<()> {
+        assert_eq!(eval_patient("Patient.active implies Patient.name.exists()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name.exists() implies Patient.photo.exists()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.photo.exists() implies Patient.name.empty()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.photo.title implies true")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.photo.title implies false")?.count(), 0);
+        assert_eq!(eval_patient("true implies Patient.photo.title")?.count(), 0);
+        assert_eq!(eval_patient("Patient.name.exists() and Patient.active implies Patient.gender = 'male'")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_extension_function() -> Result<()> {
+        assert_eq!(eval_patient("Patient.birthDate.extension('http://hl7.org/fhir/StructureDefinition/patient-birthTime').count()")?.to_integer()?, Some(1));
+        assert_eq!(eval_patient("Patient.birthDate.extension(%`ext-patient-birthTime`).value > @1974-12-25T12:00:00Z")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate.extension(%`ext-patient-birthTime`).valueDateTime.exists()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate.extension(%`ext-patient-birthTime`).valueString.exists()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.contact.name.family.extension('http://hl7.org/fhir/StructureDefinition/humanname-own-prefix').value")?.to_strings()?, Some("VV".to_string()));
+        assert_eq!(eval_patient("Patient.birthDate.extension('http://example.org/unknown').exists()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name.given.extension('http://example.org/unknown').exists()")?.to_boolean()?, Some(false));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_has_value_and_get_value() -> Result<()> {
+        assert_eq!(eval_patient("Patient.birthDate.hasValue()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate.getValue() = @1974-12-25")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.name[0].hasValue()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.name.given.hasValue()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("'abc'.hasValue()")?.to_boolean()?, Some(false));
+
+        let patient = Patient::default()
+            .set_birth_date(DateDt { id: None, extension: None, value: None }
+                .add_extension(Extension::new("http://hl7.org/fhir/StructureDefinition/data-absent-reason", AnyType::Code(CodeDt::new("unknown")))));
+        let expr = Expr::parse("Patient.birthDate.hasValue()".to_string())?;
+        assert_eq!(expr.eval(&patient)?.to_boolean()?, Some(false));
+
+        let expr = Expr::parse("Patient.birthDate.getValue()".to_string())?;
+        assert_eq!(expr.eval(&patient)?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_children_and_descendants() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name[1].children().count()")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("Patient.birthDate.children().count()")?.to_integer()?, Some(1));
+        assert_eq!(eval_patient("Patient.birthDate.descendants().count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("Patient.contact.descendants().ofType(Coding).code")?.to_strings()?, Some("N".to_string()));
+        assert_eq!(eval_patient("Patient.descendants().ofType(HumanName).count()")?.to_integer()?, Some(4));
+        assert_eq!(eval_patient("Patient.name.all(hasValue() or (children().count() > id.count()))")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_conversion_functions() -> Result<()> {
+        assert_eq!(eval_patient("'yes'.toBoolean()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("'F'.toBoolean()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("'maybe'.toBoolean()")?.count(), 0);
+        assert_eq!(eval_patient("1.0.toBoolean()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("'+42'.toInteger() + 1")?.to_integer()?, Some(43));
+        assert_eq!(eval_patient("'4.2'.convertsToInteger()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("true.toInteger()")?.to_integer()?, Some(1));
+        assert_eq!(eval_patient("'-1.5'.toDecimal()")?.to_decimal()?, Some(-1.5));
+        assert_eq!(eval_patient("'1e5'.convertsToDecimal()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("Patient.active.toString()")?.to_strings()?, Some("true".to_string()));
+        assert_eq!(eval_patient("Patient.name.given.count().toString() = '5'")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate.toString()")?.to_strings()?, Some("1974-12-25".to_string()));
+        assert!(eval_patient("Patient.name.toString()").is_err());
+        assert_eq!(eval_patient("Patient.photo.toString()")?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_temporal_and_quantity_conversion() -> Result<()> {
+        assert_eq!(eval_patient("'2012-04-15'.toDate() = Patient.birthDate")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("'1974-12-25'.toDate() = Patient.birthDate")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@1974-12-25T14:35:45+02:00.toDate() = Patient.birthDate")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("'1974-12-25T14:35:45Z'.convertsToDateTime()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.birthDate.toDateTime() < @1975-01-01T00:00:00Z")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("'abc'.convertsToDate()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("'14:35:45'.toTime().toString()")?.to_strings()?, Some("14:35:45".to_string()));
+        assert_eq!(eval_patient("'25:00:00'.convertsToTime()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("'3 days'.toQuantity() >= 3 days and '3 days'.toQuantity() <= 3 days")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("'3 parsecs'.convertsToQuantity()")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("5.toQuantity('1').toString()")?.to_strings()?, Some("5 '1'".to_string()));
+        assert_eq!(eval_patient("5.convertsToQuantity('mg')")?.to_boolean()?, Some(false));
+        assert!(eval_patient("Patient.name.given.toQuantity()").is_err());
+
+        let patient = Patient::default();
+        let context = EvalContext::new(&patient)
+            .with_variable("dose", Collection::new_string("4.5 'mg'".to_string()));
+        let expr = Expr::parse("%dose.toQuantity().toString()".to_string())?;
+        assert_eq!(expr.eval_with(&patient, &context)?.to_strings()?, Some("4.5 'mg'".to_string()));
+        let expr = Expr::parse("%dose.toQuantity('g').toString()".to_string())?;
+        assert_eq!(expr.eval_with(&patient, &context)?.to_strings()?, Some("0.0045 'g'".to_string()));
+        let expr = Expr::parse("%dose.toQuantity('mL').empty()".to_string())?;
+        assert_eq!(expr.eval_with(&patient, &context)?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_partial_datetime_literals() -> Result<()> {
+        for literal in ["2015", "2015-02", "2015-02-04T14", "2015-02-04T14:34+08:00", "2015-02-04T14:34:28.123Z", "2015-02-04T14:34:28+00:00"] {
+            let value = eval_patient(&format!("@{}.toString()", literal))?.to_strings()?;
+            assert_eq!(value, Some(literal.to_string()));
+        }
+        assert_eq!(eval_patient("@T14:34:28.123.toString()")?.to_strings()?, Some("14:34:28.123".to_string()));
+        assert_eq!(eval_patient("@T14 is Time")?.to_boolean()?, Some(true));
+
+        assert_eq!(eval_patient("@2015 = @2015")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2015 = @2015-02")?.count(), 0);
+        assert_eq!(eval_patient("@2015 = @2016-02")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("@2015 != @2015-02")?.count(), 0);
+        assert_eq!(eval_patient("@2015-02-04T14 = @2015-02-04T14:00")?.count(), 0);
+        assert_eq!(eval_patient("@2015-02-04T14:34:28 = @2015-02-04T14:34:28.000")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2015-02-04T14:00+08:00 = @2015-02-04T06:00Z")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@2015-02-04T14:00 < @2015-02-04T15")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("@T14:34 = @T14:34:00")?.count(), 0);
+        assert_eq!(eval_patient("@T14:34 < @T15")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("(@T14:34 + 30 minutes).toString()")?.to_strings()?, Some("15:04".to_string()));
+        assert_eq!(eval_patient("(@2015-02-04T14 + 1 day).toString()")?.to_strings()?, Some("2015-02-05T14".to_string()));
+        assert_eq!(eval_patient("Patient.birthDate = @1974-12")?.count(), 0);
+
+        let err = path_error(Expr::parse("@2015-02-04T14:3 = @2015".to_string()));
+        assert_eq!(err.span, 0..16);
+        assert_eq!(path_error(Expr::parse("@T25:00 = @T14".to_string())).span, 0..7);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_ucum_quantity() -> Result<()> {
+        let observation = Observation::default()
+            .set_value(AnyType::Quantity(Quantity {
+                value: Some(DecimalDt::new(1.5)),
+                unit: Some(StringDt::new("g/L")),
+                system: Some(UriDt::new("http://unitsofmeasure.org")),
+                code: Some(CodeDt::new("g/L")),
+                ..Default::default()
+            }));
+
+        assert_eq!(eval(&observation, "Observation.value > 100 'mg/dL'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "Observation.value = 150 'mg/dL'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "Observation.value < 2 'kg/L'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "Observation.value > 100 'mmol/L'")?.count(), 0);
+
+        assert_eq!(eval(&observation, "1 'kg' = 1000 'g'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "0.1 'g' = 100 'mg'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "1 'kg' != 1 'g'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "3 days = 72 hours")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "1 week = 1 'wk'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "1 year = 12 months")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "1 year = 1 'a'")?.count(), 0);
+        assert_eq!(eval(&observation, "2 'L' > 1500 'mL'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "1 '[in_i]' = 2.54 'cm'")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "5 'mg' = 5 'mL'")?.count(), 0);
+
+        assert_eq!(eval(&observation, "(5 'mg' + 1 'g').toString()")?.to_strings()?, Some("1005 'mg'".to_string()));
+        assert_eq!(eval(&observation, "(2 'h' - 30 'min').toString()")?.to_strings()?, Some("1.5 'h'".to_string()));
+        assert_eq!(eval(&observation, "(5 'mg' + 1 'mL').empty()")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&observation, "(5 'mg' * 2).value")?.to_decimal()?, Some(10.0));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_utility_functions() -> Result<()> {
+        assert_eq!(eval_patient("iif(Patient.active, 'active', 'inactive')")?.to_strings()?, Some("active".to_string()));
+        assert_eq!(eval_patient("iif(Patient.photo.exists(), 'photo')")?.count(), 0);
+        assert_eq!(eval_patient("iif(Patient.photo.exists(), Patient.photo.title, 'none')")?.to_strings()?, Some("none".to_string()));
+        assert_eq!(eval_patient("Patient.name.where(use = 'official').iif(given.count() > 1, family, 'single')")?.to_strings()?, Some("Chalmers".to_string()));
+        assert!(eval_patient("Patient.name.iif(true, 1)").is_err());
+        assert_eq!(eval_patient("iif(Patient.active, {}, 'inactive')")?.count(), 0);
+        assert_eq!(eval_patient("iif(Patient.photo.exists(), 'photo', { })")?.count(), 0);
+        assert_eq!(eval_patient("iif(Patient.active, Patient.name, {})")?.count(), 3);
+        assert_eq!(eval_patient("{} and false")?.to_boolean()?, Some(false));
+        assert_eq!(eval_patient("({} | 1).count()")?.to_integer()?, Some(1));
+        assert_eq!(eval_patient("{}.empty()")?.to_boolean()?, Some(true));
+        assert!(eval_patient("{1}").is_err());
+        assert_eq!(eval_patient("Patient.name.trace('names').given.count()")?.to_integer()?, Some(5));
+        assert_eq!(eval_patient("Patient.name.trace('names', family).count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("now() > @2020-01-01T00:00:00Z")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("today() <= now().toDate()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("timeOfDay().convertsToTime()")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_math_functions() -> Result<()> {
+        assert_eq!(eval_patient("(-5).abs()")?.to_integer()?, Some(5));
+        assert_eq!(eval_patient("(-5.5).abs()")?.to_decimal()?, Some(5.5));
+        assert_eq!(eval_patient("(-5.5 'mg').abs().toString()")?.to_strings()?, Some("5.5 'mg'".to_string()));
+        assert_eq!(eval_patient("1.1.ceiling()")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("(-1.1).floor()")?.to_integer()?, Some(-2));
+        assert_eq!(eval_patient("(-1.9).truncate()")?.to_integer()?, Some(-1));
+        assert_eq!(eval_patient("0.exp()")?.to_decimal()?, Some(1.0));
+        assert_eq!(eval_patient("1.ln()")?.to_decimal()?, Some(0.0));
+        assert_eq!(eval_patient("100.log(10)")?.to_decimal()?, Some(2.0));
+        assert_eq!(eval_patient("2.power(3)")?.to_integer()?, Some(8));
+        assert_eq!(eval_patient("2.power(3) is Integer")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("4.power(0.5)")?.to_decimal()?, Some(2.0));
+        assert_eq!(eval_patient("(-1).power(0.5)")?.count(), 0);
+        assert_eq!(eval_patient("1.23456.round(3)")?.to_decimal()?, Some(1.235));
+        assert_eq!(eval_patient("2.5.round()")?.to_decimal()?, Some(3.0));
+        assert_eq!(eval_patient("81.sqrt()")?.to_decimal()?, Some(9.0));
+        assert_eq!(eval_patient("(-1).sqrt()")?.count(), 0);
+        assert_eq!(eval_patient("Patient.photo.abs()")?.count(), 0);
+        assert!(eval_patient("'a'.abs()").is_err());
+        assert!(eval_patient("(1 | 2).sqrt()").is_err());
+        assert!(eval_patient("1.5.round(-1)").is_err());
+
+        // 正负号的优先级低于点号、高于乘除
+        assert_eq!(eval_patient("-5.abs()")?.to_integer()?, Some(-5));
+        assert_eq!(eval_patient("10.power(-1)")?.to_decimal()?, Some(0.1));
+        assert_eq!(eval_patient("-1 + 3")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("2 * -3 - -4")?.to_integer()?, Some(-2));
+        assert_eq!(eval_patient("+2.5 = 2.5 and -(1 + 2) = -3")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("-Patient.photo")?.count(), 0);
+        assert!(eval_patient("-'a'").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_boundary_functions() -> Result<()> {
+        assert_eq!(eval_patient("1.58700.precision()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("@2014-01-05T10:30.precision()")?.to_integer()?, Some(12));
+        assert_eq!(eval_patient("@T10:30.precision()")?.to_integer()?, Some(4));
+        assert_eq!(eval_patient("Patient.birthDate.precision()")?.to_integer()?, Some(8));
+
+        assert_eq!(eval_patient("1.587.lowBoundary()")?.to_decimal()?, Some(1.5865));
+        assert_eq!(eval_patient("1.587.highBoundary()")?.to_decimal()?, Some(1.5875));
+        assert_eq!(eval_patient("1.587.lowBoundary(2)")?.to_decimal()?, Some(1.58));
+        assert_eq!(eval_patient("1.587.highBoundary(2)")?.to_decimal()?, Some(1.59));
+        assert_eq!(eval_patient("(-1.587).highBoundary()")?.to_decimal()?, Some(-1.5865));
+        assert_eq!(eval_patient("1.lowBoundary()")?.to_decimal()?, Some(0.5));
+        assert_eq!(eval_patient("1.587.lowBoundary(30)")?.count(), 0);
+        assert_eq!(eval_patient("1.5 'mg'.highBoundary().toString()")?.to_strings()?, Some("1.55 'mg'".to_string()));
+
+        assert_eq!(eval_patient("@2014.highBoundary().toString()")?.to_strings()?, Some("2014-12-31".to_string()));
+        assert_eq!(eval_patient("@2014T.highBoundary().toString()")?.to_strings()?, Some("2014-12-31T23:59:59.999".to_string()));
+        assert_eq!(eval_patient("@2014-02.highBoundary(8).toString()")?.to_strings()?, Some("2014-02-28".to_string()));
+        assert_eq!(eval_patient("@2014-02.lowBoundary(8).toString()")?.to_strings()?, Some("2014-02-01".to_string()));
+        assert_eq!(eval_patient("@2014-01-01T08+08:00.highBoundary().toString()")?.to_strings()?, Some("2014-01-01T08:59:59.999+08:00".to_string()));
+        assert_eq!(eval_patient("Patient.birthDate.lowBoundary(6).toString()")?.to_strings()?, Some("1974-12".to_string()));
+        assert_eq!(eval_patient("@T10:30.highBoundary().toString()")?.to_strings()?, Some("10:30:59.999".to_string()));
+        assert_eq!(eval_patient("@T10:30.lowBoundary().toString()")?.to_strings()?, Some("10:30:00.000".to_string()));
+        assert_eq!(eval_patient("@2014.highBoundary(5)")?.count(), 0);
+
+        assert_eq!(eval_patient("1 'cm'.comparable(1 '[in_i]')")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("1 'cm'.comparable(1 's')")?.to_boolean()?, Some(false));
+        assert!(eval_patient("1.comparable(1 's')").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_collection_functions() -> Result<()> {
+        assert_eq!(eval_patient("Patient.name.first().family")?.to_strings()?, Some("Chalmers".to_string()));
+        assert_eq!(eval_patient("Patient.name.last().family")?.to_strings()?, Some("Windsor".to_string()));
+        assert_eq!(eval_patient("Patient.name.tail().count()")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("Patient.name.given.skip(2).first()")?.to_strings()?, Some("Jim".to_string()));
+        assert_eq!(eval_patient("Patient.name.given.skip(0).count()")?.to_integer()?, Some(5));
+        assert_eq!(eval_patient("Patient.name.given.take(2).count()")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("Patient.name.given.take(0).empty()")?.to_boolean()?, Some(true));
+        assert_eq!(eval_patient("Patient.photo.first().empty()")?.to_boolean()?, Some(true));
+
+        assert_eq!(eval_patient("Patient.name[0].given.intersect(Patient.name.given).count()")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("Patient.name.given.intersect(Patient.name[1].given)")?.to_strings()?, Some("Jim".to_string()));
+        assert_eq!(eval_patient("Patient.name.given.exclude('Peter').count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("(1 | 2).union(2 | 3).count()")?.to_integer()?, Some(3));
+        assert_eq!(eval_patient("(1 | 2).combine(2 | 3).count()")?.to_integer()?, Some(4));
+        assert_eq!(eval_patient("Patient.name.given.union(Patient.name.family).count()")?.to_integer()?, Some(5));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_indexer() -> Result<()> {
+        // 索引作用于整个集合，而不是每个父元素（name）分别取子元素
+        for index in 0..3 {
+            let bare = eval_patient(&format!("Patient.name.given[{}]", index))?;
+            let grouped = eval_patient(&format!("(Patient.name.given)[{}]", index))?;
+            assert_eq!(bare.count(), 1);
+            assert_eq!(bare.to_strings()?, grouped.to_strings()?);
+        }
+        assert_eq!(eval_patient("Patient.name.given[0]")?.to_strings()?, Some("Peter".to_string()));
+        assert_eq!(eval_patient("Patient.name.given[2]")?.to_strings()?, Some("Jim".to_string()));
+        assert_eq!(eval_patient("(Patient.name.given)[2]")?.to_strings()?, Some("Jim".to_string()));
+        assert_eq!(eval_patient("Patient.name.given[1 + 1]")?.to_strings()?, Some("Jim".to_string()));
+        assert_eq!(eval_patient("Patient.name.where(use = 'maiden')[0].family")?.to_strings()?, Some("Windsor".to_string()));
+        assert_eq!(eval_patient("(1 | 2 | 3)[1]")?.to_integer()?, Some(2));
+        assert_eq!(eval_patient("(1 | 2 | 3)[5]")?.count(), 0);
+        assert_eq!(eval_patient("(1 | 2 | 3)[Patient.photo.count()]")?.to_integer()?, Some(1));
+        assert!(eval_patient("(1 | 2)[-1]").is_err());
+        assert!(Expr::parse("(1 | 2)[0".to_string()).is_err());
+
+        let analyzer = Analyzer::for_type("Patient")?;
+        assert_eq!(analyze(&analyzer, "(Patient.name.given)[2]")?.result.to_string(), "string[0..1]");
+        assert_eq!(analyze(&analyzer, "Patient.name.where(use = 'maiden')[0].family")?.result.to_string(), "string[0..1]");
+        assert_eq!(analyze(&analyzer, "Patient.name.given.first()")?.result.to_string(), "string[0..1]");
+        assert_eq!(analyze(&analyzer, "Patient.name.given.combine(Patient.name.family)")?.result.to_string(), "string[0..*]");
+        assert_eq!(analyze(&analyzer, "1.5.round()")?.result.to_string(), "System.Decimal[0..1]");
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_compiled_path() -> Result<()> {
+        let patient_str = include_str!("patient-example.xml");
+        let patient: Patient = from_xml(patient_str)?;
+
+        let path = CompiledPath::compile("Patient.name.given.count()")?;
+        assert_eq!(path.text(), "Patient.name.given.count()");
+        assert_eq!(path.eval(&patient)?.to_integer()?, Some(5));
+        assert_eq!(path.eval(&Patient::default())?.to_integer()?, Some(0));
+
+        let cache = std::sync::Arc::new(PathCache::default());
+        let handles: Vec<_> = (0..4).map(|_| {
+            let cache = cache.clone();
+            std::thread::spawn(move || -> Result<Option<String>> {
+                let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+                cache.get_or_compile("Patient.name.where(use = 'official').family")?.eval(&patient)?.to_strings()
+            })
+        }).collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap()?, Some("Chalmers".to_string()));
+        }
+        assert_eq!(cache.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_borrowed_collection() -> Result<()> {
+        let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+        let official = &patient.name.as_ref().unwrap()[0];
+
+        let collection = Expr::parse("Patient.name.where(use = 'official')".to_string())?.eval(&patient)?;
+        match collection.first() {
+            Some(Item::Borrowed(item)) => assert!(std::ptr::addr_eq(*item, official)),
+            other => panic!("期望引用资源中的元素：{:?}", other),
+        }
+
+        let collection = Expr::parse("Patient.name.count()".to_string())?.eval(&patient)?;
+        assert!(matches!(collection.first(), Some(Item::Owned(_))));
+
+        let owned = Expr::parse("Patient.name[0]".to_string())?.eval(&patient)?.into_owned();
+        assert!(matches!(owned.first(), Some(Item::Owned(_))));
+        assert_eq!(owned.element(&"family".to_string(), &None)?.to_strings()?, Some("Chalmers".to_string()));
+        Ok(())
+    }
+
+    fn path_error(result: Result<impl std::fmt::Debug>) -> PathError {
+        match result {
+            Err(FhirError::FhirPath(err)) => err,
+            other => panic!("期望FHIRPath错误：{:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn test_error_diagnostic() -> Result<()> {
+        let err = path_error(Expr::parse("Patient.name.where(given = )".to_string()));
+        assert_eq!(err.span, 27..28);
+        assert_eq!(err.diagnostic(), Some("Patient.name.where(given = )\n                           ^".to_string()));
+
+        let err = path_error(Expr::parse("Patient.name.where(".to_string()));
+        assert_eq!(err.span, 19..20);
+
+        let err = path_error(Expr::parse("Patient.name # 1".to_string()));
+        assert_eq!(err.span, 13..14);
+
+        let err = path_error(Expr::parse("Patient.name given".to_string()));
+        assert_eq!(err.span, 13..18);
+
+        let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+        let path = CompiledPath::compile("Patient.name.where(given.unknown() = 'Jim')")?;
+        let err = path_error(path.eval(&patient));
+        assert_eq!(err.span, 25..32);
+        assert_eq!(err.diagnostic(), Some("Patient.name.where(given.unknown() = 'Jim')\n                         ^^^^^^^".to_string()));
+
+        let err = path_error(CompiledPath::compile("Patient.name.given > 'A'")?.eval(&patient));
+        assert_eq!(err.span, 19..20);
+        Ok(())
+    }
+
+    fn analyze(analyzer: &Analyzer, expression: &str) -> Result<Analysis> {
+        Ok(analyzer.analyze_path(&CompiledPath::compile(expression)?))
+    }
+
+    #[test]
+    pub fn test_static_analysis() -> Result<()> {
+        let analyzer = Analyzer::for_type("Patient")?;
+
+        let analysis = analyze(&analyzer, "Patient.name.given")?;
+        assert!(analysis.is_valid());
+        assert_eq!(analysis.result.to_string(), "string[0..*]");
+
+        assert_eq!(analyze(&analyzer, "Patient.birthDate")?.result.to_string(), "date[0..1]");
+        assert_eq!(analyze(&analyzer, "Patient.deceased")?.result.to_string(), "boolean | dateTime[0..1]");
+        assert_eq!(analyze(&analyzer, "Patient.deceasedDateTime")?.result.to_string(), "dateTime[0..1]");
+        assert_eq!(analyze(&analyzer, "Patient.name.count()")?.result.to_string(), "System.Integer[1..1]");
+        assert_eq!(analyze(&analyzer, "Patient.name.where(use = 'official').family")?.result.to_string(), "string[0..*]");
+        assert_eq!(analyze(&analyzer, "Patient.contact.name.family")?.result.to_string(), "string[0..*]");
+        assert_eq!(analyze(&analyzer, "Patient.name.given.exists()")?.result.to_string(), "System.Boolean[1..1]");
+        assert_eq!(analyze(&analyzer, "name.select(given | family)")?.result.to_string(), "string[0..*]");
+
+        let observation = Analyzer::for_type("Observation")?;
+        let analysis = analyze(&observation, "Observation.value.ofType(Quantity).unit")?;
+        assert!(analysis.is_valid());
+        assert_eq!(analysis.result.to_string(), "string[0..1]");
+        assert_eq!(analyze(&observation, "Observation.value.unit")?.result.to_string(), "string[0..1]");
+
+        let analysis = analyze(&analyzer, "Patient.nmae.given")?;
+        assert_eq!(analysis.errors.len(), 1);
+        assert_eq!(analysis.errors[0].span, 8..12);
+        assert_eq!(analysis.errors[0].diagnostic(), Some("Patient.nmae.given\n        ^^^^".to_string()));
+        assert!(analysis.result.is_unknown());
+
+        let analysis = analyze(&analyzer, "Patient.name.where(given.exists(1, 2)) and Patient.name.substring()")?;
+        let spans: Vec<_> = analysis.errors.iter().map(|err| err.span.clone()).collect();
+        assert_eq!(spans, vec![25..31, 56..65]);
+
+        let analysis = analyze(&analyzer, "Patient.name.ofType(Human) | Patient.unknown()")?;
+        let spans: Vec<_> = analysis.errors.iter().map(|err| err.span.clone()).collect();
+        assert_eq!(spans, vec![20..25, 37..44]);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_static_analysis_with_profile() -> Result<()> {
+        let profile: StructureDefinition = from_xml(include_str!("profile-core-outpatient-encounter.xml"))?;
+        let analyzer = Analyzer::for_profile(&profile)?;
+
+        assert_eq!(analyze(&analyzer, "Encounter.class")?.result.to_string(), "CodeableConcept[1..1]");
+        assert_eq!(analyze(&analyzer, "Encounter.identifier.value")?.result.to_string(), "string[0..*]");
+        assert_eq!(analyze(&Analyzer::for_type("Encounter")?, "Encounter.class")?.result.to_string(), "CodeableConcept[0..*]");
+
+        let analysis = analyze(&analyzer, "Encounter.diagnosis.use")?;
+        assert_eq!(analysis.errors.len(), 1);
+        assert_eq!(analysis.errors[0].span, 20..23);
+        Ok(())
+    }
+
+    fn eval<'a>(executor: &'a dyn Executor, expression: &str) -> Result<Collection<'a>> {
+        Expr::parse(expression.to_string())?.eval(executor)
+    }
+
+    fn reference(reference: &str) -> Reference {
+        Reference::default().set_reference(StringDt::new(reference))
+    }
+
+    #[test]
+    pub fn test_resolve() -> Result<()> {
+        let practitioner = Practitioner {
+            id: Some("pr1".to_string()),
+            name: Some(vec![HumanName::default().set_family(StringDt::new("Careful"))]),
+            ..Default::default()
+        };
+        let patient = Patient {
+            id: Some("p1".to_string()),
+            contained: Some(vec![AnyResource::Practitioner(practitioner)]),
+            general_practitioner: Some(vec![reference("#pr1"), reference("Organization/o1")]),
+            managing_organization: Some(reference("#")),
+            ..Default::default()
+        };
+
+        let resolved = eval(&patient, "Patient.generalPractitioner.resolve()")?;
+        assert_eq!(resolved.count(), 1);
+        assert_eq!(eval(&patient, "Patient.generalPractitioner[0].resolve() is Practitioner")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&patient, "Patient.generalPractitioner.resolve().name.family")?.to_strings()?, Some("Careful".to_string()));
+        assert_eq!(eval(&patient, "Patient.managingOrganization.resolve().id")?.to_strings()?, Some("p1".to_string()));
+
+        let mut resolver = MapResolver::new();
+        resolver.add(Organization { id: Some("o1".to_string()), name: Some(StringDt::new("ACME")), ..Default::default() })?;
+        let context = EvalContext::new(&patient).with_resolver(&resolver);
+        let name = Expr::parse("Patient.generalPractitioner.resolve().ofType(Organization).name".to_string())?
+            .eval_with(&patient, &context)?;
+        assert_eq!(name.to_strings()?, Some("ACME".to_string()));
+        let name = Expr::parse("'http://example.org/fhir/Organization/o1'.resolve().name".to_string())?.eval_with(&patient, &context)?;
+        assert_eq!(name.to_strings()?, Some("ACME".to_string()));
+
+        let entry = |full_url: Option<&str>, resource: AnyResource| BundleEntryBackboneElement {
+            full_url: full_url.map(UriDt::new),
+            resource: Some(resource),
+            ..Default::default()
+        };
+        let observation = |subject: &str| AnyResource::Observation(Observation { subject: Some(reference(subject)), ..Default::default() });
+        let bundle = Bundle {
+            entry: Some(vec![
+                entry(Some("urn:uuid:8f2e5c5a-0000-4000-8000-000000000001"),
+                      AnyResource::Patient(Patient { id: Some("p2".to_string()), gender: Some(CodeDt::new("female")), ..Default::default() })),
+                entry(Some("http://example.org/fhir/Patient/p3"),
+                      AnyResource::Patient(Patient { gender: Some(CodeDt::new("male")), ..Default::default() })),
+                entry(None, observation("urn:uuid:8f2e5c5a-0000-4000-8000-000000000001")),
+                entry(None, observation("Patient/p3")),
+                entry(None, observation("Patient/unknown")),
+            ]),
+            ..Default::default()
+        };
+
+        let genders = eval(&bundle, "Bundle.entry.resource.ofType(Observation).subject.resolve().gender")?;
+        let genders: Vec<String> = genders.iter().map(|gender| gender.to_strings()).collect::<Result<_>>()?;
+        assert_eq!(genders, vec!["female".to_string(), "male".to_string()]);
+        assert_eq!(eval(&bundle, "Bundle.entry[2].resource.subject.resolve() is Patient")?.to_boolean()?, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_user_defined_functions() -> Result<()> {
+        let mut functions = FunctionRegistry::new();
+        // 按YYYY-MM-DD计算周岁，参数为计算年龄的日期
+        functions.register("ageInYears", 1, 1, |input, args| {
+            let (birth, date) = match (input.to_strings()?, args[0].to_strings()?) {
+                (Some(birth), Some(date)) => (birth, date),
+                _ => return Ok(Collection::new()),
+            };
+            let year = |value: &str| value[..4].parse::<Integer>().unwrap_or_default();
+            let mut age = year(&date) - year(&birth);
+            if date[4..] < birth[4..] {
+                age -= 1;
+            }
+            Ok(Collection::new_integer(age))
+        });
+        // 基于本地代码表的memberOf
+        functions.register("memberOf", 1, 1, |input, args| {
+            let codes = match args[0].to_strings()?.as_deref() {
+                Some("http://example.org/ValueSet/local-gender") => vec!["male", "female"],
+                _ => return Ok(Collection::new()),
+            };
+            match input.to_strings()? {
+                Some(code) => Ok(Collection::new_boolean(codes.contains(&code.as_str()))),
+                None => Ok(Collection::new()),
+            }
+        });
+        assert_eq!(functions.len(), 2);
+
+        let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+        let context = EvalContext::new(&patient).with_functions(&functions);
+        let eval = |expression: &str| Expr::parse(expression.to_string())?.eval_with(&patient, &context);
+
+        assert_eq!(eval("Patient.birthDate.ageInYears(@2024-12-24)")?.to_integer()?, Some(49));
+        assert_eq!(eval("Patient.birthDate.ageInYears(@2024-12-25)")?.to_integer()?, Some(50));
+        assert_eq!(eval("Patient.where(birthDate.ageInYears(@2024-12-25) >= 18).exists()")?.to_boolean()?, Some(true));
+        assert_eq!(eval("Patient.gender.memberOf('http://example.org/ValueSet/local-gender')")?.to_boolean()?, Some(true));
+        assert_eq!(eval("Patient.gender.memberOf('http://example.org/ValueSet/unknown')")?.count(), 0);
+
+        let err = path_error(eval("Patient.birthDate.ageInYears()"));
+        assert_eq!(err.span, 18..28);
+        assert!(err.message.contains("ageInYears()至少拥有1个参数"));
+        assert!(Expr::parse("Patient.birthDate.ageInYears(@2024-12-25)".to_string())?.eval(&patient).is_err());
+
+        let analyzer = Analyzer::for_type("Patient")?.with_functions(&functions);
+        assert!(analyze(&analyzer, "Patient.birthDate.ageInYears(@2024-12-25) > 18")?.is_valid());
+        let analysis = analyze(&analyzer, "Patient.birthDate.ageInYears(Patient.foo, 1)")?;
+        let messages: Vec<&str> = analysis.errors.iter().map(|err| err.message.as_str()).collect();
+        assert_eq!(messages.len(), 2);
+        Ok(())
+    }
+
+    fn local_terminology() -> Result<LocalTerminology> {
+        let code_system: CodeSystem = from_json(r#"{
+            "resourceType": "CodeSystem",
+            "url": "http://example.org/CodeSystem/condition",
+            "status": "active",
+            "content": "complete",
+            "concept": [
+                {"code": "disease", "concept": [
+                    {"code": "infection", "concept": [{"code": "pneumonia"}, {"code": "influenza"}]},
+                    {"code": "diabetes"}
+                ]},
+                {"code": "injury"}
+            ]
+        }"#)?;
+        let infections: ValueSet = from_json(r#"{
+            "resourceType": "ValueSet",
+            "url": "http://example.org/ValueSet/infection",
+            "status": "active",
+            "compose": {"include": [{
+                "system": "http://example.org/CodeSystem/condition",
+                "filter": [{"property": "concept", "op": "is-a", "value": "infection"}]
+            }]}
+        }"#)?;
+        let non_influenza: ValueSet = from_json(r#"{
+            "resourceType": "ValueSet",
+            "url": "http://example.org/ValueSet/non-influenza",
+            "status": "active",
+            "compose": {
+                "include": [{"valueSet": ["http://example.org/ValueSet/infection"]}, {"system": "http://example.org/CodeSystem/condition", "concept": [{"code": "injury"}]}],
+                "exclude": [{"system": "http://example.org/CodeSystem/condition", "concept": [{"code": "influenza"}]}]
+            }
+        }"#)?;
+        let gender: ValueSet = from_json(r#"{
+            "resourceType": "ValueSet",
+            "url": "http://hl7.org/fhir/ValueSet/administrative-gender",
+            "status": "active",
+            "expansion": {"timestamp": "2024-01-01T00:00:00Z", "contains": [
+                {"system": "http://hl7.org/fhir/administrative-gender", "code": "male"},
+                {"system": "http://hl7.org/fhir/administrative-gender", "code": "female"}
+            ]}
+        }"#)?;
+
+        let mut terminology = LocalTerminology::new();
+        terminology.add_code_system(code_system)?;
+        terminology.add_value_set(infections)?;
+        terminology.add_value_set(non_influenza)?;
+        terminology.add_value_set(gender)?;
+        Ok(terminology)
+    }
+
+    #[test]
+    pub fn test_terminology_functions() -> Result<()> {
+        let terminology = local_terminology()?;
+        let coding = |code: &str| Coding::default()
+            .set_system(UriDt::new("http://example.org/CodeSystem/condition"))
+            .set_code(CodeDt::new(code));
+        let condition = Condition {
+            code: Some(CodeableConcept::default().add_coding(Coding::default().set_code(CodeDt::new("x"))).add_coding(coding("pneumonia"))),
+            category: Some(vec![CodeableConcept::default().add_coding(coding("influenza")), CodeableConcept::default().add_coding(coding("injury"))]),
+            ..Default::default()
+        };
+        let context = EvalContext::new(&condition).with_terminology(&terminology);
+        let eval = |expression: &str| Expr::parse(expression.to_string())?.eval_with(&condition, &context);
+
+        assert_eq!(eval("Condition.code.memberOf('http://example.org/ValueSet/infection')")?.to_boolean()?, Some(true));
+        assert_eq!(eval("Condition.code.coding[1].code.memberOf('http://example.org/ValueSet/infection')")?.to_boolean()?, Some(true));
+        assert_eq!(eval("Condition.category[0].memberOf('http://example.org/ValueSet/non-influenza|1.0')")?.to_boolean()?, Some(false));
+        assert_eq!(eval("Condition.category[1].memberOf('http://example.org/ValueSet/non-influenza')")?.to_boolean()?, Some(true));
+        assert_eq!(eval("Condition.category.where(memberOf('http://example.org/ValueSet/infection')).count()")?.to_integer()?, Some(1));
+        assert_eq!(eval("Condition.code.memberOf('http://example.org/ValueSet/unknown')")?.count(), 0);
+        assert_eq!(eval("'male'.memberOf(%vs-administrative-gender)")?.to_boolean()?, Some(true));
+        assert_eq!(eval("'unknown'.memberOf(%vs-administrative-gender)")?.to_boolean()?, Some(false));
+
+        assert_eq!(eval("Condition.code.subsumedBy(Condition.category[0])")?.count(), 0);
+        assert_eq!(eval("Condition.code.coding[1].subsumedBy(Condition.category[0])")?.to_boolean()?, Some(false));
+        assert_eq!(eval("Condition.category[0].coding.subsumes(Condition.code.coding[1])")?.to_boolean()?, Some(false));
+        assert_eq!(eval("Condition.category[1].subsumes(Condition.code.coding[1])")?.to_boolean()?, Some(false));
+        assert!(eval("Condition.category.subsumes(Condition.code)").is_err());
+
+        let disease = Condition {
+            code: Some(CodeableConcept::default().add_coding(coding("disease"))),
+            category: Some(vec![CodeableConcept::default().add_coding(coding("influenza"))]),
+            ..Default::default()
+        };
+        let context = EvalContext::new(&disease).with_terminology(&terminology);
+        let eval = |expression: &str| Expr::parse(expression.to_string())?.eval_with(&disease, &context);
+        assert_eq!(eval("Condition.code.subsumes(Condition.category.coding)")?.to_boolean()?, Some(true));
+        assert_eq!(eval("Condition.category.subsumedBy(Condition.code)")?.to_boolean()?, Some(true));
+        assert_eq!(eval("Condition.category.subsumes(Condition.code)")?.to_boolean()?, Some(false));
+
+        assert!(Expr::parse("Condition.code.memberOf('http://example.org/ValueSet/infection')".to_string())?.eval(&disease).is_err());
+        let analysis = analyze(&Analyzer::for_type("Condition")?, "Condition.code.memberOf('http://example.org/ValueSet/infection')")?;
+        assert_eq!(analysis.result.to_string(), "System.Boolean[0..1]");
+        Ok(())
+    }
+
+    fn patch_patient() -> Result<Patient> {
+        from_json(r#"{
+            "resourceType": "Patient",
+            "id": "p1",
+            "gender": "male",
+            "birthDate": "1974-12-25",
+            "name": [{"family": "Chalmers", "given": ["Peter", "James"]}],
+            "telecom": [{"system": "phone", "value": "(03) 5555 6473"}, {"system": "email", "value": "p@example.org"}]
+        }"#)
+    }
+
+    #[test]
+    pub fn test_fhirpath_patch() -> Result<()> {
+        let patch: Parameters = from_json(r#"{
+            "resourceType": "Parameters",
+            "parameter": [
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "add"}, {"name": "path", "valueString": "Patient"},
+                    {"name": "name", "valueString": "active"}, {"name": "value", "valueBoolean": true}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "insert"}, {"name": "path", "valueString": "Patient.name.given"},
+                    {"name": "index", "valueInteger": 1}, {"name": "value", "valueString": "Jim"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "delete"}, {"name": "path", "valueString": "Patient.telecom.where(system = 'email')"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "delete"}, {"name": "path", "valueString": "Patient.deceased"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "replace"}, {"name": "path", "valueString": "Patient.birthDate"},
+                    {"name": "value", "valueDate": "1974-12-24"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "replace"}, {"name": "path", "valueString": "Patient.gender"},
+                    {"name": "value", "valueString": "other"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "move"}, {"name": "path", "valueString": "Patient.name.given"},
+                    {"name": "source", "valueInteger": 0}, {"name": "destination", "valueInteger": 2}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "insert"}, {"name": "path", "valueString": "Patient.identifier"},
+                    {"name": "index", "valueInteger": 0}, {"name": "value", "valueIdentifier": {"system": "urn:oid:1.2.36.146.595.217.0.1", "value": "12345"}}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "add"}, {"name": "path", "valueString": "Patient"}, {"name": "name", "valueString": "contact"},
+                    {"name": "value", "part": [
+                        {"name": "name", "valueHumanName": {"family": "du March\u00e9"}},
+                        {"name": "gender", "valueCode": "female"}
+                    ]}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "add"}, {"name": "path", "valueString": "Patient"},
+                    {"name": "name", "valueString": "deceasedBoolean"}, {"name": "value", "valueBoolean": false}
+                ]}
+            ]
+        }"#)?;
+
+        let mut patient = patch_patient()?;
+        apply_patch(&mut patient, &patch).map_err(|errors| FhirError::Message(format!("{:?}", errors)))?;
+
+        assert_eq!(eval(&patient, "Patient.active")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&patient, "Patient.name.given.join(',')")?.to_strings()?, Some("Jim,James,Peter".to_string()));
+        assert_eq!(eval(&patient, "Patient.telecom.system")?.to_strings()?, Some("phone".to_string()));
+        assert_eq!(eval(&patient, "Patient.birthDate = @1974-12-24")?.to_boolean()?, Some(true));
+        assert_eq!(eval(&patient, "Patient.gender")?.to_strings()?, Some("other".to_string()));
+        assert_eq!(eval(&patient, "Patient.identifier.value")?.to_strings()?, Some("12345".to_string()));
+        assert_eq!(eval(&patient, "Patient.contact.name.family")?.to_strings()?, Some("du Marché".to_string()));
+        assert_eq!(eval(&patient, "Patient.contact.gender")?.to_strings()?, Some("female".to_string()));
+        assert_eq!(eval(&patient, "Patient.deceasedBoolean")?.to_boolean()?, Some(false));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_fhirpath_patch_errors() -> Result<()> {
+        let patch: Parameters = from_json(r#"{
+            "resourceType": "Parameters",
+            "parameter": [
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "replace"}, {"name": "path", "valueString": "Patient.name.given"},
+                    {"name": "value", "valueString": "Jim"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "delete"}, {"name": "path", "valueString": "Patient.telecom[0]"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "remove"}, {"name": "path", "valueString": "Patient.gender"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "add"}, {"name": "path", "valueString": "Patient"},
+                    {"name": "name", "valueString": "gender"}, {"name": "value", "valueCode": "female"}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "move"}, {"name": "path", "valueString": "Patient.name.given"},
+                    {"name": "source", "valueInteger": 0}, {"name": "destination", "valueInteger": 5}
+                ]},
+                {"name": "operation", "part": [
+                    {"name": "type", "valueCode": "replace"}, {"name": "path", "valueString": "Patient.name.given.first().length()"},
+                    {"name": "value", "valueInteger": 3}
+                ]}
+            ]
+        }"#)?;
+
+        let mut patient = patch_patient()?;
+        let errors = apply_patch(&mut patient, &patch).unwrap_err();
+        let failed: Vec<(usize, Option<&str>)> = errors.iter().map(|err| (err.index, err.operation)).collect();
+        assert_eq!(failed, vec![(0, Some("replace")), (2, None), (3, Some("add")), (4, Some("move")), (5, Some("replace"))]);
+        assert!(errors[0].to_string().contains("匹配到多个元素"));
+        assert!(errors[2].to_string().contains("只能有一个值"));
+
+        // 有操作失败时资源保持不变
+        assert_eq!(eval(&patient, "Patient.telecom.count()")?.to_integer()?, Some(2));
+        Ok(())
+    }
+}
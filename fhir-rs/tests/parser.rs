@@ -10,11 +10,9 @@ mod parser {
         let list = tokenizer.tokenize()?;
 
         let parser = Parser::new(list);
-        let plist = parser.parse()?;
+        let expr = parser.parse_expr()?;
 
-        for pt in plist.component {
-            println!("{:?}", pt);
-        };
+        println!("{:#?}", expr);
 
         Ok(())
     }
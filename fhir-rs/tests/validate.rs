@@ -13,5 +13,512 @@ pub fn test_validate() -> Result<()> {
 
     println!("Validate Outcome: {:?}", &outcome);
 
+    // 核心约束（如enc-2、dom-6）都应当能够执行，不能因为不支持的语法被跳过
+    let skipped: Vec<String> = outcome.items().iter()
+        .filter(|item| item.key.is_some() && matches!(item.status, ValidateStatus::Skip))
+        .map(|item| format!("{} {}: {}", item.key.as_deref().unwrap_or_default(), &item.path, &item.message))
+        .collect();
+    assert!(skipped.is_empty(), "{:#?}", skipped);
+
+    Ok(())
+}
+
+/// 测试用的Profile，只包含snapshot中的元素列表，名称取url的最后一段
+fn profile(url: &str, type_name: &str, elements: &str) -> Result<StructureDefinition> {
+    let kind = match TypeInfo::lookup(type_name).map(|type_info| &type_info.kind) {
+        Some(TypeKind::Resource) => "resource",
+        _ => "complex-type",
+    };
+    let name = url.rsplit('/').next().unwrap_or(url);
+    from_json(&format!(r#"{{
+        "resourceType": "StructureDefinition",
+        "url": "{url}",
+        "name": "{name}",
+        "status": "draft",
+        "kind": "{kind}",
+        "abstract": false,
+        "type": "{type_name}",
+        "snapshot": {{"element": {elements}}}
+    }}"#))
+}
+
+fn constraint_profile() -> Result<StructureDefinition> {
+    profile("http://example.org/StructureDefinition/patient-constraints", "Patient", r#"[
+        {
+            "id": "Patient",
+            "path": "Patient",
+            "min": 0,
+            "max": "*",
+            "constraint": [
+                {"key": "pat-1", "severity": "error", "human": "必须有姓名", "expression": "name.exists()"},
+                {"key": "pat-2", "severity": "error", "human": "必须有照片", "expression": "photo.exists()"}
+            ]
+        },
+        {
+            "id": "Patient.name",
+            "path": "Patient.name",
+            "min": 0,
+            "max": "*",
+            "constraint": [
+                {"key": "ele-1", "severity": "error", "human": "All FHIR elements must have a @value or children", "expression": "hasValue() or (children().count() > id.count())"},
+                {"key": "pat-3", "severity": "warning", "human": "姓名应当包含姓", "expression": "family.exists()"},
+                {"key": "pat-4", "severity": "error", "human": "无法执行的约束", "expression": "family.unknown()"}
+            ]
+        }
+    ]"#)
+}
+
+#[test]
+pub fn test_validate_constraints() -> Result<()> {
+    let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+
+    let mut validator = Validator::new(constraint_profile()?);
+    let outcome = validator.validate(&patient)?;
+
+    let statuses = |key: &str| -> Vec<String> {
+        outcome.items().iter()
+            .filter(|item| item.key.as_deref() == Some(key))
+            .map(|item| format!("{:?}", item.status))
+            .collect()
+    };
+
+    assert_eq!(statuses("pat-1"), vec!["Success"]);
+    assert_eq!(statuses("pat-2"), vec!["Error"]);
+    assert_eq!(statuses("ele-1"), vec!["Success", "Success", "Success"]);
+    assert_eq!(statuses("pat-3"), vec!["Success", "Warn", "Success"]);
+    assert_eq!(statuses("pat-4"), vec!["Skip", "Skip", "Skip"]);
+
+    let violation = outcome.items().iter().find(|item| item.key.as_deref() == Some("pat-2")).unwrap();
+    assert_eq!(violation.message, "违反约束[pat-2]: 必须有照片");
+    assert_eq!(violation.expression, "photo.exists()");
+    assert_eq!(outcome.error_count, 1);
+    assert_eq!(outcome.warn_count, 1);
+
+    let outcome: OperationOutcome = outcome.into();
+    let issue = outcome.issue.unwrap().into_iter()
+        .find(|issue| issue.diagnostics.as_ref().and_then(|text| text.value.as_deref()) == Some("违反约束[pat-2]: 必须有照片"))
+        .unwrap();
+    assert_eq!(issue.details.unwrap().coding.unwrap()[0].code.as_ref().and_then(|code| code.value.clone()), Some("pat-2".to_string()));
+    Ok(())
+}
+
+fn binding_terminology() -> Result<LocalTerminology> {
+    let mut terminology = LocalTerminology::new();
+    terminology.add_code_system(from_json(r#"{
+        "resourceType": "CodeSystem",
+        "url": "http://hl7.org/fhir/administrative-gender",
+        "status": "active",
+        "content": "complete",
+        "concept": [{"code": "male"}, {"code": "female"}, {"code": "other"}, {"code": "unknown"}]
+    }"#)?)?;
+    terminology.add_value_set(from_json(r#"{
+        "resourceType": "ValueSet",
+        "url": "http://hl7.org/fhir/ValueSet/administrative-gender",
+        "status": "active",
+        "compose": {"include": [{"system": "http://hl7.org/fhir/administrative-gender"}]}
+    }"#)?)?;
+    terminology.add_value_set(from_json(r#"{
+        "resourceType": "ValueSet",
+        "url": "http://example.org/ValueSet/female",
+        "status": "active",
+        "compose": {
+            "include": [{"system": "http://hl7.org/fhir/administrative-gender"}],
+            "exclude": [{"system": "http://hl7.org/fhir/administrative-gender", "concept": [{"code": "male"}]}]
+        }
+    }"#)?)?;
+    terminology.add_code_system(from_json(r#"{
+        "resourceType": "CodeSystem",
+        "url": "http://terminology.hl7.org/CodeSystem/v2-0131",
+        "status": "active",
+        "content": "complete",
+        "concept": [{"code": "C", "concept": [{"code": "N"}, {"code": "E"}]}, {"code": "U"}]
+    }"#)?)?;
+    terminology.add_value_set(from_json(r#"{
+        "resourceType": "ValueSet",
+        "url": "http://example.org/ValueSet/contact-role",
+        "status": "active",
+        "compose": {"include": [{
+            "system": "http://terminology.hl7.org/CodeSystem/v2-0131",
+            "filter": [{"property": "concept", "op": "is-a", "value": "C"}]
+        }]}
+    }"#)?)?;
+    Ok(terminology)
+}
+
+fn binding_profile(gender_value_set: &str) -> Result<StructureDefinition> {
+    profile("http://example.org/StructureDefinition/patient-bindings", "Patient", &format!(r#"[
+        {{"id": "Patient", "path": "Patient", "min": 0, "max": "*"}},
+        {{"id": "Patient.identifier", "path": "Patient.identifier", "min": 0, "max": "*"}},
+        {{
+            "id": "Patient.identifier.type", "path": "Patient.identifier.type", "min": 0, "max": "1",
+            "binding": {{"strength": "preferred", "valueSet": "http://example.org/ValueSet/contact-role"}}
+        }},
+        {{
+            "id": "Patient.gender", "path": "Patient.gender", "min": 0, "max": "1",
+            "binding": {{"strength": "required", "valueSet": "{}"}}
+        }},
+        {{"id": "Patient.contact", "path": "Patient.contact", "min": 0, "max": "*"}},
+        {{
+            "id": "Patient.contact.relationship", "path": "Patient.contact.relationship", "min": 0, "max": "*",
+            "binding": {{"strength": "extensible", "valueSet": "http://example.org/ValueSet/contact-role"}}
+        }},
+        {{
+            "id": "Patient.contact.gender", "path": "Patient.contact.gender", "min": 0, "max": "1",
+            "binding": {{"strength": "example", "valueSet": "http://example.org/ValueSet/female"}}
+        }}
+    ]"#, gender_value_set))
+}
+
+#[test]
+pub fn test_validate_bindings() -> Result<()> {
+    let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+    let terminology = std::sync::Arc::new(binding_terminology()?);
+
+    let statuses = |outcome: &ValidateResult, path: &str| -> Vec<String> {
+        outcome.items().iter()
+            .filter(|item| item.path == path && item.message.contains("值域"))
+            .map(|item| format!("{:?}", item.status))
+            .collect()
+    };
+
+    let mut validator = Validator::new(binding_profile("http://hl7.org/fhir/ValueSet/administrative-gender")?)
+        .with_terminology(terminology.clone());
+    let outcome = validator.validate(&patient)?;
+    assert_eq!(statuses(&outcome, "Patient.gender"), vec!["Success"]);
+    assert_eq!(statuses(&outcome, "Patient.contact.relationship"), vec!["Success"]);
+    assert_eq!(statuses(&outcome, "Patient.identifier.type"), vec!["Info"]);
+    assert!(statuses(&outcome, "Patient.contact.gender").is_empty());
+    assert_eq!(outcome.error_count, 0);
+
+    let mut validator = Validator::new(binding_profile("http://example.org/ValueSet/female")?)
+        .with_terminology(terminology.clone());
+    let outcome = validator.validate(&patient)?;
+    assert_eq!(statuses(&outcome, "Patient.gender"), vec!["Error"]);
+    assert_eq!(outcome.error_count, 1);
+
+    let mut validator = Validator::new(binding_profile("http://example.org/ValueSet/unknown")?)
+        .with_terminology(terminology);
+    let outcome = validator.validate(&patient)?;
+    assert_eq!(statuses(&outcome, "Patient.gender"), vec!["Skip"]);
+
+    let mut validator = Validator::new(binding_profile("http://hl7.org/fhir/ValueSet/administrative-gender")?);
+    let outcome = validator.validate(&patient)?;
+    assert_eq!(statuses(&outcome, "Patient.gender"), vec!["Skip"]);
+    Ok(())
+}
+
+fn slicing_profile(rules: &str, ordered: bool) -> Result<StructureDefinition> {
+    profile("http://example.org/StructureDefinition/patient-slicing", "Patient", &format!(r#"[
+        {{"id": "Patient", "path": "Patient", "min": 0, "max": "*"}},
+        {{
+            "id": "Patient.name",
+            "path": "Patient.name",
+            "min": 0,
+            "max": "*",
+            "slicing": {{"discriminator": [{{"type": "exists", "path": "period"}}], "rules": "open"}}
+        }},
+        {{
+            "id": "Patient.name.given",
+            "path": "Patient.name.given",
+            "min": 0,
+            "max": "*",
+            "slicing": {{"discriminator": [{{"type": "position", "path": "$this"}}], "rules": "open"}}
+        }},
+        {{"id": "Patient.name.given:first", "path": "Patient.name.given", "sliceName": "first", "min": 1, "max": "1"}},
+        {{"id": "Patient.name.given:second", "path": "Patient.name.given", "sliceName": "second", "min": 1, "max": "1"}},
+        {{"id": "Patient.name:current", "path": "Patient.name", "sliceName": "current", "min": 1, "max": "*"}},
+        {{"id": "Patient.name:current.period", "path": "Patient.name.period", "min": 0, "max": "0"}},
+        {{"id": "Patient.name:historic", "path": "Patient.name", "sliceName": "historic", "min": 0, "max": "1"}},
+        {{"id": "Patient.name:historic.period", "path": "Patient.name.period", "min": 1, "max": "1"}},
+        {{"id": "Patient.name:historic.family", "path": "Patient.name.family", "min": 1, "max": "1"}},
+        {{
+            "id": "Patient.telecom",
+            "path": "Patient.telecom",
+            "min": 0,
+            "max": "*",
+            "slicing": {{
+                "discriminator": [{{"type": "value", "path": "system"}}, {{"type": "pattern", "path": "use"}}],
+                "rules": "{rules}",
+                "ordered": {ordered}
+            }}
+        }},
+        {{"id": "Patient.telecom:mobile", "path": "Patient.telecom", "sliceName": "mobile", "min": 0, "max": "*"}},
+        {{"id": "Patient.telecom:mobile.system", "path": "Patient.telecom.system", "min": 1, "max": "1", "patternCode": "phone"}},
+        {{"id": "Patient.telecom:mobile.use", "path": "Patient.telecom.use", "min": 1, "max": "1", "patternCode": "mobile"}},
+        {{"id": "Patient.telecom:work", "path": "Patient.telecom", "sliceName": "work", "min": 1, "max": "1"}},
+        {{"id": "Patient.telecom:work.system", "path": "Patient.telecom.system", "min": 1, "max": "1", "fixedCode": "phone"}},
+        {{"id": "Patient.telecom:work.use", "path": "Patient.telecom.use", "min": 1, "max": "1", "fixedCode": "work"}},
+        {{
+            "id": "Patient.birthDate.extension",
+            "path": "Patient.birthDate.extension",
+            "min": 0,
+            "max": "*",
+            "slicing": {{"discriminator": [{{"type": "type", "path": "value"}}], "rules": "closed"}}
+        }},
+        {{"id": "Patient.birthDate.extension:time", "path": "Patient.birthDate.extension", "sliceName": "time", "min": 1, "max": "1"}},
+        {{"id": "Patient.birthDate.extension:time.value[x]", "path": "Patient.birthDate.extension.value[x]", "min": 1, "max": "1", "type": [{{"code": "dateTime"}}]}},
+        {{"id": "Patient.birthDate.extension:date", "path": "Patient.birthDate.extension", "sliceName": "date", "min": 0, "max": "1"}},
+        {{"id": "Patient.birthDate.extension:date.value[x]", "path": "Patient.birthDate.extension.value[x]", "min": 1, "max": "1", "type": [{{"code": "date"}}]}}
+    ]"#))
+}
+
+#[test]
+pub fn test_validate_slicing() -> Result<()> {
+    let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+
+    let statuses = |outcome: &ValidateResult, expression: &str| -> Vec<String> {
+        outcome.items().iter()
+            .filter(|item| item.expression == expression)
+            .map(|item| format!("{:?}", item.status))
+            .collect()
+    };
+    let rules = |outcome: &ValidateResult, path: &str| -> Vec<String> {
+        outcome.items().iter()
+            .filter(|item| item.expression == path && item.message.contains("切片"))
+            .map(|item| format!("{:?}", item.status))
+            .collect()
+    };
+
+    let mut validator = Validator::new(slicing_profile("open", false)?);
+    let outcome = validator.validate(&patient)?;
+    assert_eq!(statuses(&outcome, "Patient.name:current"), vec!["Success"]);
+    assert_eq!(statuses(&outcome, "Patient.name:current.period"), vec!["Success", "Success", "Success", "Success"]);
+    assert_eq!(statuses(&outcome, "Patient.name:historic.family"), vec!["Success", "Success"]);
+    assert_eq!(statuses(&outcome, "Patient.name.given:first"), vec!["Success", "Success"]);
+    // 位置切片在每个name下分别计算，usual只有一个given，缺少second切片
+    assert_eq!(statuses(&outcome, "Patient.name.given:second"), vec!["Error", "Success"]);
+    assert_eq!(statuses(&outcome, "Patient.telecom:work"), vec!["Success", "Success"]);
+    assert_eq!(statuses(&outcome, "Patient.telecom:mobile"), vec!["Success"]);
+    assert_eq!(statuses(&outcome, "Patient.birthDate.extension:time"), vec!["Success", "Success"]);
+    assert_eq!(statuses(&outcome, "Patient.birthDate.extension:date"), vec!["Success", "Success"]);
+    assert_eq!(rules(&outcome, "Patient.telecom"), vec!["Success"]);
+    assert_eq!(rules(&outcome, "Patient.birthDate.extension"), vec!["Success"]);
+    assert_eq!(outcome.error_count, 1);
+
+    let mut validator = Validator::new(slicing_profile("closed", false)?);
+    let outcome = validator.validate(&patient)?;
+    assert_eq!(rules(&outcome, "Patient.telecom"), vec!["Error", "Error"]);
+    assert_eq!(outcome.error_count, 3);
+
+    let mut validator = Validator::new(slicing_profile("openAtEnd", true)?);
+    let outcome = validator.validate(&patient)?;
+    let errors: Vec<&str> = outcome.items().iter()
+        .filter(|item| item.expression == "Patient.telecom" && matches!(item.status, ValidateStatus::Error))
+        .map(|item| item.message.as_str())
+        .collect();
+    assert_eq!(errors, vec![
+        "第0个元素不属于任何切片，切片规则为openAtEnd，这类元素只能出现在最后",
+        "切片[mobile]的元素出现在切片[work]之后，切片的顺序与定义不一致",
+    ]);
+    Ok(())
+}
+
+fn value_profile() -> Result<StructureDefinition> {
+    profile("http://example.org/StructureDefinition/patient-values", "Patient", r#"[
+        {"id": "Patient", "path": "Patient", "min": 0, "max": "*"},
+        {"id": "Patient.identifier", "path": "Patient.identifier", "min": 0, "max": "*"},
+        {
+            "id": "Patient.identifier.type",
+            "path": "Patient.identifier.type",
+            "min": 0,
+            "max": "1",
+            "patternCodeableConcept": {"coding": [{"system": "http://terminology.hl7.org/CodeSystem/v2-0203", "code": "MR"}]}
+        },
+        {"id": "Patient.active", "path": "Patient.active", "min": 0, "max": "1", "fixedBoolean": false},
+        {"id": "Patient.gender", "path": "Patient.gender", "min": 0, "max": "1", "fixedCode": "male"},
+        {"id": "Patient.birthDate", "path": "Patient.birthDate", "min": 0, "max": "1", "fixedDate": "1974-12-25"},
+        {
+            "id": "Patient.address",
+            "path": "Patient.address",
+            "min": 0,
+            "max": "*",
+            "fixedAddress": {"use": "home", "type": "both", "city": "PleasantVille"}
+        },
+        {"id": "Patient.contact", "path": "Patient.contact", "min": 0, "max": "*"},
+        {
+            "id": "Patient.contact.relationship",
+            "path": "Patient.contact.relationship",
+            "min": 0,
+            "max": "*",
+            "patternCodeableConcept": {"coding": [{"system": "http://terminology.hl7.org/CodeSystem/v2-0131", "code": "C"}]}
+        },
+        {
+            "id": "Patient.contact.address",
+            "path": "Patient.contact.address",
+            "min": 0,
+            "max": "1",
+            "patternAddress": {"use": "home", "city": "PleasantVille", "period": {"start": "1974-12-25"}}
+        }
+    ]"#)
+}
+
+#[test]
+pub fn test_validate_fixed_and_pattern() -> Result<()> {
+    let patient: Patient = from_xml(include_str!("patient-example.xml"))?;
+
+    let mut validator = Validator::new(value_profile()?);
+    let outcome = validator.validate(&patient)?;
+
+    let messages = |path: &str| -> Vec<String> {
+        outcome.items().iter()
+            .filter(|item| item.path == path && (item.message.contains("固定值") || item.message.contains("模式值")))
+            .map(|item| format!("{:?}: {}", item.status, item.message))
+            .collect()
+    };
+
+    assert_eq!(messages("Patient.identifier.type"), vec!["Success: 符合模式值要求。"]);
+    assert_eq!(messages("Patient.gender"), vec!["Success: 符合固定值要求。"]);
+    assert_eq!(messages("Patient.birthDate"), vec!["Success: 符合固定值要求。"]);
+    assert_eq!(messages("Patient.contact.address"), vec!["Success: 符合模式值要求。"]);
+    assert_eq!(messages("Patient.active"), vec!["Error: 与固定值不一致: 期望为[false]，实际为[true]"]);
+    assert_eq!(messages("Patient.contact.relationship"), vec!["Error: 与模式值不一致: coding[0].code: 期望为[C]，实际为[N]"]);
+
+    let address = messages("Patient.address");
+    assert_eq!(address.len(), 1);
+    assert!(address[0].starts_with("Error: 与固定值不一致: "));
+    assert!(address[0].contains("line: 期望有0个值，实际为1个"));
+    assert!(address[0].contains("postalCode: 期望有0个值，实际为1个"));
+    assert!(!address[0].contains("city"));
+    assert_eq!(outcome.error_count, 3);
+    Ok(())
+}
+
+fn type_profile(deceased_types: &str) -> Result<StructureDefinition> {
+    profile("http://example.org/StructureDefinition/patient-types", "Patient", &format!(r#"[
+        {{"id": "Patient", "path": "Patient", "min": 0, "max": "*"}},
+        {{
+            "id": "Patient.identifier",
+            "path": "Patient.identifier",
+            "min": 0,
+            "max": "*",
+            "type": [{{"code": "Identifier", "profile": ["http://example.org/StructureDefinition/local-identifier"]}}]
+        }},
+        {{
+            "id": "Patient.name",
+            "path": "Patient.name",
+            "min": 0,
+            "max": "*",
+            "type": [{{"code": "HumanName", "profile": ["http://example.org/StructureDefinition/unknown-name"]}}]
+        }},
+        {{"id": "Patient.deceased[x]", "path": "Patient.deceased[x]", "min": 0, "max": "1", "type": [{deceased_types}]}},
+        {{
+            "id": "Patient.generalPractitioner",
+            "path": "Patient.generalPractitioner",
+            "min": 0,
+            "max": "*",
+            "type": [{{
+                "code": "Reference",
+                "targetProfile": [
+                    "http://hl7.org/fhir/StructureDefinition/Practitioner",
+                    "http://hl7.org/fhir/StructureDefinition/Organization"
+                ]
+            }}]
+        }},
+        {{
+            "id": "Patient.managingOrganization",
+            "path": "Patient.managingOrganization",
+            "min": 0,
+            "max": "1",
+            "type": [{{"code": "Reference", "targetProfile": ["http://example.org/StructureDefinition/local-organization"]}}]
+        }},
+        {{"id": "Patient.link", "path": "Patient.link", "min": 0, "max": "*"}},
+        {{
+            "id": "Patient.link.other",
+            "path": "Patient.link.other",
+            "min": 1,
+            "max": "1",
+            "type": [{{"code": "Reference", "targetProfile": ["http://example.org/StructureDefinition/local-related-person"]}}]
+        }}
+    ]"#))
+}
+
+fn identifier_profile() -> Result<StructureDefinition> {
+    profile("http://example.org/StructureDefinition/local-identifier", "Identifier", r#"[
+        {"id": "Identifier", "path": "Identifier", "min": 0, "max": "*"},
+        {"id": "Identifier.system", "path": "Identifier.system", "min": 1, "max": "1", "fixedUri": "urn:oid:1.2.3"},
+        {"id": "Identifier.value", "path": "Identifier.value", "min": 1, "max": "1", "fixedString": "54321"}
+    ]"#)
+}
+
+fn organization_profile() -> Result<StructureDefinition> {
+    profile("http://example.org/StructureDefinition/local-organization", "Organization", r#"[
+        {"id": "Organization", "path": "Organization", "min": 0, "max": "*"}
+    ]"#)
+}
+
+#[test]
+pub fn test_validate_types() -> Result<()> {
+    let mut patient: Patient = from_json(r##"{
+        "resourceType": "Patient",
+        "id": "p1",
+        "identifier": [{"system": "urn:oid:1.2.3", "value": "12345"}],
+        "name": [{"family": "Chalmers"}],
+        "deceasedDateTime": "2019-11-11T12:23:34Z",
+        "generalPractitioner": [
+            {"reference": "#gp1"},
+            {"reference": "Organization/1"},
+            {"reference": "urn:uuid:04121321-4af5-424c-a0e1-ed3aab1c349d"},
+            {"reference": "Patient/2"}
+        ],
+        "managingOrganization": {"reference": "http://example.org/fhir/Organization/1"},
+        "link": [{"other": {"reference": "RelatedPerson/r1"}, "type": "seealso"}]
+    }"##)?;
+    let practitioner: Practitioner = from_json(r#"{"resourceType": "Practitioner", "id": "gp1"}"#)?;
+    patient.contained = Some(vec![AnyResource::Practitioner(practitioner)]);
+
+    let related: RelatedPerson = from_json(r#"{
+        "resourceType": "RelatedPerson",
+        "id": "r1",
+        "meta": {"profile": ["http://example.org/StructureDefinition/local-related-person"]},
+        "patient": {"reference": "Patient/p1"}
+    }"#)?;
+    let mut resolver = MapResolver::new();
+    resolver.add(related)?;
+
+    let messages = |outcome: &ValidateResult, path: &str| -> Vec<String> {
+        outcome.items().iter()
+            .filter(|item| item.path == path && (item.message.contains("类型") || item.message.contains("Profile") || item.message.contains("固定值")))
+            .map(|item| format!("{:?}: {}", item.status, item.message))
+            .collect()
+    };
+
+    let mut validator = Validator::new(type_profile(r#"{"code": "boolean"}"#)?)
+        .with_resolver(resolver)
+        .with_profile(identifier_profile()?)
+        .with_profile(organization_profile()?);
+    let outcome = validator.validate(&patient)?;
+
+    assert_eq!(messages(&outcome, "Patient.deceased[x]"), vec!["Error: 类型[dateTime]不在允许的类型[boolean]中。"]);
+    assert_eq!(messages(&outcome, "Patient.generalPractitioner"), vec![
+        "Success: 引用的目标类型[Practitioner]符合要求。",
+        "Success: 引用的目标类型[Organization]符合要求。",
+        "Skip: 无法确定引用[urn:uuid:04121321-4af5-424c-a0e1-ed3aab1c349d]的目标类型。",
+        "Error: 引用的目标类型[Patient]不在允许的范围[http://hl7.org/fhir/StructureDefinition/Practitioner, http://hl7.org/fhir/StructureDefinition/Organization]中。",
+    ]);
+    assert_eq!(messages(&outcome, "Patient.managingOrganization"), vec!["Success: 引用的目标类型[Organization]符合要求。"]);
+    assert_eq!(messages(&outcome, "Patient.link.other"), vec!["Success: 引用的目标类型[RelatedPerson]符合要求。"]);
+    assert_eq!(messages(&outcome, "Patient.name"), vec!["Skip: 缺少Profile[http://example.org/StructureDefinition/unknown-name]的定义，无法校验。"]);
+    assert_eq!(messages(&outcome, "Patient.identifier.system"), vec!["Success: 符合固定值要求。"]);
+    assert_eq!(messages(&outcome, "Patient.identifier.value"), vec!["Error: 与固定值不一致: 期望为[54321]，实际为[12345]"]);
+    assert_eq!(outcome.error_count, 3);
+
+    // 只有differential的Profile无法校验，跳过而不是中断整个校验
+    let name_profile: StructureDefinition = from_json(r#"{
+        "resourceType": "StructureDefinition",
+        "url": "http://example.org/StructureDefinition/unknown-name",
+        "name": "UnknownName",
+        "status": "draft",
+        "kind": "complex-type",
+        "abstract": false,
+        "type": "HumanName",
+        "differential": {"element": [{"id": "HumanName.family", "path": "HumanName.family", "min": 1}]}
+    }"#)?;
+    let mut validator = Validator::new(type_profile(r#"{"code": "boolean"}, {"code": "dateTime"}"#)?)
+        .with_profile(name_profile);
+    let outcome = validator.validate(&patient)?;
+    assert_eq!(messages(&outcome, "Patient.name"), vec!["Skip: Profile[http://example.org/StructureDefinition/unknown-name]中没有snapshot，无法校验。"]);
+    assert_eq!(messages(&outcome, "Patient.deceased[x]"), vec!["Success: 符合类型[dateTime]要求。"]);
+    // 没有解析器时无法读取被引用资源声明的Profile，而该Profile的定义也未加载，无法判断是否符合
+    assert_eq!(messages(&outcome, "Patient.link.other"), vec!["Skip: 缺少Profile[http://example.org/StructureDefinition/local-related-person]的定义，无法校验。"]);
     Ok(())
-}
\ No newline at end of file
+}